@@ -0,0 +1,26 @@
+use mtls_message::{crypto::SigningKey, handshake::certificate_request::CertificateRequestPayload};
+
+/// Certificate chain a client authenticates with, and the private key of its end-entity
+/// certificate.
+#[derive(Clone, Copy)]
+pub struct ClientCertificate<'a> {
+    /// DER encoded certificates, from the end-entity towards the trust anchor.
+    pub chain: &'a [&'a [u8]],
+    /// Signs the CertificateVerify, and limits the schemes offered for it.
+    pub key: &'a dyn SigningKey,
+}
+
+/// Chooses the certificate a client answers a CertificateRequest with.
+///
+/// [`ClientCertificate`] is the simplest implementation, always offering the same certificate.
+pub trait ResolvesClientCert {
+    /// Returns the certificate to send for `request`, or `None` to send an empty Certificate
+    /// message. The server may name the certificate authorities it accepts in the request.
+    fn resolve(&self, request: &CertificateRequestPayload<'_>) -> Option<ClientCertificate<'_>>;
+}
+
+impl<'a> ResolvesClientCert for ClientCertificate<'a> {
+    fn resolve(&self, _request: &CertificateRequestPayload<'_>) -> Option<ClientCertificate<'_>> {
+        Some(*self)
+    }
+}
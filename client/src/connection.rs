@@ -0,0 +1,929 @@
+use core::ops::Range;
+use managed::ManagedSlice;
+use mtls_message::{
+    crypto::{Aead, Hash},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::{
+        deframer::HandshakeDeframer, enums::ProtocolVersion, message::HandshakeMessage,
+        transcript::Transcript,
+    },
+    record::{
+        change_cipher_spec::{ChangeCipherSpecFilter, CHANGE_CIPHER_SPEC},
+        protection::RecordProtection,
+        ContentType, RecordHeader, MAX_CIPHERTEXT_LEN, MAX_FRAGMENT_LEN, RECORD_HEADER_SIZE,
+    },
+    Codec, CodecSized, Decoder, Encoder,
+};
+
+/// Largest number of bytes of each buffer in use at any one time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BufferUsage {
+    pub rx: usize,
+    pub tx: usize,
+    pub handshake: usize,
+}
+
+const HANDSHAKE_HEADER_SIZE: usize = 4;
+
+/// Statically sized storage for the buffers of a [`ClientConnection`].
+///
+/// `RX` must hold the largest record the peer sends, `TX` the records queued between calls to
+/// [`ClientConnection::write_tls`] and `HS` the largest handshake message the peer sends.
+pub struct Buffers<const RX: usize, const TX: usize, const HS: usize> {
+    rx: [u8; RX],
+    tx: [u8; TX],
+    handshake: [u8; HS],
+}
+
+impl<const RX: usize, const TX: usize, const HS: usize> Buffers<RX, TX, HS> {
+    pub fn new() -> Self {
+        Self {
+            rx: [0; RX],
+            tx: [0; TX],
+            handshake: [0; HS],
+        }
+    }
+
+    pub fn connection<A: Aead>(&mut self) -> ClientConnection<'_, A> {
+        ClientConnection::new(self.rx.as_mut(), self.tx.as_mut(), self.handshake.as_mut())
+    }
+}
+
+impl<const RX: usize, const TX: usize, const HS: usize> Default for Buffers<RX, TX, HS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Record layer of a client connection.
+///
+/// Records received from the server are buffered in the receive buffer until they are complete,
+/// and handshake messages are reassembled from them in the handshake buffer. Records to send are
+/// queued in the send buffer.
+pub struct ClientConnection<'a, A: Aead> {
+    rx: ManagedSlice<'a, u8>,
+    /// Unconsumed handshake content of the record being processed, decrypted in place.
+    content: Range<usize>,
+    /// Start of the records that haven't been processed yet.
+    rx_start: usize,
+    rx_end: usize,
+    tx: ManagedSlice<'a, u8>,
+    tx_len: usize,
+    handshake: HandshakeDeframer<'a>,
+    read_protection: Option<RecordProtection<A>>,
+    write_protection: Option<RecordProtection<A>>,
+    change_cipher_spec: ChangeCipherSpecFilter,
+    peak_usage: BufferUsage,
+}
+
+impl<'a, A: Aead> ClientConnection<'a, A> {
+    pub fn new<RX, TX, HS>(rx: RX, tx: TX, handshake: HS) -> Self
+    where
+        RX: Into<ManagedSlice<'a, u8>>,
+        TX: Into<ManagedSlice<'a, u8>>,
+        HS: Into<ManagedSlice<'a, u8>>,
+    {
+        Self {
+            rx: rx.into(),
+            content: 0..0,
+            rx_start: 0,
+            rx_end: 0,
+            tx: tx.into(),
+            tx_len: 0,
+            handshake: HandshakeDeframer::new(handshake),
+            read_protection: None,
+            write_protection: None,
+            change_cipher_spec: ChangeCipherSpecFilter::new(),
+            peak_usage: BufferUsage::default(),
+        }
+    }
+
+    /// Protects records received from now on.
+    ///
+    /// Records longer than the limit set on `protection` are rejected with
+    /// [`TlsError::RecordOverflow`].
+    pub fn set_read_protection(&mut self, protection: RecordProtection<A>) {
+        self.read_protection = Some(protection);
+    }
+
+    /// Protects records sent from now on.
+    ///
+    /// Handshake messages are fragmented to fit the limit set on `protection`.
+    pub fn set_write_protection(&mut self, protection: RecordProtection<A>) {
+        self.write_protection = Some(protection);
+    }
+
+    /// Marks the end of the handshake, once the server's Finished was received. The server may
+    /// no longer send a ChangeCipherSpec record (RFC 8446, section 5).
+    pub fn complete_handshake(&mut self) {
+        self.change_cipher_spec.handshake_complete();
+    }
+
+    /// Applies the limits negotiated with the record_size_limit extension (RFC 8449) to the
+    /// current protection in each direction. `read` is the limit the client advertised, and
+    /// `write` the one the server advertised.
+    pub fn set_record_size_limits(&mut self, read: u16, write: u16) -> TlsResult<()> {
+        match (&mut self.read_protection, &mut self.write_protection) {
+            (Some(read_protection), Some(write_protection)) => {
+                read_protection.set_record_size_limit(read)?;
+                write_protection.set_record_size_limit(write)
+            }
+            // The limits only apply to protected records (RFC 8449, section 4).
+            _ => Err(TlsError::InternalError("records aren't protected")),
+        }
+    }
+
+    /// Whether handshake content received after the last returned message is buffered, in the
+    /// same record or as part of a message. Keys must only change at a record boundary (RFC 8446,
+    /// section 5.1).
+    pub fn has_pending_handshake(&self) -> bool {
+        !self.content.is_empty() || self.handshake.has_partial()
+    }
+
+    /// Largest number of bytes of each buffer used so far.
+    pub fn peak_usage(&self) -> BufferUsage {
+        self.peak_usage
+    }
+
+    /// Buffers bytes received from the server, returning how many were taken.
+    ///
+    /// Fewer bytes than given are taken when the receive buffer is full, in which case the
+    /// buffered records should be processed before calling this again.
+    pub fn read_tls(&mut self, bytes: &[u8]) -> TlsResult<usize> {
+        self.compact_rx();
+        let len = match self.rx {
+            ManagedSlice::Borrowed(ref buf) => bytes.len().min(buf.len() - self.rx_end),
+            ManagedSlice::Owned(ref mut buf) => {
+                buf.resize(self.rx_end + bytes.len(), 0);
+                bytes.len()
+            }
+        };
+
+        self.rx[self.rx_end..self.rx_end + len].copy_from_slice(&bytes[..len]);
+        self.rx_end += len;
+        self.peak_usage.rx = self.peak_usage.rx.max(self.rx_end);
+
+        if let Some(header) = self.next_record_header() {
+            if RECORD_HEADER_SIZE + usize::from(header.len) > self.rx_capacity() {
+                return Err(TlsError::InternalError("record exceeds receive buffer"));
+            }
+        }
+        Ok(len)
+    }
+
+    /// Returns the next complete handshake message received from the server, processing
+    /// buffered records as needed.
+    ///
+    /// Messages that arrive whole in a record are returned from the receive buffer, only
+    /// fragmented messages are copied into the handshake buffer to be reassembled.
+    pub fn next_handshake_message(&mut self) -> TlsResult<Option<&[u8]>> {
+        loop {
+            if self.handshake.has_message() {
+                return Ok(self.handshake.next_message());
+            }
+
+            if self.content.is_empty() {
+                if !self.process_record()? {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            if self.handshake.has_partial() {
+                let len = self.handshake.missing().min(self.content.len());
+                self.push_content(len)?;
+                continue;
+            }
+
+            match self.whole_message_len() {
+                Some(len) => {
+                    let start = self.content.start;
+                    self.content.start += len;
+                    return Ok(Some(&self.rx[start..start + len]));
+                }
+                None => self.push_content(self.content.len())?,
+            }
+        }
+    }
+
+    /// Encodes a handshake message, adding it to the transcript, and queues it to be sent in as
+    /// many records as needed.
+    pub fn send_handshake<H: Hash>(
+        &mut self,
+        msg: &HandshakeMessage<'_>,
+        transcript: &mut Transcript<'_, H>,
+    ) -> TlsResult<()> {
+        let len = msg.data_size();
+        let ty = ContentType::Handshake;
+        let max_len = self.max_fragment_len();
+        let records = len.div_ceil(max_len);
+        let fragment_len = |i: usize| (len - i * max_len).min(max_len);
+        let total_len = (0..records)
+            .map(|i| self.record_len(ty, fragment_len(i)))
+            .sum::<usize>();
+        self.reserve_tx(total_len)?;
+
+        // The message is encoded in one piece after the first record header, then fragments are
+        // moved into place from the last so none are overwritten.
+        let start = self.tx_len;
+        let msg_start = start + RECORD_HEADER_SIZE;
+        {
+            let mut enc = Encoder::new(&mut self.tx[msg_start..msg_start + len]);
+            transcript.encode_message(msg, &mut enc)?;
+        }
+
+        let mut offset = start + total_len;
+        for i in (0..records).rev() {
+            offset -= self.record_len(ty, fragment_len(i));
+            let fragment_start = msg_start + i * max_len;
+            self.tx.copy_within(
+                fragment_start..fragment_start + fragment_len(i),
+                offset + RECORD_HEADER_SIZE,
+            );
+        }
+
+        for i in 0..records {
+            offset += self.seal_record(ty, offset, fragment_len(i))?;
+        }
+
+        self.tx_len = offset;
+        self.peak_usage.tx = self.peak_usage.tx.max(self.tx_len);
+        Ok(())
+    }
+
+    /// Queues the dummy ChangeCipherSpec record of middlebox compatibility mode (RFC 8446,
+    /// appendix D.4), which is never protected.
+    pub fn send_change_cipher_spec(&mut self) -> TlsResult<()> {
+        let len = RECORD_HEADER_SIZE + CHANGE_CIPHER_SPEC.len();
+        self.reserve_tx(len)?;
+
+        let mut enc = Encoder::new(&mut self.tx[self.tx_len..self.tx_len + len]);
+        RecordHeader {
+            ty: ContentType::ChangeCipherSpec,
+            version: ProtocolVersion::TLSv1_2,
+            len: CHANGE_CIPHER_SPEC.len() as u16,
+        }
+        .encode(&mut enc)?;
+        enc.append(CHANGE_CIPHER_SPEC)?;
+
+        self.tx_len += len;
+        self.peak_usage.tx = self.peak_usage.tx.max(self.tx_len);
+        Ok(())
+    }
+
+    /// Whether there are records waiting to be written.
+    pub fn wants_write(&self) -> bool {
+        self.tx_len != 0
+    }
+
+    /// Moves queued records to `out`, returning how many bytes were written.
+    pub fn write_tls(&mut self, out: &mut [u8]) -> usize {
+        let len = out.len().min(self.tx_len);
+        out[..len].copy_from_slice(&self.tx[..len]);
+        self.tx.copy_within(len..self.tx_len, 0);
+        self.tx_len -= len;
+        len
+    }
+
+    /// Processes the next buffered record, returning whether there was a complete record.
+    fn process_record(&mut self) -> TlsResult<bool> {
+        let header = match self.next_record_header() {
+            Some(header) => header,
+            None => return Ok(false),
+        };
+        let max_len = match self.read_protection {
+            Some(ref prot) => prot.max_ciphertext_len(),
+            None => MAX_FRAGMENT_LEN,
+        };
+        if usize::from(header.len) > max_len {
+            return Err(TlsError::RecordOverflow);
+        }
+
+        let record_start = self.rx_start;
+        let record_len = RECORD_HEADER_SIZE + usize::from(header.len);
+        if self.rx_end - record_start < record_len {
+            return Ok(false);
+        }
+        self.rx_start += record_len;
+
+        // A server in middlebox compatibility mode sends a ChangeCipherSpec record during the
+        // handshake, unprotected even once the handshake is.
+        if header.ty == ContentType::ChangeCipherSpec {
+            // Handshake messages can't be interleaved with other records (RFC 8446, section 5.1).
+            if self.handshake.has_partial() {
+                return Err(TlsError::UnexpectedMessage);
+            }
+            let content_start = record_start + RECORD_HEADER_SIZE;
+            self.change_cipher_spec
+                .discard(&self.rx[content_start..record_start + record_len])?;
+            return Ok(true);
+        }
+
+        let record = &mut self.rx[record_start..record_start + record_len];
+        let (ty, len) = match self.read_protection {
+            Some(ref mut prot) => prot.open(record).map(|(ty, c)| (ty, c.len()))?,
+            None => (header.ty, usize::from(header.len)),
+        };
+
+        // Alerts and application data aren't handled yet.
+        if ty != ContentType::Handshake || len == 0 {
+            return Err(TlsError::UnexpectedMessage);
+        }
+
+        let content_start = record_start + RECORD_HEADER_SIZE;
+        self.content = content_start..content_start + len;
+        Ok(true)
+    }
+
+    /// Length of the handshake message at the start of the record content, if all of it is
+    /// there.
+    fn whole_message_len(&self) -> Option<usize> {
+        let content = &self.rx[self.content.clone()];
+        let header = content.get(..HANDSHAKE_HEADER_SIZE)?;
+        let len = HANDSHAKE_HEADER_SIZE
+            + header[1..]
+                .iter()
+                .fold(0, |len, b| (len << 8) | usize::from(*b));
+
+        if content.len() < len {
+            return None;
+        }
+        Some(len)
+    }
+
+    /// Moves `len` bytes of record content into the handshake buffer.
+    fn push_content(&mut self, len: usize) -> TlsResult<()> {
+        let start = self.content.start;
+        self.content.start += len;
+        self.handshake
+            .push(ContentType::Handshake, &self.rx[start..start + len])?;
+        self.peak_usage.handshake = self.peak_usage.handshake.max(self.handshake.buffered());
+        Ok(())
+    }
+
+    /// Largest content that may be sent in a record, as agreed with the server.
+    fn max_fragment_len(&self) -> usize {
+        match self.write_protection {
+            Some(ref prot) => prot.max_fragment_len(),
+            None => MAX_FRAGMENT_LEN,
+        }
+    }
+
+    /// Size of the record that carries `len` bytes of content.
+    fn record_len(&self, ty: ContentType, len: usize) -> usize {
+        match self.write_protection {
+            Some(ref prot) => prot.sealed_len(ty, len),
+            None => RECORD_HEADER_SIZE + len,
+        }
+    }
+
+    /// Writes the header of the record at `offset` whose content is already in place, sealing
+    /// it if records are being protected. Returns the size of the record.
+    fn seal_record(&mut self, ty: ContentType, offset: usize, len: usize) -> TlsResult<usize> {
+        match self.write_protection {
+            Some(ref mut prot) => prot.seal(ty, &mut self.tx[offset..], len),
+            None => {
+                let header = RecordHeader {
+                    ty,
+                    version: ProtocolVersion::TLSv1_2,
+                    len: len as u16,
+                };
+                header.encode(&mut Encoder::new(
+                    &mut self.tx[offset..offset + RECORD_HEADER_SIZE],
+                ))?;
+                Ok(RECORD_HEADER_SIZE + len)
+            }
+        }
+    }
+
+    fn next_record_header(&self) -> Option<RecordHeader> {
+        RecordHeader::decode(&mut Decoder::new(&self.rx[self.rx_start..self.rx_end]))
+    }
+
+    fn rx_capacity(&self) -> usize {
+        match self.rx {
+            ManagedSlice::Borrowed(ref buf) => buf.len(),
+            ManagedSlice::Owned(_) => RECORD_HEADER_SIZE + MAX_CIPHERTEXT_LEN,
+        }
+    }
+
+    /// Moves unconsumed content and unprocessed records to the start of the receive buffer.
+    fn compact_rx(&mut self) {
+        if self.content.is_empty() {
+            self.content = self.rx_start..self.rx_start;
+        }
+
+        let start = self.content.start;
+        self.rx.copy_within(start..self.rx_end, 0);
+        self.content = 0..self.content.len();
+        self.rx_start -= start;
+        self.rx_end -= start;
+    }
+
+    fn reserve_tx(&mut self, len: usize) -> TlsResult<()> {
+        let needed = self.tx_len + len;
+        match self.tx {
+            ManagedSlice::Borrowed(ref buf) if buf.len() < needed => {
+                Err(TlsError::InternalError("message exceeds send buffer"))
+            }
+            ManagedSlice::Borrowed(_) => Ok(()),
+            ManagedSlice::Owned(ref mut buf) => {
+                if buf.len() < needed {
+                    buf.resize(needed, 0);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mtls_message::testing::{RingAead, Sha256};
+    use mtls_message::{
+        handshake::{
+            enums::{HandshakeType, MaxFragmentLength},
+            message::HandshakePayload,
+        },
+        record::padding::PaddingPolicy,
+    };
+    use std::vec::Vec;
+
+    const FINISHED: [u8; 7] = [0x14, 0, 0, 3, 1, 2, 3];
+    const KEY_UPDATE: [u8; 5] = [0x18, 0, 0, 1, 0];
+
+    fn owned() -> ManagedSlice<'static, u8> {
+        ManagedSlice::Owned(vec![])
+    }
+
+    fn connection() -> ClientConnection<'static, RingAead> {
+        ClientConnection::new(vec![], vec![], vec![])
+    }
+
+    fn protection() -> RecordProtection<RingAead> {
+        RecordProtection::new(RingAead::aes_128_gcm(&[7; 16]), [9; 12])
+    }
+
+    fn record(ty: ContentType, content: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![u8::from(ty), 3, 3, 0, 0];
+        bytes[3..5].copy_from_slice(&(content.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(content);
+        bytes
+    }
+
+    fn send(conn: &mut ClientConnection<'_, RingAead>, payload: &[u8]) -> Vec<u8> {
+        let msg = HandshakeMessage {
+            ty: HandshakeType::Finished,
+            payload: HandshakePayload::Opaque(payload),
+        };
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        conn.send_handshake(&msg, &mut transcript).unwrap();
+
+        let mut out = vec![0; 2 * MAX_CIPHERTEXT_LEN];
+        let len = conn.write_tls(&mut out);
+        out.truncate(len);
+        out
+    }
+
+    fn receive_all(conn: &mut ClientConnection<'_, RingAead>, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut messages = vec![];
+        let mut offset = 0;
+        loop {
+            offset += conn.read_tls(&bytes[offset..]).unwrap();
+            while let Some(msg) = conn.next_handshake_message().unwrap() {
+                messages.push(msg.to_vec());
+            }
+            if offset == bytes.len() {
+                return messages;
+            }
+        }
+    }
+
+    #[test]
+    fn send_handshake_record() {
+        let mut conn = connection();
+        let bytes = send(&mut conn, &[1, 2, 3]);
+
+        assert_eq!(bytes, record(ContentType::Handshake, &FINISHED));
+        assert!(!conn.wants_write());
+    }
+
+    #[test]
+    fn send_handshake_adds_to_transcript() {
+        let msg = HandshakeMessage {
+            ty: HandshakeType::Finished,
+            payload: HandshakePayload::Opaque(&[1, 2, 3]),
+        };
+        let mut conn = connection();
+        let mut transcript = Transcript::new(vec![]);
+        transcript.start_hash(Sha256::default()).unwrap();
+        conn.send_handshake(&msg, &mut transcript).unwrap();
+
+        let mut expected = Sha256::default();
+        expected.update(&FINISHED);
+        assert_eq!(transcript.current_hash().unwrap(), expected.finish());
+    }
+
+    #[test]
+    fn send_fragmented_handshake() {
+        let payload = vec![5; MAX_FRAGMENT_LEN + 10];
+        let bytes = send(&mut connection(), &payload);
+
+        assert_eq!(bytes.len(), 2 * RECORD_HEADER_SIZE + 4 + payload.len());
+        assert_eq!(bytes[..5], [22, 3, 3, 0x40, 0]);
+        assert_eq!(bytes[5..9], [0x14, 0, 0x40, 10]);
+        assert_eq!(bytes[5 + MAX_FRAGMENT_LEN..][..5], [22, 3, 3, 0, 14]);
+
+        let messages = receive_all(&mut connection(), &bytes);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0][4..], payload[..]);
+    }
+
+    #[test]
+    fn send_buffer_too_small() {
+        let mut tx = [0; 8];
+        let mut conn = ClientConnection::<RingAead>::new(owned(), tx.as_mut(), owned());
+        let msg = HandshakeMessage {
+            ty: HandshakeType::Finished,
+            payload: HandshakePayload::Opaque(&[1, 2, 3]),
+        };
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+
+        assert!(conn.send_handshake(&msg, &mut transcript).is_err());
+        assert!(!conn.wants_write());
+    }
+
+    #[test]
+    fn write_in_pieces() {
+        let mut conn = connection();
+        let msg = HandshakeMessage {
+            ty: HandshakeType::Finished,
+            payload: HandshakePayload::Opaque(&[1, 2, 3]),
+        };
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        conn.send_handshake(&msg, &mut transcript).unwrap();
+
+        let mut out = [0; 8];
+        assert_eq!(conn.write_tls(&mut out), 8);
+        assert!(conn.wants_write());
+        assert_eq!(conn.write_tls(&mut out), 4);
+        assert!(!conn.wants_write());
+    }
+
+    #[test]
+    fn receive_coalesced_messages() {
+        let bytes = record(
+            ContentType::Handshake,
+            &[FINISHED, [0x18, 0, 0, 1, 0, 0, 0]].concat()[..12],
+        );
+
+        assert_eq!(
+            receive_all(&mut connection(), &bytes),
+            vec![FINISHED.to_vec(), KEY_UPDATE.to_vec()]
+        );
+    }
+
+    #[test]
+    fn receive_message_over_records() {
+        let mut bytes = record(ContentType::Handshake, &FINISHED[..2]);
+        bytes.extend(record(ContentType::Handshake, &FINISHED[2..6]));
+        bytes.extend(record(
+            ContentType::Handshake,
+            &[&FINISHED[6..], &KEY_UPDATE[..]].concat(),
+        ));
+
+        assert_eq!(
+            receive_all(&mut connection(), &bytes),
+            vec![FINISHED.to_vec(), KEY_UPDATE.to_vec()]
+        );
+    }
+
+    #[test]
+    fn receive_in_pieces() {
+        let bytes = record(ContentType::Handshake, &FINISHED);
+        let mut conn = connection();
+
+        assert_eq!(conn.read_tls(&bytes[..3]).unwrap(), 3);
+        assert!(conn.next_handshake_message().unwrap().is_none());
+        assert_eq!(conn.read_tls(&bytes[3..]).unwrap(), bytes.len() - 3);
+        assert_eq!(conn.next_handshake_message().unwrap().unwrap(), FINISHED);
+    }
+
+    #[test]
+    fn receive_protected() {
+        let mut sender = connection();
+        sender.set_write_protection(protection());
+        let bytes = send(&mut sender, &[1, 2, 3]);
+        assert_eq!(bytes[0], 23);
+
+        let mut receiver = connection();
+        receiver.set_read_protection(protection());
+        assert_eq!(receive_all(&mut receiver, &bytes), vec![FINISHED.to_vec()]);
+    }
+
+    #[test]
+    fn receive_protected_padded_and_fragmented() {
+        let mut prot = protection();
+        prot.set_padding(PaddingPolicy::Multiple(64));
+        let mut sender = connection();
+        sender.set_write_protection(prot);
+        let payload = vec![5; MAX_FRAGMENT_LEN + 10];
+        let bytes = send(&mut sender, &payload);
+
+        let mut receiver = connection();
+        receiver.set_read_protection(protection());
+        let messages = receive_all(&mut receiver, &bytes);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0][4..], payload[..]);
+    }
+
+    #[test]
+    fn receive_plaintext_when_protected() {
+        let bytes = record(ContentType::Handshake, &FINISHED);
+        let mut conn = connection();
+        conn.set_read_protection(protection());
+        conn.read_tls(&bytes).unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn receive_interleaved_content_type() {
+        let mut bytes = record(ContentType::Handshake, &FINISHED[..2]);
+        bytes.extend(record(ContentType::Alert, &[2, 10]));
+        let mut conn = connection();
+        conn.read_tls(&bytes).unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn receive_change_cipher_spec() {
+        let mut bytes = record(ContentType::ChangeCipherSpec, &[1]);
+        bytes.extend(record(ContentType::Handshake, &FINISHED));
+
+        assert_eq!(
+            receive_all(&mut connection(), &bytes),
+            vec![FINISHED.to_vec()]
+        );
+    }
+
+    #[test]
+    fn receive_change_cipher_spec_when_protected() {
+        let mut sender = connection();
+        sender.set_write_protection(protection());
+        let mut bytes = record(ContentType::ChangeCipherSpec, &[1]);
+        bytes.extend(send(&mut sender, &[1, 2, 3]));
+
+        let mut receiver = connection();
+        receiver.set_read_protection(protection());
+        assert_eq!(receive_all(&mut receiver, &bytes), vec![FINISHED.to_vec()]);
+    }
+
+    #[test]
+    fn receive_second_change_cipher_spec() {
+        let mut bytes = record(ContentType::ChangeCipherSpec, &[1]);
+        bytes.extend(record(ContentType::ChangeCipherSpec, &[1]));
+        let mut conn = connection();
+        conn.read_tls(&bytes).unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn receive_change_cipher_spec_after_handshake() {
+        let mut conn = connection();
+        conn.complete_handshake();
+        conn.read_tls(&record(ContentType::ChangeCipherSpec, &[1]))
+            .unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn receive_invalid_change_cipher_spec() {
+        for content in [&[2][..], &[1, 1], &[]] {
+            let mut conn = connection();
+            conn.read_tls(&record(ContentType::ChangeCipherSpec, content))
+                .unwrap();
+
+            assert!(matches!(
+                conn.next_handshake_message(),
+                Err(TlsError::UnexpectedMessage)
+            ));
+        }
+    }
+
+    #[test]
+    fn receive_protected_change_cipher_spec() {
+        let mut prot = protection();
+        let mut bytes = vec![0; 64];
+        bytes[RECORD_HEADER_SIZE] = 1;
+        let len = prot
+            .seal(ContentType::ChangeCipherSpec, &mut bytes, 1)
+            .unwrap();
+        bytes.truncate(len);
+
+        let mut conn = connection();
+        conn.set_read_protection(protection());
+        conn.read_tls(&bytes).unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn receive_change_cipher_spec_within_message() {
+        let mut bytes = record(ContentType::Handshake, &FINISHED[..2]);
+        bytes.extend(record(ContentType::ChangeCipherSpec, &[1]));
+        bytes.extend(record(ContentType::Handshake, &FINISHED[2..]));
+        let mut conn = connection();
+        conn.read_tls(&bytes).unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn send_change_cipher_spec() {
+        let mut conn = connection();
+        conn.set_write_protection(protection());
+        conn.send_change_cipher_spec().unwrap();
+
+        let mut out = [0; 16];
+        let len = conn.write_tls(&mut out);
+        assert_eq!(out[..len], record(ContentType::ChangeCipherSpec, &[1])[..]);
+    }
+
+    #[test]
+    fn receive_empty_handshake_record() {
+        let mut conn = connection();
+        conn.read_tls(&record(ContentType::Handshake, &[])).unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn receive_record_overflow() {
+        let mut conn = connection();
+        conn.read_tls(&[22, 3, 3, 0x40, 0x01]).unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::RecordOverflow)
+        ));
+    }
+
+    #[test]
+    fn record_exceeds_receive_buffer() {
+        let mut rx = [0; 16];
+        let mut conn = ClientConnection::<RingAead>::new(rx.as_mut(), owned(), owned());
+
+        assert!(matches!(
+            conn.read_tls(&record(ContentType::Handshake, &[0; 12])),
+            Err(TlsError::InternalError(_))
+        ));
+    }
+
+    #[test]
+    fn message_exceeds_handshake_buffer() {
+        let mut hs = [0; 8];
+        let mut conn = ClientConnection::<RingAead>::new(owned(), owned(), hs.as_mut());
+        conn.read_tls(&record(ContentType::Handshake, &[0x14, 0, 0, 5, 1]))
+            .unwrap();
+
+        assert!(matches!(
+            conn.next_handshake_message(),
+            Err(TlsError::InternalError(_))
+        ));
+    }
+
+    #[test]
+    fn whole_message_ignores_handshake_buffer() {
+        let mut hs = [0; 4];
+        let mut conn = ClientConnection::<RingAead>::new(owned(), owned(), hs.as_mut());
+        conn.read_tls(&record(ContentType::Handshake, &FINISHED))
+            .unwrap();
+
+        assert_eq!(conn.next_handshake_message().unwrap().unwrap(), FINISHED);
+        assert_eq!(conn.peak_usage().handshake, 0);
+    }
+
+    #[test]
+    fn static_buffers() {
+        let mut buffers = Buffers::<64, 64, 16>::new();
+        let mut sender = buffers.connection::<RingAead>();
+        let bytes = send(&mut sender, &[1, 2, 3]);
+
+        let mut buffers = Buffers::<16, 16, 16>::new();
+        let mut receiver = buffers.connection::<RingAead>();
+        assert_eq!(receive_all(&mut receiver, &bytes), vec![FINISHED.to_vec()]);
+    }
+
+    #[test]
+    fn peak_usage() {
+        let mut sender = connection();
+        send(&mut sender, &[1, 2, 3]);
+        assert_eq!(
+            sender.peak_usage(),
+            BufferUsage {
+                rx: 0,
+                tx: 12,
+                handshake: 0,
+            }
+        );
+
+        let mut bytes = record(ContentType::Handshake, &FINISHED[..2]);
+        bytes.extend(record(ContentType::Handshake, &FINISHED[2..]));
+        let mut receiver = connection();
+        receive_all(&mut receiver, &bytes);
+        assert_eq!(
+            receiver.peak_usage(),
+            BufferUsage {
+                rx: 17,
+                tx: 0,
+                handshake: 7,
+            }
+        );
+    }
+
+    #[test]
+    fn send_within_record_size_limit() {
+        let mut prot = protection();
+        prot.set_record_size_limit(64).unwrap();
+        let mut sender = connection();
+        sender.set_write_protection(prot);
+        let payload = vec![5; 100];
+        let bytes = send(&mut sender, &payload);
+
+        // 104 bytes of message in records of at most 63 bytes of content.
+        assert_eq!(bytes.len(), 2 * (RECORD_HEADER_SIZE + 1 + 16) + 104);
+        assert_eq!(bytes[..5], [23, 3, 3, 0, 80]);
+
+        let mut receiver = connection();
+        let mut prot = protection();
+        prot.set_record_size_limit(64).unwrap();
+        receiver.set_read_protection(prot);
+        let messages = receive_all(&mut receiver, &bytes);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0][4..], payload[..]);
+    }
+
+    #[test]
+    fn receive_over_max_fragment_length() {
+        let mut sender = connection();
+        sender.set_write_protection(protection());
+        let bytes = send(&mut sender, &[5; 600]);
+
+        let mut receiver = connection();
+        let mut prot = protection();
+        prot.set_max_fragment_length(MaxFragmentLength::Max512)
+            .unwrap();
+        receiver.set_read_protection(prot);
+        receiver.read_tls(&bytes).unwrap();
+        assert!(matches!(
+            receiver.next_handshake_message(),
+            Err(TlsError::RecordOverflow)
+        ));
+    }
+
+    #[test]
+    fn pending_handshake() {
+        let mut conn = connection();
+        let mut bytes = record(
+            ContentType::Handshake,
+            &[&FINISHED[..], &KEY_UPDATE[..2]].concat(),
+        );
+        bytes.extend(record(ContentType::Handshake, &KEY_UPDATE[2..]));
+        conn.read_tls(&bytes).unwrap();
+
+        assert!(!conn.has_pending_handshake());
+        assert_eq!(conn.next_handshake_message().unwrap(), Some(&FINISHED[..]));
+        assert!(conn.has_pending_handshake());
+        assert_eq!(
+            conn.next_handshake_message().unwrap(),
+            Some(&KEY_UPDATE[..])
+        );
+        assert!(!conn.has_pending_handshake());
+    }
+}
@@ -0,0 +1,1582 @@
+use crate::{
+    certificate::{ClientCertificate, ResolvesClientCert},
+    connection::ClientConnection,
+    validation::{validate_hello_retry_request, validate_server_hello},
+};
+use mtls_message::{
+    crypto::{Aead, Digest, Hash, KeyExchange, SignatureVerifier, MAX_SIGNATURE_LEN},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::{
+        certificate::{CertificateEntry, CertificatePayload},
+        certificate_request::CertificateRequestPayload,
+        certificate_verify::{CertificateVerifyPayload, Sender, MAX_SIGNED_CONTENT_LEN},
+        client::ClientHelloPayload,
+        enums::{
+            AeadAlgorithm, CipherSuite, CipherSuiteInfo, ExtensionType, HandshakeType,
+            HashAlgorithm, NamedGroup, ProtocolVersion, SignatureScheme,
+        },
+        extension::{
+            client::ClientExtension, server::ServerExtension, Extensions, KeyShareEntries,
+            KeyShareEntry,
+        },
+        key_schedule::KeySchedule,
+        message::{HandshakeMessage, HandshakePayload},
+        random::Random,
+        server::ServerHelloPayload,
+        session::SessionId,
+        transcript::Transcript,
+    },
+    record::{protection::RecordProtection, MIN_RECORD_SIZE_LIMIT},
+    time::TimeSource,
+    x509::{
+        certificate::{Certificate, SignatureAlgorithmIdentifier, SubjectPublicKeyInfo},
+        identity::ServerName,
+        verifier::{ServerCertVerifier, MAX_CERTIFICATES},
+    },
+    Array, Codec, CodecSized, Decoder,
+};
+
+/// Longest public key of a supported group (an uncompressed secp521r1 point).
+const MAX_KEY_SHARE_LEN: usize = 133;
+
+/// Longest shared secret of a supported group (secp521r1).
+const MAX_SHARED_SECRET_LEN: usize = 66;
+
+/// Longest key of a supported AEAD.
+const MAX_KEY_LEN: usize = 32;
+
+/// Longest SubjectPublicKeyInfo kept from the server's certificate (an RSA-4096 key).
+const MAX_PUBLIC_KEY_LEN: usize = 550;
+
+/// Longest certificate_request_context (RFC 8446, section 4.3.2).
+const MAX_CONTEXT_LEN: usize = 255;
+
+/// Longest cookie kept to echo in ClientHello2.
+///
+/// Cookies may be up to 2^16-1 bytes long, but a HelloRetryRequest with one longer than this is
+/// rejected with an illegal_parameter alert.
+pub const MAX_COOKIE_LEN: usize = 256;
+
+/// What to offer in the ClientHello.
+#[derive(Debug, Clone, Copy)]
+pub struct HelloConfig<'c> {
+    pub cipher_suites: &'c [CipherSuite],
+    pub groups: &'c [NamedGroup],
+    /// Group of the key share sent in ClientHello1, which must be one of `groups`.
+    pub key_share_group: NamedGroup,
+    /// Schemes the server may sign its CertificateVerify with.
+    pub signature_schemes: &'c [SignatureScheme],
+    /// Largest protected record the client can receive, content type and padding included,
+    /// advertised with the record_size_limit extension (RFC 8449). At least 64.
+    pub record_size_limit: Option<u16>,
+}
+
+/// How the server is authenticated, and how the client authenticates when asked to.
+#[derive(Clone, Copy)]
+pub struct AuthConfig<'c> {
+    /// Name the server's certificate must be issued to.
+    pub server_name: ServerName<'c>,
+    pub server_verifier: &'c dyn ServerCertVerifier,
+    /// Clock the server's certificates are checked against when they're verified.
+    pub time: &'c dyn TimeSource,
+    /// Verifies the server's CertificateVerify with the public key of its certificate.
+    pub signature_verifier: &'c dyn SignatureVerifier,
+    /// Chooses the certificate to answer a CertificateRequest with. Without one, the client
+    /// answers with an empty Certificate message.
+    pub client_cert: Option<&'c dyn ResolvesClientCert>,
+}
+
+/// What a call to [`ClientHandshake::process`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// The server sent a HelloRetryRequest, and ClientHello2 was queued in answer.
+    HelloRetry,
+    /// The server sent a valid ServerHello. Its key share is in
+    /// [`ClientHandshake::server_key_share`], and records are protected with the handshake
+    /// traffic keys from now on.
+    ServerHello {
+        cipher_suite: CipherSuite,
+        group: NamedGroup,
+    },
+    /// The server sent a valid message of its encrypted flight, before its Finished.
+    Received(HandshakeType),
+    /// The server's Finished was verified and the client's last flight queued. Records are
+    /// protected with the application traffic keys from now on.
+    Connected,
+    /// The server sent a message after the handshake that was ignored. Only NewSessionTicket is,
+    /// as sessions aren't resumed.
+    Ignored(HandshakeType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    WaitServerHello,
+    /// A HelloRetryRequest selected the cipher suite.
+    WaitServerHelloAfterRetry(CipherSuite),
+    WaitEncryptedExtensions,
+    WaitCertificateOrRequest,
+    /// The server sent a CertificateRequest.
+    WaitCertificate,
+    WaitCertificateVerify,
+    WaitFinished,
+    Connected,
+}
+
+/// Client side of a full handshake.
+///
+/// The ClientHello offers TLS 1.3 only, with a key share for a single group. A HelloRetryRequest
+/// is answered with ClientHello2, with a new key share for the group the server selected and the
+/// server's cookie. The server must authenticate with a certificate, and the client answers a
+/// CertificateRequest with the certificate [`AuthConfig::client_cert`] chooses.
+pub struct ClientHandshake<'c, 't, H: Hash, A: Aead> {
+    hello: Hello<'c>,
+    auth: AuthConfig<'c>,
+    transcript: Transcript<'t, H>,
+    /// Hash context for the hash algorithm of the negotiated cipher suite.
+    new_hash: fn(HashAlgorithm) -> Option<H>,
+    /// AEAD for the negotiated cipher suite, with the given key.
+    new_aead: fn(AeadAlgorithm, &[u8]) -> Option<A>,
+    server_key_share: KeyShare,
+    keys: Option<HandshakeKeys<H>>,
+    server_public_key: ServerPublicKey,
+    client_auth: Option<ClientAuth<'c>>,
+    /// Limit the server advertised in its EncryptedExtensions, for records sent to it.
+    server_record_size_limit: Option<u16>,
+    middlebox_compatibility: bool,
+    change_cipher_spec_sent: bool,
+    state: State,
+}
+
+impl<'c, 't, H: Hash, A: Aead> ClientHandshake<'c, 't, H, A> {
+    pub fn new(
+        config: HelloConfig<'c>,
+        auth: AuthConfig<'c>,
+        random: Random,
+        transcript: Transcript<'t, H>,
+        new_hash: fn(HashAlgorithm) -> Option<H>,
+        new_aead: fn(AeadAlgorithm, &[u8]) -> Option<A>,
+    ) -> Self {
+        Self {
+            hello: Hello {
+                config,
+                random,
+                session_id: SessionId::empty(),
+                key_share: KeyShare::empty(config.key_share_group),
+                cookie: [0; MAX_COOKIE_LEN],
+                cookie_len: 0,
+            },
+            auth,
+            transcript,
+            new_hash,
+            new_aead,
+            server_key_share: KeyShare::empty(config.key_share_group),
+            keys: None,
+            server_public_key: ServerPublicKey::empty(),
+            client_auth: None,
+            server_record_size_limit: None,
+            middlebox_compatibility: false,
+            change_cipher_spec_sent: false,
+            state: State::Start,
+        }
+    }
+
+    /// Makes the handshake look like a TLS 1.2 session resumption to middleboxes (RFC 8446,
+    /// appendix D.4), by sending `session_id` as the legacy session id, and a dummy
+    /// ChangeCipherSpec record before the second flight.
+    ///
+    /// `session_id` should be freshly random for each connection.
+    pub fn middlebox_compatibility(mut self, session_id: [u8; 32]) -> Self {
+        self.hello.session_id = SessionId::from(session_id);
+        self.middlebox_compatibility = true;
+        self
+    }
+
+    /// Generates the key share and queues ClientHello1 to be sent.
+    pub fn start<K: KeyExchange>(
+        &mut self,
+        conn: &mut ClientConnection<'_, A>,
+        kx: &mut K,
+    ) -> TlsResult<()> {
+        if self.state != State::Start {
+            return Err(TlsError::InternalError("handshake already started"));
+        }
+        match self.hello.config.record_size_limit {
+            Some(limit) if limit < MIN_RECORD_SIZE_LIMIT => {
+                return Err(TlsError::InternalError("record size limit too small"));
+            }
+            _ => {}
+        }
+
+        self.hello
+            .key_share
+            .generate(self.hello.config.key_share_group, kx)?;
+        self.send_client_hello(conn)?;
+        self.state = State::WaitServerHello;
+        Ok(())
+    }
+
+    /// Handles the next handshake message received on `conn`, returning `None` if there isn't a
+    /// complete one yet.
+    ///
+    /// On failure, the error is the alert to send to the server.
+    pub fn process<K: KeyExchange>(
+        &mut self,
+        conn: &mut ClientConnection<'_, A>,
+        kx: &mut K,
+    ) -> TlsResult<Option<Progress>> {
+        let bytes = match conn.next_handshake_message()? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        let msg =
+            HandshakeMessage::decode(&mut Decoder::new(bytes)).ok_or(TlsError::DecodeError)?;
+
+        let progress = match self.state {
+            State::WaitServerHello | State::WaitServerHelloAfterRetry(_) => {
+                let server = match msg.payload {
+                    HandshakePayload::ServerHello(ref server)
+                        if msg.ty == HandshakeType::ServerHello =>
+                    {
+                        server
+                    }
+                    _ => return Err(TlsError::UnexpectedMessage),
+                };
+
+                if server.random().is_hello_retry_request() {
+                    self.handle_hello_retry_request(server, bytes, kx)?;
+                    self.send_change_cipher_spec(conn)?;
+                    self.send_client_hello(conn)?;
+                    return Ok(Some(Progress::HelloRetry));
+                }
+                let progress = self.handle_server_hello(server, bytes, kx)?;
+                self.send_change_cipher_spec(conn)?;
+                self.protect_handshake(conn)?;
+                progress
+            }
+            State::WaitFinished => {
+                let verify_data = match msg.payload {
+                    HandshakePayload::Opaque(data) if msg.ty == HandshakeType::Finished => data,
+                    _ => return Err(TlsError::UnexpectedMessage),
+                };
+                self.handle_finished(verify_data, bytes)?;
+                self.send_last_flight(conn)?;
+                Progress::Connected
+            }
+            // Tickets are only needed to resume sessions (RFC 8446, section 4.6.1).
+            State::Connected if msg.ty == HandshakeType::NewSessionTicket => {
+                Progress::Ignored(msg.ty)
+            }
+            _ => {
+                let ty = msg.ty;
+                self.handle_encrypted(&msg)?;
+                self.transcript.add(bytes)?;
+                if ty == HandshakeType::EncryptedExtensions {
+                    self.limit_records(conn)?;
+                }
+                Progress::Received(ty)
+            }
+        };
+        Ok(Some(progress))
+    }
+
+    /// Public key of the server's key share, once the ServerHello was received.
+    pub fn server_key_share(&self) -> Option<&[u8]> {
+        self.keys.as_ref().map(|_| self.server_key_share.as_bytes())
+    }
+
+    /// Transcript of the messages exchanged so far.
+    pub fn transcript(&self) -> &Transcript<'t, H> {
+        &self.transcript
+    }
+
+    fn handle_hello_retry_request<K: KeyExchange>(
+        &mut self,
+        retry: &ServerHelloPayload<'_>,
+        bytes: &[u8],
+        kx: &mut K,
+    ) -> TlsResult<()> {
+        match self.state {
+            State::WaitServerHello => {}
+            // A client must only answer one HelloRetryRequest (RFC 8446, section 4.1.4).
+            _ => return Err(TlsError::UnexpectedMessage),
+        }
+        self.hello
+            .with_client_hello(|client| validate_hello_retry_request(&client, retry))?;
+
+        let cipher_suite = retry.cipher_suite();
+        self.start_hash(cipher_suite)?;
+        self.transcript.hello_retry()?;
+        self.transcript.add(bytes)?;
+
+        for ext in retry.extensions().iter() {
+            match *ext.as_ref() {
+                ServerExtension::HelloRetryKeyShare(group) => {
+                    self.hello.key_share.generate(group, kx)?;
+                }
+                ServerExtension::Cookie(cookie) => {
+                    let buf = self
+                        .hello
+                        .cookie
+                        .get_mut(..cookie.len())
+                        .ok_or(TlsError::InternalError("cookie exceeds buffer"))?;
+                    buf.copy_from_slice(cookie);
+                    self.hello.cookie_len = cookie.len();
+                }
+                _ => {}
+            }
+        }
+
+        self.state = State::WaitServerHelloAfterRetry(cipher_suite);
+        Ok(())
+    }
+
+    fn handle_server_hello<K: KeyExchange>(
+        &mut self,
+        server: &ServerHelloPayload<'_>,
+        bytes: &[u8],
+        kx: &mut K,
+    ) -> TlsResult<Progress> {
+        let cipher_suite = server.cipher_suite();
+        match self.state {
+            State::WaitServerHello => {}
+            // The ServerHello must keep the cipher suite of the HelloRetryRequest (RFC 8446,
+            // section 4.1.4).
+            State::WaitServerHelloAfterRetry(suite) if suite == cipher_suite => {}
+            State::WaitServerHelloAfterRetry(_) => return Err(TlsError::IllegalParameter),
+            _ => return Err(TlsError::UnexpectedMessage),
+        }
+        self.hello
+            .with_client_hello(|client| validate_server_hello(&client, server))?;
+
+        // The share is for the group offered, as that was validated.
+        let share = server
+            .extensions()
+            .iter()
+            .find_map(|ext| match *ext.as_ref() {
+                ServerExtension::KeyShare(share) => Some(share),
+                _ => None,
+            })
+            .ok_or(TlsError::MissingExtension)?;
+        self.server_key_share.copy_from(share)?;
+
+        if !self.transcript.is_hashing() {
+            self.start_hash(cipher_suite)?;
+        }
+        self.transcript.add(bytes)?;
+
+        let suite = cipher_suite
+            .info()
+            .ok_or(TlsError::InternalError("unknown cipher suite"))?;
+        let hash = (self.new_hash)(suite.hash)
+            .ok_or(TlsError::InternalError("no hash for cipher suite"))?;
+        let mut schedule = KeySchedule::new(hash, suite.hash);
+        let mut shared_secret = [0; MAX_SHARED_SECRET_LEN];
+        let len = kx.agree(self.server_key_share.as_bytes(), &mut shared_secret)?;
+        schedule.handshake(&shared_secret[..len])?;
+
+        let hash = self.transcript.current_hash()?;
+        self.keys = Some(HandshakeKeys {
+            client_secret: schedule.derive_secret(b"c hs traffic", &hash)?,
+            server_secret: schedule.derive_secret(b"s hs traffic", &hash)?,
+            schedule,
+            suite,
+        });
+
+        self.state = State::WaitEncryptedExtensions;
+        Ok(Progress::ServerHello {
+            cipher_suite,
+            group: share.group,
+        })
+    }
+
+    /// Handles a message of the server's encrypted flight before its Finished.
+    fn handle_encrypted(&mut self, msg: &HandshakeMessage<'_>) -> TlsResult<()> {
+        self.state = match (self.state, &msg.payload) {
+            (State::WaitEncryptedExtensions, HandshakePayload::Opaque(payload))
+                if msg.ty == HandshakeType::EncryptedExtensions =>
+            {
+                self.server_record_size_limit =
+                    check_extensions(payload, self.hello.config.record_size_limit.is_some())?;
+                State::WaitCertificateOrRequest
+            }
+            (State::WaitCertificateOrRequest, HandshakePayload::CertificateRequest(request)) => {
+                self.handle_certificate_request(request)?;
+                State::WaitCertificate
+            }
+            (
+                State::WaitCertificateOrRequest | State::WaitCertificate,
+                HandshakePayload::Certificate(certificates),
+            ) => {
+                self.handle_certificate(certificates)?;
+                State::WaitCertificateVerify
+            }
+            (State::WaitCertificateVerify, HandshakePayload::CertificateVerify(verify)) => {
+                self.handle_certificate_verify(verify)?;
+                State::WaitFinished
+            }
+            _ => return Err(TlsError::UnexpectedMessage),
+        };
+        Ok(())
+    }
+
+    /// Chooses the certificate to answer with once the server's Finished is verified, and the
+    /// scheme to sign the CertificateVerify with.
+    fn handle_certificate_request(
+        &mut self,
+        request: &CertificateRequestPayload<'_>,
+    ) -> TlsResult<()> {
+        // signature_algorithms must be present (RFC 8446, section 4.3.2).
+        let schemes = request
+            .signature_schemes()
+            .ok_or(TlsError::MissingExtension)?;
+
+        let mut auth = ClientAuth {
+            context: [0; MAX_CONTEXT_LEN],
+            context_len: request.context().len(),
+            certificate: None,
+        };
+        auth.context[..auth.context_len].copy_from_slice(request.context());
+
+        // Without a scheme both sides support, the client can't authenticate, and sends no
+        // certificate for the server to decide whether to continue.
+        let resolver = self.auth.client_cert;
+        auth.certificate = resolver
+            .and_then(|resolver| resolver.resolve(request))
+            .and_then(|certificate| {
+                schemes
+                    .iter()
+                    .map(|scheme| *scheme.as_ref())
+                    .find(|scheme| {
+                        scheme.is_tls13_handshake() && certificate.key.schemes().contains(scheme)
+                    })
+                    .map(|scheme| (certificate, scheme))
+            });
+
+        self.client_auth = Some(auth);
+        Ok(())
+    }
+
+    fn handle_certificate(&mut self, certificates: &CertificatePayload<'_>) -> TlsResult<()> {
+        // The context is only set in answer to a CertificateRequest (RFC 8446, section 4.4.2).
+        if !certificates.context().is_empty() {
+            return Err(TlsError::IllegalParameter);
+        }
+        // A server must always send a certificate (RFC 8446, section 4.4.2.4).
+        let end_entity = certificates.entries().next().ok_or(TlsError::DecodeError)?;
+        let end_entity = end_entity.as_ref();
+
+        self.auth.server_verifier.verify_server_cert(
+            certificates,
+            self.auth.server_name,
+            end_entity.ocsp_response(),
+            end_entity.signed_certificate_timestamps(),
+            self.auth.time.now()?,
+        )?;
+
+        let certificate = Certificate::parse(end_entity.data)?;
+        self.server_public_key
+            .copy_from(certificate.public_key().as_bytes())
+    }
+
+    fn handle_certificate_verify(
+        &mut self,
+        verify: &CertificateVerifyPayload<'_>,
+    ) -> TlsResult<()> {
+        let scheme = verify.scheme;
+        if !scheme.is_tls13_handshake() || !self.hello.config.signature_schemes.contains(&scheme) {
+            return Err(TlsError::IllegalParameter);
+        }
+        let public_key = SubjectPublicKeyInfo::parse(self.server_public_key.as_bytes())
+            .ok_or(TlsError::BadCertificate)?;
+        if !public_key.algorithm().supports(scheme) {
+            return Err(TlsError::IllegalParameter);
+        }
+        let info = scheme.info().ok_or(TlsError::IllegalParameter)?;
+
+        let hash = self.transcript.current_hash()?;
+        let mut buf = [0; MAX_SIGNED_CONTENT_LEN];
+        let content = CertificateVerifyPayload::signed_content(Sender::Server, &hash, &mut buf);
+        let algorithm = SignatureAlgorithmIdentifier {
+            algorithm: info.algorithm,
+            hash: info.hash,
+        };
+
+        self.auth
+            .signature_verifier
+            .verify(&public_key, algorithm, content, verify.signature)
+            // A signature that doesn't verify is a decrypt_error (RFC 8446, section 4.4.3).
+            .map_err(|err| match err {
+                TlsError::BadCertificate => TlsError::DecryptError,
+                err => err,
+            })
+    }
+
+    fn handle_finished(&mut self, verify_data: &[u8], bytes: &[u8]) -> TlsResult<()> {
+        let hash = self.transcript.current_hash()?;
+        let keys = self.handshake_keys()?;
+        keys.schedule
+            .verify_finished(&keys.server_secret, &hash, verify_data)?;
+        self.transcript.add(bytes)
+    }
+
+    /// Queues the client's Certificate and CertificateVerify if the server asked for them, then
+    /// its Finished, and switches to the application traffic keys.
+    fn send_last_flight(&mut self, conn: &mut ClientConnection<'_, A>) -> TlsResult<()> {
+        if conn.has_pending_handshake() {
+            return Err(TlsError::UnexpectedMessage);
+        }
+
+        // The application traffic secrets cover the transcript up to the server's Finished.
+        let hash = self.transcript.current_hash()?;
+        let mut keys = self
+            .keys
+            .take()
+            .ok_or(TlsError::InternalError("no handshake keys"))?;
+        keys.schedule.master()?;
+        let client_secret = keys.schedule.derive_secret(b"c ap traffic", &hash)?;
+        let server_secret = keys.schedule.derive_secret(b"s ap traffic", &hash)?;
+
+        if let Some(auth) = self.client_auth.take() {
+            self.send_client_certificate(conn, &auth)?;
+        }
+
+        let hash = self.transcript.current_hash()?;
+        let verify_data = keys
+            .schedule
+            .finished_verify_data(&keys.client_secret, &hash)?;
+        let msg = HandshakeMessage {
+            ty: HandshakeType::Finished,
+            payload: HandshakePayload::Opaque(verify_data.as_ref()),
+        };
+        conn.send_handshake(&msg, &mut self.transcript)?;
+
+        conn.set_read_protection(keys.protection(&server_secret, self.new_aead)?);
+        conn.set_write_protection(keys.protection(&client_secret, self.new_aead)?);
+        self.limit_records(conn)?;
+        conn.complete_handshake();
+        self.keys = Some(keys);
+        self.state = State::Connected;
+        Ok(())
+    }
+
+    fn send_client_certificate(
+        &mut self,
+        conn: &mut ClientConnection<'_, A>,
+        auth: &ClientAuth<'_>,
+    ) -> TlsResult<()> {
+        let context = &auth.context[..auth.context_len];
+        let (certificate, scheme) = match auth.certificate {
+            Some(certificate) => certificate,
+            None => {
+                let msg = HandshakeMessage {
+                    ty: HandshakeType::Certificate,
+                    payload: HandshakePayload::Certificate(CertificatePayload::new(context, &[])),
+                };
+                return conn.send_handshake(&msg, &mut self.transcript);
+            }
+        };
+
+        let chain = certificate.chain;
+        if chain.len() > MAX_CERTIFICATES {
+            return Err(TlsError::InternalError("too many client certificates"));
+        }
+        let entries: [CertificateEntry<'_>; MAX_CERTIFICATES] =
+            core::array::from_fn(|i| CertificateEntry {
+                data: chain.get(i).copied().unwrap_or_default(),
+                extensions: Extensions::empty(),
+            });
+        let msg = HandshakeMessage {
+            ty: HandshakeType::Certificate,
+            payload: HandshakePayload::Certificate(CertificatePayload::new(
+                context,
+                &entries[..chain.len()],
+            )),
+        };
+        conn.send_handshake(&msg, &mut self.transcript)?;
+
+        let hash = self.transcript.current_hash()?;
+        let mut signature = [0; MAX_SIGNATURE_LEN];
+        let msg = HandshakeMessage {
+            ty: HandshakeType::CertificateVerify,
+            payload: HandshakePayload::CertificateVerify(CertificateVerifyPayload::sign(
+                Sender::Client,
+                &hash,
+                certificate.key,
+                scheme,
+                &mut signature,
+            )?),
+        };
+        conn.send_handshake(&msg, &mut self.transcript)
+    }
+
+    /// Protects records in both directions with the handshake traffic keys.
+    fn protect_handshake(&mut self, conn: &mut ClientConnection<'_, A>) -> TlsResult<()> {
+        // The ServerHello must end at a record boundary, as keys change after it (RFC 8446,
+        // section 5.1).
+        if conn.has_pending_handshake() {
+            return Err(TlsError::UnexpectedMessage);
+        }
+
+        let keys = self.handshake_keys()?;
+        conn.set_read_protection(keys.protection(&keys.server_secret, self.new_aead)?);
+        conn.set_write_protection(keys.protection(&keys.client_secret, self.new_aead)?);
+        Ok(())
+    }
+
+    /// Limits the size of records in both directions, once both sides sent the
+    /// record_size_limit extension.
+    fn limit_records(&self, conn: &mut ClientConnection<'_, A>) -> TlsResult<()> {
+        match (
+            self.hello.config.record_size_limit,
+            self.server_record_size_limit,
+        ) {
+            (Some(read), Some(write)) => conn.set_record_size_limits(read, write),
+            _ => Ok(()),
+        }
+    }
+
+    fn handshake_keys(&self) -> TlsResult<&HandshakeKeys<H>> {
+        self.keys
+            .as_ref()
+            .ok_or(TlsError::InternalError("no handshake keys"))
+    }
+
+    fn start_hash(&mut self, cipher_suite: CipherSuite) -> TlsResult<()> {
+        let hash = cipher_suite
+            .hash()
+            .and_then(self.new_hash)
+            .ok_or(TlsError::InternalError("no hash for cipher suite"))?;
+        self.transcript.start_hash(hash)
+    }
+
+    /// Queues the dummy ChangeCipherSpec record in middlebox compatibility mode, once, before
+    /// ClientHello2 or the encrypted flight.
+    fn send_change_cipher_spec(&mut self, conn: &mut ClientConnection<'_, A>) -> TlsResult<()> {
+        if self.middlebox_compatibility && !self.change_cipher_spec_sent {
+            conn.send_change_cipher_spec()?;
+            self.change_cipher_spec_sent = true;
+        }
+        Ok(())
+    }
+
+    fn send_client_hello(&mut self, conn: &mut ClientConnection<'_, A>) -> TlsResult<()> {
+        let transcript = &mut self.transcript;
+        self.hello.with_client_hello(|client| {
+            let msg = HandshakeMessage {
+                ty: HandshakeType::ClientHello,
+                payload: HandshakePayload::ClientHello(client),
+            };
+            conn.send_handshake(&msg, transcript)
+        })
+    }
+}
+
+/// Checks the extensions of an EncryptedExtensions message decode, returning the server's
+/// record_size_limit if it sent one.
+///
+/// Only extensions the ClientHello offered that are allowed in EncryptedExtensions may be sent,
+/// which are supported_groups, and record_size_limit if `record_size_limit_offered` (RFC 8446,
+/// section 4.2).
+fn check_extensions(payload: &[u8], record_size_limit_offered: bool) -> TlsResult<Option<u16>> {
+    let mut dec = Decoder::new(payload);
+    let len = ServerExtension::decode_len(&mut dec).ok_or(TlsError::DecodeError)?;
+    let mut extensions = dec.sub(len).ok_or(TlsError::DecodeError)?;
+    let mut record_size_limit = None;
+    while !extensions.is_complete() {
+        match ServerExtension::decode(&mut extensions).ok_or(TlsError::DecodeError)? {
+            ServerExtension::RecordSizeLimit(limit) if record_size_limit_offered => {
+                // Smaller limits are invalid (RFC 8449, section 4).
+                if limit < MIN_RECORD_SIZE_LIMIT {
+                    return Err(TlsError::IllegalParameter);
+                }
+                record_size_limit = Some(limit);
+            }
+            ServerExtension::Unknown(ExtensionType::SupportedGroups, _) => {}
+            _ => return Err(TlsError::UnsupportedExtension),
+        }
+    }
+
+    if !dec.is_complete() {
+        return Err(TlsError::DecodeError);
+    }
+    Ok(record_size_limit)
+}
+
+/// Everything that goes into the ClientHello, apart from the transcript so the two can be
+/// borrowed separately.
+struct Hello<'c> {
+    config: HelloConfig<'c>,
+    random: Random,
+    session_id: SessionId,
+    key_share: KeyShare,
+    cookie: [u8; MAX_COOKIE_LEN],
+    cookie_len: usize,
+}
+
+impl<'c> Hello<'c> {
+    /// Calls `f` with the ClientHello to send next, which is ClientHello2 once a cookie or new
+    /// key share is set.
+    fn with_client_hello<R>(&self, f: impl FnOnce(ClientHelloPayload<'_>) -> R) -> R {
+        let versions = [ProtocolVersion::TLSv1_3];
+        let shares = [KeyShareEntry {
+            group: self.key_share.group,
+            payload: self.key_share.as_bytes(),
+        }];
+        // The optional extensions are written over the placeholders at the end.
+        let mut extensions = [
+            ClientExtension::from(Array::from(&versions[..])),
+            ClientExtension::from(Array::from(self.config.groups)),
+            ClientExtension::from(Array::from(self.config.signature_schemes)),
+            ClientExtension::from(KeyShareEntries::from(Array::from(&shares[..]))),
+            ClientExtension::Cookie(&[]),
+            ClientExtension::Cookie(&[]),
+        ];
+        let mut len = 4;
+        if let Some(limit) = self.config.record_size_limit {
+            extensions[len] = ClientExtension::RecordSizeLimit(limit);
+            len += 1;
+        }
+        if self.cookie_len != 0 {
+            extensions[len] = ClientExtension::Cookie(&self.cookie[..self.cookie_len]);
+            len += 1;
+        }
+
+        let client = ClientHelloPayload::builder()
+            .random(self.random)
+            .session_id(self.session_id)
+            .cipher_suites(Array::from(self.config.cipher_suites))
+            .extensions(Array::from(&extensions[..len]).into())
+            .build();
+        f(client)
+    }
+}
+
+/// Public key of a key share.
+struct KeyShare {
+    group: NamedGroup,
+    data: [u8; MAX_KEY_SHARE_LEN],
+    len: usize,
+}
+
+impl KeyShare {
+    fn empty(group: NamedGroup) -> Self {
+        Self {
+            group,
+            data: [0; MAX_KEY_SHARE_LEN],
+            len: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    fn generate<K: KeyExchange>(&mut self, group: NamedGroup, kx: &mut K) -> TlsResult<()> {
+        self.len = kx.generate(group, &mut self.data)?;
+        self.group = group;
+        Ok(())
+    }
+
+    fn copy_from(&mut self, share: KeyShareEntry<'_>) -> TlsResult<()> {
+        let len = share.payload.len();
+        if len > MAX_KEY_SHARE_LEN {
+            return Err(TlsError::IllegalParameter);
+        }
+
+        self.data[..len].copy_from_slice(share.payload);
+        self.len = len;
+        self.group = share.group;
+        Ok(())
+    }
+}
+
+/// Key schedule and handshake traffic secrets, from the ServerHello on.
+struct HandshakeKeys<H: Hash> {
+    schedule: KeySchedule<H>,
+    suite: CipherSuiteInfo,
+    client_secret: Digest,
+    server_secret: Digest,
+}
+
+impl<H: Hash> HandshakeKeys<H> {
+    /// Protection for records with the traffic secret `secret`.
+    fn protection<A: Aead>(
+        &self,
+        secret: &Digest,
+        new_aead: fn(AeadAlgorithm, &[u8]) -> Option<A>,
+    ) -> TlsResult<RecordProtection<A>> {
+        let mut key = [0; MAX_KEY_LEN];
+        let key = &mut key[..self.suite.key_len];
+        self.schedule.traffic_key(secret, key)?;
+        let iv = self.schedule.traffic_iv(secret)?;
+
+        let aead = new_aead(self.suite.aead, key)
+            .ok_or(TlsError::InternalError("no AEAD for cipher suite"))?;
+        Ok(RecordProtection::new(aead, iv))
+    }
+}
+
+/// Answer to a CertificateRequest, sent after the server's Finished.
+struct ClientAuth<'c> {
+    context: [u8; MAX_CONTEXT_LEN],
+    context_len: usize,
+    /// Certificate to send and the scheme to sign the CertificateVerify with, or `None` to send
+    /// an empty Certificate message.
+    certificate: Option<(ClientCertificate<'c>, SignatureScheme)>,
+}
+
+/// SubjectPublicKeyInfo of the server's certificate, kept to verify its CertificateVerify.
+struct ServerPublicKey {
+    data: [u8; MAX_PUBLIC_KEY_LEN],
+    len: usize,
+}
+
+impl ServerPublicKey {
+    fn empty() -> Self {
+        Self {
+            data: [0; MAX_PUBLIC_KEY_LEN],
+            len: 0,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+
+    fn copy_from(&mut self, public_key: &[u8]) -> TlsResult<()> {
+        let len = public_key.len();
+        if len > MAX_PUBLIC_KEY_LEN {
+            return Err(TlsError::UnsupportedCertificate);
+        }
+
+        self.data[..len].copy_from_slice(public_key);
+        self.len = len;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mtls_message::testing::{new_aead, new_hash, sha256, RingAead, Sha256, NOW};
+    use mtls_message::{
+        record::ContentType,
+        time::{FixedTime, Now},
+        x509::verifier::Verified,
+    };
+    use rustls::{
+        internal::msgs::{
+            base::PayloadU16 as r_PayloadU16,
+            codec::Codec as r_Codec,
+            enums::{
+                Compression as r_Compression, HandshakeType as r_HandshakeType,
+                NamedGroup as r_NamedGroup,
+            },
+            handshake::{
+                HandshakeMessagePayload as r_HandshakeMessagePayload,
+                HandshakePayload as r_HandshakePayload,
+                HelloRetryExtension as r_HelloRetryExtension,
+                HelloRetryRequest as r_HelloRetryRequest, KeyShareEntry as r_KeyShareEntry,
+                Random as r_Random, ServerExtension as r_ServerExtension,
+                ServerHelloPayload as r_ServerHelloPayload, SessionID as r_SessionId,
+            },
+        },
+        CipherSuite as r_CipherSuite, ProtocolVersion as r_ProtocolVersion,
+    };
+    use std::vec::Vec;
+
+    const CONFIG: HelloConfig<'static> = HelloConfig {
+        cipher_suites: &[
+            CipherSuite::TlsAes128GcmSha256,
+            CipherSuite::TlsChaCha20Poly1305Sha256,
+        ],
+        groups: &[NamedGroup::X25519, NamedGroup::Secp256r1],
+        key_share_group: NamedGroup::X25519,
+        signature_schemes: &[SignatureScheme::EcdsaNistp256Sha256],
+        record_size_limit: None,
+    };
+
+    /// Key exchange with a public key made of the group's low byte, and a fixed shared secret.
+    struct TestKeyExchange;
+
+    impl KeyExchange for TestKeyExchange {
+        fn generate(&mut self, group: NamedGroup, out: &mut [u8]) -> TlsResult<usize> {
+            for byte in out[..32].iter_mut() {
+                *byte = u16::from(group) as u8;
+            }
+            Ok(32)
+        }
+
+        fn agree(&mut self, _peer_public_key: &[u8], out: &mut [u8]) -> TlsResult<usize> {
+            out[..32].copy_from_slice(&[5; 32]);
+            Ok(32)
+        }
+    }
+
+    /// Accepts any certificate and signature.
+    struct AcceptAll;
+
+    impl ServerCertVerifier for AcceptAll {
+        fn verify_server_cert(
+            &self,
+            _certificates: &CertificatePayload<'_>,
+            _server_name: ServerName<'_>,
+            _ocsp_response: Option<&[u8]>,
+            _signed_certificate_timestamps: Option<&[u8]>,
+            _now: Now,
+        ) -> TlsResult<Verified> {
+            Ok(Verified::assertion())
+        }
+    }
+
+    impl SignatureVerifier for AcceptAll {
+        fn verify(
+            &self,
+            _public_key: &SubjectPublicKeyInfo<'_>,
+            _algorithm: SignatureAlgorithmIdentifier,
+            _message: &[u8],
+            _signature: &[u8],
+        ) -> TlsResult<()> {
+            Ok(())
+        }
+    }
+
+    fn handshake() -> ClientHandshake<'static, 'static, Sha256, RingAead> {
+        handshake_with(CONFIG)
+    }
+
+    fn handshake_with(
+        config: HelloConfig<'static>,
+    ) -> ClientHandshake<'static, 'static, Sha256, RingAead> {
+        let auth = AuthConfig {
+            server_name: ServerName::new("localhost").unwrap(),
+            server_verifier: &AcceptAll,
+            time: &FixedTime(NOW),
+            signature_verifier: &AcceptAll,
+            client_cert: None,
+        };
+        ClientHandshake::new(
+            config,
+            auth,
+            Random::from([7; 32]),
+            Transcript::new(vec![]),
+            new_hash,
+            new_aead,
+        )
+    }
+
+    fn connection() -> ClientConnection<'static, RingAead> {
+        ClientConnection::new(vec![], vec![], vec![])
+    }
+
+    fn hello_retry_request(
+        session_id: &[u8],
+        group: r_NamedGroup,
+        cookie: Option<&[u8]>,
+    ) -> Vec<u8> {
+        let mut extensions = vec![
+            r_HelloRetryExtension::SupportedVersions(r_ProtocolVersion::TLSv1_3),
+            r_HelloRetryExtension::KeyShare(group),
+        ];
+        if let Some(cookie) = cookie {
+            extensions.push(r_HelloRetryExtension::Cookie(r_PayloadU16::new(
+                cookie.to_vec(),
+            )));
+        }
+
+        let mut bytes = vec![];
+        r_HandshakeMessagePayload {
+            typ: r_HandshakeType::HelloRetryRequest,
+            payload: r_HandshakePayload::HelloRetryRequest(r_HelloRetryRequest {
+                legacy_version: r_ProtocolVersion::TLSv1_2,
+                session_id: r_SessionId::new(session_id),
+                cipher_suite: r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+                extensions,
+            }),
+        }
+        .encode(&mut bytes);
+        bytes
+    }
+
+    fn server_hello(session_id: &[u8], suite: r_CipherSuite, group: r_NamedGroup) -> Vec<u8> {
+        let mut bytes = vec![];
+        r_HandshakeMessagePayload {
+            typ: r_HandshakeType::ServerHello,
+            payload: r_HandshakePayload::ServerHello(r_ServerHelloPayload {
+                legacy_version: r_ProtocolVersion::TLSv1_2,
+                random: r_Random::from_slice(&[2; 32]),
+                session_id: r_SessionId::new(session_id),
+                cipher_suite: suite,
+                compression_method: r_Compression::Null,
+                extensions: vec![
+                    r_ServerExtension::SupportedVersions(r_ProtocolVersion::TLSv1_3),
+                    r_ServerExtension::KeyShare(r_KeyShareEntry::new(group, &[3; 32])),
+                ],
+            }),
+        }
+        .encode(&mut bytes);
+        bytes
+    }
+
+    fn receive(conn: &mut ClientConnection<'_, RingAead>, msg: &[u8]) {
+        let mut record = vec![0x16, 3, 3, 0, 0];
+        record[3..5].copy_from_slice(&(msg.len() as u16).to_be_bytes());
+        record.extend_from_slice(msg);
+        assert_eq!(conn.read_tls(&record).unwrap(), record.len());
+    }
+
+    /// Content types and content of the records queued on `conn`.
+    fn sent_records(conn: &mut ClientConnection<'_, RingAead>) -> Vec<(u8, Vec<u8>)> {
+        let mut buf = [0; 1024];
+        let len = conn.write_tls(&mut buf);
+        assert!(!conn.wants_write());
+
+        let mut records = vec![];
+        let mut rest = &buf[..len];
+        while !rest.is_empty() {
+            let len = 5 + usize::from(u16::from_be_bytes([rest[3], rest[4]]));
+            records.push((rest[0], rest[5..len].to_vec()));
+            rest = &rest[len..];
+        }
+        records
+    }
+
+    /// Handshake message in the single record queued on `conn`.
+    fn sent(conn: &mut ClientConnection<'_, RingAead>) -> Vec<u8> {
+        let mut records = sent_records(conn);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].0, 0x16);
+        records.remove(0).1
+    }
+
+    fn client_hello_extensions(msg: &[u8]) -> Vec<ClientExtension<'_>> {
+        match HandshakeMessage::decode(&mut Decoder::new(msg))
+            .unwrap()
+            .payload
+        {
+            HandshakePayload::ClientHello(client) => client
+                .extensions()
+                .iter()
+                .map(|ext| ext.as_ref().clone())
+                .collect(),
+            _ => panic!("not a ClientHello"),
+        }
+    }
+
+    fn key_share_groups(extensions: &[ClientExtension<'_>]) -> Vec<NamedGroup> {
+        extensions
+            .iter()
+            .find_map(|ext| match ext {
+                ClientExtension::KeyShare(shares) => {
+                    Some(shares.iter().map(|share| share.as_ref().group).collect())
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn cookie<'a>(extensions: &[ClientExtension<'a>]) -> Option<&'a [u8]> {
+        extensions.iter().find_map(|ext| match *ext {
+            ClientExtension::Cookie(cookie) => Some(cookie),
+            _ => None,
+        })
+    }
+
+    /// Starts a handshake and answers ClientHello1 with `hrr`, returning both ClientHellos.
+    fn retried(
+        hs: &mut ClientHandshake<'_, '_, Sha256, RingAead>,
+        conn: &mut ClientConnection<'_, RingAead>,
+        hrr: &[u8],
+    ) -> (Vec<u8>, Vec<u8>) {
+        hs.start(conn, &mut TestKeyExchange).unwrap();
+        let ch1 = sent(conn);
+
+        receive(conn, hrr);
+        assert_eq!(
+            hs.process(conn, &mut TestKeyExchange).unwrap(),
+            Some(Progress::HelloRetry)
+        );
+        (ch1, sent(conn))
+    }
+
+    #[test]
+    fn without_hello_retry() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+        let ch1 = sent(&mut conn);
+
+        assert_eq!(hs.process(&mut conn, &mut TestKeyExchange).unwrap(), None);
+        assert_eq!(hs.server_key_share(), None);
+
+        let sh = server_hello(
+            &[],
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_NamedGroup::X25519,
+        );
+        receive(&mut conn, &sh);
+        assert_eq!(
+            hs.process(&mut conn, &mut TestKeyExchange).unwrap(),
+            Some(Progress::ServerHello {
+                cipher_suite: CipherSuite::TlsAes128GcmSha256,
+                group: NamedGroup::X25519,
+            })
+        );
+        assert_eq!(hs.server_key_share(), Some(&[3; 32][..]));
+        assert_eq!(
+            hs.transcript().current_hash().unwrap(),
+            sha256(&[ch1, sh].concat())
+        );
+    }
+
+    #[test]
+    fn with_hello_retry() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        let hrr = hello_retry_request(&[], r_NamedGroup::secp256r1, Some(&[1, 2, 3]));
+        let (ch1, ch2) = retried(&mut hs, &mut conn, &hrr);
+
+        let extensions = client_hello_extensions(&ch1);
+        assert_eq!(key_share_groups(&extensions), [NamedGroup::X25519]);
+        assert_eq!(cookie(&extensions), None);
+
+        let extensions = client_hello_extensions(&ch2);
+        assert_eq!(key_share_groups(&extensions), [NamedGroup::Secp256r1]);
+        assert_eq!(cookie(&extensions), Some(&[1, 2, 3][..]));
+
+        let sh = server_hello(
+            &[],
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_NamedGroup::secp256r1,
+        );
+        receive(&mut conn, &sh);
+        assert_eq!(
+            hs.process(&mut conn, &mut TestKeyExchange).unwrap(),
+            Some(Progress::ServerHello {
+                cipher_suite: CipherSuite::TlsAes128GcmSha256,
+                group: NamedGroup::Secp256r1,
+            })
+        );
+
+        // ClientHello1 is replaced by a message_hash (RFC 8446, section 4.4.1).
+        let mut message_hash = vec![0xfe, 0, 0, 32];
+        message_hash.extend_from_slice(sha256(&ch1).as_ref());
+        assert_eq!(
+            hs.transcript().current_hash().unwrap(),
+            sha256(&[message_hash, hrr, ch2, sh].concat())
+        );
+    }
+
+    #[test]
+    fn second_hello_retry_request() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        let hrr = hello_retry_request(&[], r_NamedGroup::secp256r1, None);
+        retried(&mut hs, &mut conn, &hrr);
+
+        receive(&mut conn, &hrr);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn hello_retry_request_for_shared_group() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+
+        receive(
+            &mut conn,
+            &hello_retry_request(&[], r_NamedGroup::X25519, None),
+        );
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn server_hello_group_mismatch() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        let hrr = hello_retry_request(&[], r_NamedGroup::secp256r1, None);
+        retried(&mut hs, &mut conn, &hrr);
+
+        let sh = server_hello(
+            &[],
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_NamedGroup::X25519,
+        );
+        receive(&mut conn, &sh);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn server_hello_cipher_suite_changed() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        let hrr = hello_retry_request(&[], r_NamedGroup::secp256r1, None);
+        retried(&mut hs, &mut conn, &hrr);
+
+        let sh = server_hello(
+            &[],
+            r_CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+            r_NamedGroup::secp256r1,
+        );
+        receive(&mut conn, &sh);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn unexpected_message() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+
+        receive(&mut conn, &[0x14, 0, 0, 3, 1, 2, 3]);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn middlebox_compatibility() {
+        let mut hs = handshake().middlebox_compatibility([4; 32]);
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+        let ch1 = sent(&mut conn);
+        match HandshakeMessage::decode(&mut Decoder::new(&ch1))
+            .unwrap()
+            .payload
+        {
+            HandshakePayload::ClientHello(client) => {
+                assert_eq!(client.session_id().as_bytes(), [4; 32])
+            }
+            _ => panic!("not a ClientHello"),
+        }
+
+        let sh = server_hello(
+            &[4; 32],
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_NamedGroup::X25519,
+        );
+        receive(&mut conn, &sh);
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+        assert_eq!(sent_records(&mut conn), [(0x14, vec![1])]);
+    }
+
+    #[test]
+    fn middlebox_compatibility_hello_retry() {
+        let mut hs = handshake().middlebox_compatibility([4; 32]);
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+        sent(&mut conn);
+
+        receive(
+            &mut conn,
+            &hello_retry_request(&[4; 32], r_NamedGroup::secp256r1, None),
+        );
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+        let records = sent_records(&mut conn);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0], (0x14, vec![1]));
+        assert_eq!(records[1].0, 0x16);
+
+        // Only one ChangeCipherSpec is sent.
+        let sh = server_hello(
+            &[4; 32],
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_NamedGroup::secp256r1,
+        );
+        receive(&mut conn, &sh);
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+        assert!(!conn.wants_write());
+    }
+
+    #[test]
+    fn no_change_cipher_spec_by_default() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+        sent(&mut conn);
+
+        let sh = server_hello(
+            &[],
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_NamedGroup::X25519,
+        );
+        receive(&mut conn, &sh);
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+        assert!(!conn.wants_write());
+    }
+
+    /// Handshake that received the ServerHello, and protection to seal the server's handshake
+    /// records with.
+    fn after_server_hello() -> (
+        ClientHandshake<'static, 'static, Sha256, RingAead>,
+        ClientConnection<'static, RingAead>,
+        RecordProtection<RingAead>,
+    ) {
+        after_server_hello_with(CONFIG)
+    }
+
+    fn after_server_hello_with(
+        config: HelloConfig<'static>,
+    ) -> (
+        ClientHandshake<'static, 'static, Sha256, RingAead>,
+        ClientConnection<'static, RingAead>,
+        RecordProtection<RingAead>,
+    ) {
+        let mut hs = handshake_with(config);
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+        sent(&mut conn);
+
+        let sh = server_hello(
+            &[],
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_NamedGroup::X25519,
+        );
+        receive(&mut conn, &sh);
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+
+        let keys = hs.keys.as_ref().unwrap();
+        let server = keys.protection(&keys.server_secret, new_aead).unwrap();
+        (hs, conn, server)
+    }
+
+    fn receive_encrypted(
+        conn: &mut ClientConnection<'_, RingAead>,
+        server: &mut RecordProtection<RingAead>,
+        msg: &[u8],
+    ) {
+        let mut record = vec![0; server.sealed_len(ContentType::Handshake, msg.len())];
+        record[5..5 + msg.len()].copy_from_slice(msg);
+        server
+            .seal(ContentType::Handshake, &mut record, msg.len())
+            .unwrap();
+        assert_eq!(conn.read_tls(&record).unwrap(), record.len());
+    }
+
+    #[test]
+    fn encrypted_extensions() {
+        let (mut hs, mut conn, mut server) = after_server_hello();
+
+        receive_encrypted(&mut conn, &mut server, &[0x08, 0, 0, 2, 0, 0]);
+        assert_eq!(
+            hs.process(&mut conn, &mut TestKeyExchange).unwrap(),
+            Some(Progress::Received(HandshakeType::EncryptedExtensions))
+        );
+    }
+
+    const RECORD_SIZE_LIMIT: HelloConfig<'static> = HelloConfig {
+        record_size_limit: Some(512),
+        ..CONFIG
+    };
+
+    #[test]
+    fn record_size_limit_offered() {
+        let mut hs = handshake_with(RECORD_SIZE_LIMIT);
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+
+        let ch1 = sent(&mut conn);
+        assert!(client_hello_extensions(&ch1).contains(&ClientExtension::RecordSizeLimit(512)));
+    }
+
+    #[test]
+    fn record_size_limit_too_small() {
+        let mut hs = handshake_with(HelloConfig {
+            record_size_limit: Some(63),
+            ..CONFIG
+        });
+        assert!(matches!(
+            hs.start(&mut connection(), &mut TestKeyExchange),
+            Err(TlsError::InternalError(_))
+        ));
+    }
+
+    #[test]
+    fn record_size_limit_negotiated() {
+        let (mut hs, mut conn, mut server) = after_server_hello_with(RECORD_SIZE_LIMIT);
+        receive_encrypted(
+            &mut conn,
+            &mut server,
+            &[0x08, 0, 0, 8, 0, 6, 0, 0x1c, 0, 2, 0, 64],
+        );
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+
+        // Records sent are limited to the server's 64 bytes, content type included.
+        let msg = HandshakeMessage {
+            ty: HandshakeType::Finished,
+            payload: HandshakePayload::Opaque(&[1; 100]),
+        };
+        conn.send_handshake(&msg, &mut Transcript::<Sha256>::new(vec![]))
+            .unwrap();
+        assert_eq!(sent_records(&mut conn).len(), 2);
+
+        // Records received are limited to the client's 512 bytes.
+        receive_encrypted(&mut conn, &mut server, &[0x0b; 512]);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::RecordOverflow)
+        ));
+    }
+
+    #[test]
+    fn record_size_limit_not_negotiated() {
+        let (mut hs, mut conn, mut server) = after_server_hello_with(RECORD_SIZE_LIMIT);
+        receive_encrypted(&mut conn, &mut server, &[0x08, 0, 0, 2, 0, 0]);
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+
+        let msg = HandshakeMessage {
+            ty: HandshakeType::Finished,
+            payload: HandshakePayload::Opaque(&[1; 100]),
+        };
+        conn.send_handshake(&msg, &mut Transcript::<Sha256>::new(vec![]))
+            .unwrap();
+        assert_eq!(sent_records(&mut conn).len(), 1);
+    }
+
+    #[test]
+    fn server_record_size_limit_too_small() {
+        let (mut hs, mut conn, mut server) = after_server_hello_with(RECORD_SIZE_LIMIT);
+        receive_encrypted(
+            &mut conn,
+            &mut server,
+            &[0x08, 0, 0, 8, 0, 6, 0, 0x1c, 0, 2, 0, 63],
+        );
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn extension_not_offered() {
+        let (mut hs, mut conn, mut server) = after_server_hello();
+        receive_encrypted(
+            &mut conn,
+            &mut server,
+            &[0x08, 0, 0, 8, 0, 6, 0, 0x1c, 0, 2, 0, 64],
+        );
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::UnsupportedExtension)
+        ));
+    }
+
+    #[test]
+    fn extension_not_allowed_in_encrypted_extensions() {
+        // supported_groups may be sent, but not supported_versions or key_share.
+        let extensions: [&[u8]; 3] = [
+            &[0, 0x0a, 0, 4, 0, 2, 0, 0x1d],
+            &[0, 0x2b, 0, 2, 3, 4],
+            &[0, 0x33, 0, 2, 0, 0x1d],
+        ];
+        let results = extensions
+            .iter()
+            .map(|ext| {
+                let (mut hs, mut conn, mut server) = after_server_hello();
+                let mut msg = vec![0x08, 0, 0, ext.len() as u8 + 2, 0, ext.len() as u8];
+                msg.extend_from_slice(ext);
+                receive_encrypted(&mut conn, &mut server, &msg);
+                hs.process(&mut conn, &mut TestKeyExchange)
+            })
+            .collect::<Vec<_>>();
+
+        assert!(results[0].is_ok());
+        for result in &results[1..] {
+            assert!(matches!(result, Err(TlsError::UnsupportedExtension)));
+        }
+    }
+
+    #[test]
+    fn new_session_ticket_ignored() {
+        let (mut hs, mut conn, mut server) = after_server_hello();
+        hs.state = State::Connected;
+        let ticket = [
+            0x04, 0, 0, 15, 0, 0, 0x0e, 0x10, 0, 0, 0, 1, 1, 0, 0, 1, 9, 0, 0,
+        ];
+
+        receive_encrypted(&mut conn, &mut server, &ticket);
+        assert_eq!(
+            hs.process(&mut conn, &mut TestKeyExchange).unwrap(),
+            Some(Progress::Ignored(HandshakeType::NewSessionTicket))
+        );
+        // Other post-handshake messages aren't supported.
+        receive_encrypted(&mut conn, &mut server, &[0x18, 0, 0, 1, 0]);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn certificate_before_encrypted_extensions() {
+        let (mut hs, mut conn, mut server) = after_server_hello();
+
+        receive_encrypted(&mut conn, &mut server, &[0x0b, 0, 0, 4, 0, 0, 0, 0]);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn certificate_request_without_signature_algorithms() {
+        let (mut hs, mut conn, mut server) = after_server_hello();
+        receive_encrypted(&mut conn, &mut server, &[0x08, 0, 0, 2, 0, 0]);
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+
+        receive_encrypted(&mut conn, &mut server, &[0x0d, 0, 0, 3, 0, 0, 0]);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::MissingExtension)
+        ));
+    }
+
+    #[test]
+    fn empty_server_certificate() {
+        let (mut hs, mut conn, mut server) = after_server_hello();
+        receive_encrypted(&mut conn, &mut server, &[0x08, 0, 0, 2, 0, 0]);
+        hs.process(&mut conn, &mut TestKeyExchange).unwrap();
+
+        receive_encrypted(&mut conn, &mut server, &[0x0b, 0, 0, 4, 0, 0, 0, 0]);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::DecodeError)
+        ));
+    }
+
+    #[test]
+    fn server_hello_with_pending_handshake() {
+        let mut hs = handshake();
+        let mut conn = connection();
+        hs.start(&mut conn, &mut TestKeyExchange).unwrap();
+        sent(&mut conn);
+
+        // The EncryptedExtensions must not share a record with the ServerHello.
+        let mut sh = server_hello(
+            &[],
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_NamedGroup::X25519,
+        );
+        sh.extend_from_slice(&[0x08, 0, 0, 2, 0, 0]);
+        receive(&mut conn, &sh);
+        assert!(matches!(
+            hs.process(&mut conn, &mut TestKeyExchange),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+}
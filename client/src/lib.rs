@@ -1,4 +1,10 @@
 #![no_std]
 
 #[cfg(test)]
+#[macro_use]
 extern crate std;
+
+pub mod certificate;
+pub mod connection;
+pub mod handshake;
+pub mod validation;
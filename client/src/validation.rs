@@ -0,0 +1,581 @@
+use crate::handshake::MAX_COOKIE_LEN;
+use mtls_message::{
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::{
+        client::ClientHelloPayload,
+        enums::{CompressionMethod, ExtensionType, ProtocolVersion},
+        extension::{client::ClientExtension, server::ServerExtension},
+        server::ServerHelloPayload,
+    },
+};
+
+/// Checks the server's ServerHello is a valid answer to the ClientHello that was sent (RFC 8446,
+/// section 4.1.3).
+///
+/// On failure, the error is the alert to send to the server. HelloRetryRequests aren't
+/// ServerHellos as far as this is concerned, and must be handled before getting here.
+pub fn validate_server_hello(
+    client: &ClientHelloPayload<'_>,
+    server: &ServerHelloPayload<'_>,
+) -> TlsResult<()> {
+    validate_version(client, server)?;
+
+    if server.session_id() != client.session_id() {
+        return Err(TlsError::IllegalParameter);
+    }
+    if !client
+        .cipher_suites()
+        .iter()
+        .any(|suite| suite == server.cipher_suite())
+    {
+        return Err(TlsError::IllegalParameter);
+    }
+    if server.compression_method() != CompressionMethod::Null {
+        return Err(TlsError::IllegalParameter);
+    }
+
+    validate_extensions(client, server)
+}
+
+/// Checks a HelloRetryRequest is a valid answer to the ClientHello that was sent, and that it
+/// asks for a change to it (RFC 8446, section 4.1.4).
+///
+/// On failure, the error is the alert to send to the server.
+pub fn validate_hello_retry_request(
+    client: &ClientHelloPayload<'_>,
+    retry: &ServerHelloPayload<'_>,
+) -> TlsResult<()> {
+    let selected = server_extensions(retry).find_map(|ext| match ext {
+        ServerExtension::SupportedVersions(version) => Some(version),
+        _ => None,
+    });
+    match selected {
+        Some(ProtocolVersion::TLSv1_3) => {}
+        Some(_) => return Err(TlsError::IllegalParameter),
+        None => return Err(TlsError::ProtocolVersion),
+    }
+
+    if retry.session_id() != client.session_id() {
+        return Err(TlsError::IllegalParameter);
+    }
+    if !client
+        .cipher_suites()
+        .iter()
+        .any(|suite| suite == retry.cipher_suite())
+    {
+        return Err(TlsError::IllegalParameter);
+    }
+    if retry.compression_method() != CompressionMethod::Null {
+        return Err(TlsError::IllegalParameter);
+    }
+
+    let mut changed = false;
+    for (i, ext) in server_extensions(retry).enumerate() {
+        let ty = ext.ty();
+        // Cookies are sent without being offered.
+        if ty != ExtensionType::Cookie && !client_extensions(client).any(|c| c.ty() == ty) {
+            return Err(TlsError::UnsupportedExtension);
+        }
+        if server_extensions(retry).take(i).any(|s| s.ty() == ty) {
+            return Err(TlsError::IllegalParameter);
+        }
+
+        match ext {
+            ServerExtension::SupportedVersions(_) => {}
+            ServerExtension::HelloRetryKeyShare(group) => {
+                // The group must be supported, and not one a key share was already sent for.
+                let supported = client_extensions(client).any(|c| match c {
+                    ClientExtension::SupportedGroups(groups) => groups.iter().any(|g| g == group),
+                    _ => false,
+                });
+                let shared = client_extensions(client).any(|c| match c {
+                    ClientExtension::KeyShare(shares) => {
+                        shares.iter().any(|s| s.as_ref().group == group)
+                    }
+                    _ => false,
+                });
+                if !supported || shared {
+                    return Err(TlsError::IllegalParameter);
+                }
+                changed = true;
+            }
+            ServerExtension::Cookie(cookie) => {
+                if cookie.len() > MAX_COOKIE_LEN {
+                    return Err(TlsError::IllegalParameter);
+                }
+                changed = true;
+            }
+            _ => return Err(TlsError::IllegalParameter),
+        }
+    }
+
+    if !changed {
+        return Err(TlsError::IllegalParameter);
+    }
+    Ok(())
+}
+
+/// Checks the version selected by the server was offered, and that a server which supports
+/// TLS 1.3 wasn't made to negotiate an earlier version.
+fn validate_version(
+    client: &ClientHelloPayload<'_>,
+    server: &ServerHelloPayload<'_>,
+) -> TlsResult<()> {
+    let offered = |version: ProtocolVersion| {
+        client_extensions(client).any(|ext| match ext {
+            ClientExtension::SupportedVersions(versions) => versions.iter().any(|v| v == version),
+            _ => false,
+        })
+    };
+    let selected = server_extensions(server).find_map(|ext| match ext {
+        ServerExtension::SupportedVersions(version) => Some(version),
+        _ => None,
+    });
+
+    match selected {
+        // legacy_version stays at TLS 1.2 when TLS 1.3 is negotiated (RFC 8446, section 4.1.3).
+        Some(ProtocolVersion::TLSv1_3) if server.server_version() != ProtocolVersion::TLSv1_2 => {
+            Err(TlsError::IllegalParameter)
+        }
+        Some(ProtocolVersion::TLSv1_3) if offered(ProtocolVersion::TLSv1_3) => Ok(()),
+        Some(_) => Err(TlsError::IllegalParameter),
+        None if offered(ProtocolVersion::TLSv1_3) && server.random().has_downgrade_sentinel() => {
+            Err(TlsError::IllegalParameter)
+        }
+        // Earlier versions aren't supported.
+        None => Err(TlsError::ProtocolVersion),
+    }
+}
+
+/// Checks the server only sent extensions that were offered, each once, and that belong in a
+/// TLS 1.3 ServerHello.
+fn validate_extensions(
+    client: &ClientHelloPayload<'_>,
+    server: &ServerHelloPayload<'_>,
+) -> TlsResult<()> {
+    for (i, ext) in server_extensions(server).enumerate() {
+        let ty = ext.ty();
+        if !client_extensions(client).any(|c| c.ty() == ty) {
+            return Err(TlsError::UnsupportedExtension);
+        }
+        if server_extensions(server).take(i).any(|s| s.ty() == ty) {
+            return Err(TlsError::IllegalParameter);
+        }
+
+        match ext {
+            ServerExtension::SupportedVersions(_) => {}
+            ServerExtension::KeyShare(share) => {
+                let offered = client_extensions(client).any(|c| match c {
+                    ClientExtension::KeyShare(shares) => {
+                        shares.iter().any(|s| s.as_ref().group == share.group)
+                    }
+                    _ => false,
+                });
+                if !offered {
+                    return Err(TlsError::IllegalParameter);
+                }
+            }
+            _ => return Err(TlsError::IllegalParameter),
+        }
+    }
+    Ok(())
+}
+
+fn client_extensions<'a>(
+    client: &ClientHelloPayload<'a>,
+) -> impl Iterator<Item = ClientExtension<'a>> + 'a {
+    client.extensions().iter().map(|ext| ext.as_ref().clone())
+}
+
+fn server_extensions<'a>(
+    server: &ServerHelloPayload<'a>,
+) -> impl Iterator<Item = ServerExtension<'a>> + 'a {
+    server.extensions().iter().map(|ext| ext.as_ref().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mtls_message::{handshake::random::DOWNGRADE_TLS12, Codec, Decoder};
+    use rustls::{
+        internal::msgs::{
+            base::{Payload as r_Payload, PayloadU16 as r_PayloadU16},
+            codec::Codec as r_Codec,
+            enums::{
+                Compression as r_Compression, ExtensionType as r_ExtensionType,
+                NamedGroup as r_NamedGroup,
+            },
+            handshake::{
+                ClientExtension as r_ClientExtension, ClientHelloPayload as r_ClientHelloPayload,
+                HelloRetryExtension as r_HelloRetryExtension,
+                HelloRetryRequest as r_HelloRetryRequest, KeyShareEntry as r_KeyShareEntry,
+                Random as r_Random, ServerExtension as r_ServerExtension,
+                ServerHelloPayload as r_ServerHelloPayload, SessionID as r_SessionId,
+                UnknownExtension as r_UnknownExtension,
+            },
+        },
+        CipherSuite as r_CipherSuite, ProtocolVersion as r_ProtocolVersion,
+        SignatureScheme as r_SignatureScheme,
+    };
+
+    fn client_hello() -> r_ClientHelloPayload {
+        r_ClientHelloPayload {
+            client_version: r_ProtocolVersion::TLSv1_2,
+            random: r_Random::from_slice(&[0; 32]),
+            session_id: r_SessionId::new(&[1; 32]),
+            cipher_suites: vec![
+                r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+                r_CipherSuite::TLS13_AES_256_GCM_SHA384,
+            ],
+            compression_methods: vec![r_Compression::Null],
+            extensions: vec![
+                r_ClientExtension::SupportedVersions(vec![r_ProtocolVersion::TLSv1_3]),
+                r_ClientExtension::NamedGroups(vec![r_NamedGroup::X25519]),
+                r_ClientExtension::KeyShare(vec![r_KeyShareEntry::new(
+                    r_NamedGroup::X25519,
+                    &[1; 32],
+                )]),
+                r_ClientExtension::SignatureAlgorithms(vec![
+                    r_SignatureScheme::ECDSA_NISTP256_SHA256,
+                ]),
+            ],
+        }
+    }
+
+    fn server_hello() -> r_ServerHelloPayload {
+        r_ServerHelloPayload {
+            legacy_version: r_ProtocolVersion::TLSv1_2,
+            random: r_Random::from_slice(&[2; 32]),
+            session_id: r_SessionId::new(&[1; 32]),
+            cipher_suite: r_CipherSuite::TLS13_AES_256_GCM_SHA384,
+            compression_method: r_Compression::Null,
+            extensions: vec![
+                r_ServerExtension::SupportedVersions(r_ProtocolVersion::TLSv1_3),
+                r_ServerExtension::KeyShare(r_KeyShareEntry::new(r_NamedGroup::X25519, &[3; 32])),
+            ],
+        }
+    }
+
+    fn validate(client: r_ClientHelloPayload, server: r_ServerHelloPayload) -> TlsResult<()> {
+        let client_bytes = client.get_encoding();
+        let server_bytes = server.get_encoding();
+        let client = ClientHelloPayload::decode(&mut Decoder::new(&client_bytes)).unwrap();
+        let server = ServerHelloPayload::decode(&mut Decoder::new(&server_bytes)).unwrap();
+
+        validate_server_hello(&client, &server)
+    }
+
+    fn hello_retry_request() -> r_HelloRetryRequest {
+        r_HelloRetryRequest {
+            legacy_version: r_ProtocolVersion::TLSv1_2,
+            session_id: r_SessionId::new(&[1; 32]),
+            cipher_suite: r_CipherSuite::TLS13_AES_256_GCM_SHA384,
+            extensions: vec![
+                r_HelloRetryExtension::SupportedVersions(r_ProtocolVersion::TLSv1_3),
+                r_HelloRetryExtension::KeyShare(r_NamedGroup::secp256r1),
+            ],
+        }
+    }
+
+    /// ClientHello supporting secp256r1 without a key share for it.
+    fn retry_client_hello() -> r_ClientHelloPayload {
+        let mut client = client_hello();
+        client.extensions[1] =
+            r_ClientExtension::NamedGroups(vec![r_NamedGroup::X25519, r_NamedGroup::secp256r1]);
+        client
+    }
+
+    fn validate_retry(client: r_ClientHelloPayload, retry: r_HelloRetryRequest) -> TlsResult<()> {
+        let client_bytes = client.get_encoding();
+        let retry_bytes = retry.get_encoding();
+        let client = ClientHelloPayload::decode(&mut Decoder::new(&client_bytes)).unwrap();
+        let retry = ServerHelloPayload::decode(&mut Decoder::new(&retry_bytes)).unwrap();
+
+        assert!(retry.random().is_hello_retry_request());
+        validate_hello_retry_request(&client, &retry)
+    }
+
+    fn downgrade_random() -> r_Random {
+        let mut bytes = [2; 32];
+        bytes[24..].copy_from_slice(&DOWNGRADE_TLS12);
+        r_Random::from_slice(&bytes)
+    }
+
+    #[test]
+    fn valid() {
+        assert!(validate(client_hello(), server_hello()).is_ok());
+    }
+
+    #[test]
+    fn session_id_not_echoed() {
+        let mut server = server_hello();
+        server.session_id = r_SessionId::new(&[2; 32]);
+
+        assert!(matches!(
+            validate(client_hello(), server),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn cipher_suite_not_offered() {
+        let mut server = server_hello();
+        server.cipher_suite = r_CipherSuite::TLS13_CHACHA20_POLY1305_SHA256;
+
+        assert!(matches!(
+            validate(client_hello(), server),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn compression() {
+        let mut server = server_hello();
+        server.compression_method = r_Compression::Deflate;
+
+        assert!(matches!(
+            validate(client_hello(), server),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn legacy_version_not_tls12() {
+        for version in [r_ProtocolVersion::TLSv1_3, r_ProtocolVersion::TLSv1_1] {
+            let mut server = server_hello();
+            server.legacy_version = version;
+
+            assert!(matches!(
+                validate(client_hello(), server),
+                Err(TlsError::IllegalParameter)
+            ));
+        }
+    }
+
+    #[test]
+    fn version_not_offered() {
+        let mut client = client_hello();
+        client.extensions[0] =
+            r_ClientExtension::SupportedVersions(vec![r_ProtocolVersion::TLSv1_2]);
+
+        assert!(matches!(
+            validate(client, server_hello()),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn version_before_tls13_in_extension() {
+        let mut client = client_hello();
+        client.extensions[0] = r_ClientExtension::SupportedVersions(vec![
+            r_ProtocolVersion::TLSv1_3,
+            r_ProtocolVersion::TLSv1_2,
+        ]);
+        let mut server = server_hello();
+        server.extensions[0] = r_ServerExtension::SupportedVersions(r_ProtocolVersion::TLSv1_2);
+
+        assert!(matches!(
+            validate(client, server),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn tls12_server() {
+        let mut server = server_hello();
+        server.extensions.clear();
+
+        assert!(matches!(
+            validate(client_hello(), server),
+            Err(TlsError::ProtocolVersion)
+        ));
+    }
+
+    #[test]
+    fn downgrade_sentinel() {
+        let mut server = server_hello();
+        server.random = downgrade_random();
+        server.extensions.clear();
+
+        assert!(matches!(
+            validate(client_hello(), server),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn downgrade_sentinel_ignored_in_tls13() {
+        let mut server = server_hello();
+        server.random = downgrade_random();
+
+        assert!(validate(client_hello(), server).is_ok());
+    }
+
+    #[test]
+    fn extension_not_offered() {
+        let mut client = client_hello();
+        client.extensions.remove(2);
+
+        assert!(matches!(
+            validate(client, server_hello()),
+            Err(TlsError::UnsupportedExtension)
+        ));
+    }
+
+    #[test]
+    fn unknown_extension_not_offered() {
+        let mut server = server_hello();
+        server
+            .extensions
+            .push(r_ServerExtension::Unknown(r_UnknownExtension {
+                typ: r_ExtensionType::Unknown(0xff01),
+                payload: r_Payload::new(vec![]),
+            }));
+
+        assert!(matches!(
+            validate(client_hello(), server),
+            Err(TlsError::UnsupportedExtension)
+        ));
+    }
+
+    #[test]
+    fn extension_not_allowed_in_server_hello() {
+        let mut client = client_hello();
+        client
+            .extensions
+            .push(r_ClientExtension::Unknown(r_UnknownExtension {
+                typ: r_ExtensionType::Unknown(0xff01),
+                payload: r_Payload::new(vec![]),
+            }));
+        let mut server = server_hello();
+        server
+            .extensions
+            .push(r_ServerExtension::Unknown(r_UnknownExtension {
+                typ: r_ExtensionType::Unknown(0xff01),
+                payload: r_Payload::new(vec![]),
+            }));
+
+        assert!(matches!(
+            validate(client, server),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn duplicate_extension() {
+        let mut server = server_hello();
+        server.extensions.push(r_ServerExtension::SupportedVersions(
+            r_ProtocolVersion::TLSv1_3,
+        ));
+
+        assert!(matches!(
+            validate(client_hello(), server),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn key_share_group_not_offered() {
+        let mut server = server_hello();
+        server.extensions[1] =
+            r_ServerExtension::KeyShare(r_KeyShareEntry::new(r_NamedGroup::secp256r1, &[3; 65]));
+
+        assert!(matches!(
+            validate(client_hello(), server),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn valid_hello_retry_request() {
+        assert!(validate_retry(retry_client_hello(), hello_retry_request()).is_ok());
+    }
+
+    #[test]
+    fn hello_retry_request_cookie_only() {
+        let mut retry = hello_retry_request();
+        retry.extensions[1] = r_HelloRetryExtension::Cookie(r_PayloadU16::new(vec![1, 2, 3]));
+
+        assert!(validate_retry(client_hello(), retry).is_ok());
+    }
+
+    #[test]
+    fn hello_retry_request_long_cookie() {
+        let mut retry = hello_retry_request();
+        retry.extensions[1] =
+            r_HelloRetryExtension::Cookie(r_PayloadU16::new(vec![1; MAX_COOKIE_LEN + 1]));
+
+        assert!(matches!(
+            validate_retry(client_hello(), retry),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn hello_retry_request_without_change() {
+        let mut retry = hello_retry_request();
+        retry.extensions.pop();
+
+        assert!(matches!(
+            validate_retry(retry_client_hello(), retry),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn hello_retry_request_for_shared_group() {
+        let mut retry = hello_retry_request();
+        retry.extensions[1] = r_HelloRetryExtension::KeyShare(r_NamedGroup::X25519);
+
+        assert!(matches!(
+            validate_retry(retry_client_hello(), retry),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn hello_retry_request_for_unsupported_group() {
+        assert!(matches!(
+            validate_retry(client_hello(), hello_retry_request()),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn hello_retry_request_without_supported_versions() {
+        let mut retry = hello_retry_request();
+        retry.extensions.remove(0);
+
+        assert!(matches!(
+            validate_retry(retry_client_hello(), retry),
+            Err(TlsError::ProtocolVersion)
+        ));
+    }
+
+    #[test]
+    fn hello_retry_request_cipher_suite_not_offered() {
+        let mut retry = hello_retry_request();
+        retry.cipher_suite = r_CipherSuite::TLS13_CHACHA20_POLY1305_SHA256;
+
+        assert!(matches!(
+            validate_retry(retry_client_hello(), retry),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn hello_retry_request_extension_not_offered() {
+        let mut retry = hello_retry_request();
+        retry
+            .extensions
+            .push(r_HelloRetryExtension::Unknown(r_UnknownExtension {
+                typ: r_ExtensionType::Unknown(0xff01),
+                payload: r_Payload::new(vec![]),
+            }));
+
+        assert!(matches!(
+            validate_retry(retry_client_hello(), retry),
+            Err(TlsError::UnsupportedExtension)
+        ));
+    }
+}
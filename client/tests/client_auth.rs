@@ -0,0 +1,268 @@
+use crate::{keytype::KeyType, server};
+use mtls_client::{
+    certificate::{ClientCertificate, ResolvesClientCert},
+    connection::ClientConnection,
+    handshake::{AuthConfig, ClientHandshake, HelloConfig, Progress},
+};
+use mtls_message::{
+    crypto::{Signer, SigningKey, SoftwareSigningKey},
+    error::Result as TlsResult,
+    handshake::{
+        enums::{CipherSuite, NamedGroup, SignatureScheme},
+        random::Random,
+        transcript::Transcript,
+    },
+    testing::{new_aead, new_hash, RingAead, RingKeyExchange, RingSigner, RingVerifier, NOW},
+    time::FixedTime,
+    x509::{
+        identity::ServerName,
+        key::PrivateKey,
+        path::{PathValidator, TrustAnchor},
+    },
+};
+use rustls::{
+    internal::pemfile, AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient,
+    ClientCertVerified, ClientCertVerifier, DistinguishedNames, ProtocolVersion, RootCertStore,
+    ServerSession, Session, TLSError,
+};
+use std::{cell::Cell, fs, fs::File, io::BufReader, sync::Arc};
+
+const CONFIG: HelloConfig<'static> = HelloConfig {
+    cipher_suites: &[CipherSuite::TlsAes128GcmSha256],
+    groups: &[NamedGroup::X25519],
+    key_share_group: NamedGroup::X25519,
+    signature_schemes: &[
+        SignatureScheme::EcdsaNistp256Sha256,
+        SignatureScheme::RsaPssSha256,
+    ],
+    record_size_limit: None,
+};
+
+/// Checks client certificates like `inner`, but at a time the test certificates are valid,
+/// where rustls' verifiers use the current time.
+struct FixedTimeVerifier {
+    inner: Arc<dyn ClientCertVerifier>,
+    ca: Vec<u8>,
+}
+
+impl ClientCertVerifier for FixedTimeVerifier {
+    fn offer_client_auth(&self) -> bool {
+        self.inner.offer_client_auth()
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        self.inner.client_auth_mandatory()
+    }
+
+    fn client_auth_root_subjects(&self) -> DistinguishedNames {
+        self.inner.client_auth_root_subjects()
+    }
+
+    fn verify_client_cert(
+        &self,
+        presented_certs: &[rustls::Certificate],
+    ) -> Result<ClientCertVerified, TLSError> {
+        let (end_entity, intermediates) = presented_certs
+            .split_first()
+            .ok_or(TLSError::NoCertificatesPresented)?;
+        let cert = webpki::EndEntityCert::from(untrusted::Input::from(&end_entity.0))
+            .map_err(TLSError::WebPKIError)?;
+        let ca = untrusted::Input::from(&self.ca);
+        let anchors = [webpki::trust_anchor_util::cert_der_as_trust_anchor(ca).unwrap()];
+        let intermediates = intermediates
+            .iter()
+            .map(|cert| untrusted::Input::from(&cert.0))
+            .collect::<Vec<_>>();
+
+        cert.verify_is_valid_tls_client_cert(
+            &[
+                &webpki::ECDSA_P256_SHA256,
+                &webpki::ECDSA_P256_SHA384,
+                &webpki::ECDSA_P384_SHA256,
+                &webpki::ECDSA_P384_SHA384,
+                &webpki::RSA_PKCS1_2048_8192_SHA256,
+            ],
+            &webpki::TLSClientTrustAnchors(&anchors),
+            &intermediates,
+            webpki::Time::from_seconds_since_unix_epoch(NOW),
+        )
+        .map_err(TLSError::WebPKIError)?;
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+#[test]
+fn rsa_client_certificate() {
+    test_client_certificate(KeyType::RSA);
+}
+
+#[test]
+fn ecdsa_client_certificate() {
+    test_client_certificate(KeyType::ECDSA);
+}
+
+#[test]
+fn no_client_certificate() {
+    let kt = KeyType::ECDSA;
+    let verifier = AllowAnyAuthenticatedClient::new(root_store(kt));
+    let (server, result) = handshake(kt, verifier, None);
+
+    assert!(matches!(result, Err(TLSError::NoCertificatesPresented)));
+    assert!(server.get_peer_certificates().is_none());
+}
+
+#[test]
+fn optional_client_certificate() {
+    let kt = KeyType::ECDSA;
+    let verifier = AllowAnyAnonymousOrAuthenticatedClient::new(root_store(kt));
+    let (server, result) = handshake(kt, verifier, None);
+
+    result.unwrap();
+    assert!(!server.is_handshaking());
+    assert!(server.get_peer_certificates().is_none());
+}
+
+#[test]
+fn external_signing_key() {
+    let kt = KeyType::RSA;
+    let chain = pemfile::certs(&mut reader(kt, "client.fullchain")).unwrap();
+    let key = pemfile::pkcs8_private_keys(&mut reader(kt, "client.key")).unwrap();
+    let chain_der = chain.iter().map(|cert| &cert.0[..]).collect::<Vec<_>>();
+    let key = ExternalKey {
+        key: PrivateKey::from_pkcs8(&key[0].0).unwrap(),
+        scheme: Cell::new(None),
+    };
+    let certificate = ClientCertificate {
+        chain: &chain_der,
+        key: &key,
+    };
+
+    let (server, result) = handshake(kt, verifier(kt), Some(&certificate));
+    result.unwrap();
+    assert_eq!(key.scheme.get(), Some(SignatureScheme::RsaPssSha256));
+    assert_eq!(server.get_peer_certificates(), Some(chain));
+}
+
+/// Stands in for a key held in a secure element, signing only with RSA-PSS and SHA-256 and
+/// recording the scheme it was asked for.
+struct ExternalKey<'a> {
+    key: PrivateKey<'a>,
+    scheme: Cell<Option<SignatureScheme>>,
+}
+
+impl<'a> SigningKey for ExternalKey<'a> {
+    fn schemes(&self) -> &[SignatureScheme] {
+        &[SignatureScheme::RsaPssSha256]
+    }
+
+    fn sign(&self, scheme: SignatureScheme, message: &[u8], out: &mut [u8]) -> TlsResult<usize> {
+        self.scheme.set(Some(scheme));
+        RingSigner.sign(&self.key, scheme, message, out)
+    }
+}
+
+fn test_client_certificate(kt: KeyType) {
+    let chain = pemfile::certs(&mut reader(kt, "client.fullchain")).unwrap();
+    let key = pemfile::pkcs8_private_keys(&mut reader(kt, "client.key")).unwrap();
+    let chain_der = chain.iter().map(|cert| &cert.0[..]).collect::<Vec<_>>();
+    let key = SoftwareSigningKey::new(PrivateKey::from_pkcs8(&key[0].0).unwrap(), RingSigner);
+    let certificate = ClientCertificate {
+        chain: &chain_der,
+        key: &key,
+    };
+
+    let (server, result) = handshake(kt, verifier(kt), Some(&certificate));
+    result.unwrap();
+    assert!(!server.is_handshaking());
+    assert_eq!(server.get_peer_certificates(), Some(chain));
+}
+
+/// Runs a handshake against a rustls server that authenticates clients with `verifier`,
+/// returning the server and the result of processing the client's last flight.
+fn handshake(
+    kt: KeyType,
+    verifier: Arc<dyn ClientCertVerifier>,
+    client_cert: Option<&dyn ResolvesClientCert>,
+) -> (ServerSession, Result<(), TLSError>) {
+    let config = server::config_with_verifier(
+        ProtocolVersion::TLSv1_3,
+        kt.cert_chain(),
+        kt.priv_key(),
+        verifier,
+    );
+    let mut server = server::session(&Arc::new(config));
+
+    let ca = fs::read(kt.path("ca.der")).unwrap();
+    let anchors = [TrustAnchor::from_certificate(&ca).unwrap()];
+    let validator = PathValidator::new(&anchors, RingVerifier);
+    let auth = AuthConfig {
+        server_name: ServerName::new("localhost").unwrap(),
+        server_verifier: &validator,
+        time: &FixedTime(NOW),
+        signature_verifier: &RingVerifier,
+        client_cert,
+    };
+    let mut conn = ClientConnection::<RingAead>::new(vec![], vec![], vec![]);
+    let mut kx = RingKeyExchange::default();
+    let mut hs = ClientHandshake::new(
+        CONFIG,
+        auth,
+        Random::from([7; 32]),
+        Transcript::new(vec![]),
+        new_hash,
+        new_aead,
+    );
+    hs.start(&mut conn, &mut kx).unwrap();
+
+    send(&mut conn, &mut server).unwrap();
+    receive(&mut conn, &mut server);
+    let mut progress = None;
+    while let Some(p) = hs.process(&mut conn, &mut kx).unwrap() {
+        progress = Some(p);
+    }
+    assert_eq!(progress, Some(Progress::Connected));
+
+    let result = send(&mut conn, &mut server);
+    (server, result)
+}
+
+/// Sends the client's queued records to the server, returning how it processed them.
+fn send(
+    conn: &mut ClientConnection<'_, RingAead>,
+    server: &mut ServerSession,
+) -> Result<(), TLSError> {
+    let mut buf = [0; 8192];
+    while conn.wants_write() {
+        let len = conn.write_tls(&mut buf);
+        server.read_tls(&mut &buf[..len]).unwrap();
+    }
+    server.process_new_packets()
+}
+
+/// Sends the server's queued records to the client.
+fn receive(conn: &mut ClientConnection<'_, RingAead>, server: &mut ServerSession) {
+    let mut records = vec![];
+    while server.wants_write() {
+        server.write_tls(&mut records).unwrap();
+    }
+    assert_eq!(conn.read_tls(&records).unwrap(), records.len());
+}
+
+fn verifier(kt: KeyType) -> Arc<dyn ClientCertVerifier> {
+    Arc::new(FixedTimeVerifier {
+        inner: AllowAnyAuthenticatedClient::new(root_store(kt)),
+        ca: fs::read(kt.path("ca.der")).unwrap(),
+    })
+}
+
+fn root_store(kt: KeyType) -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots
+        .add_pem_file(&mut BufReader::new(File::open(kt.ca_path()).unwrap()))
+        .unwrap();
+    roots
+}
+
+fn reader(kt: KeyType, file: &str) -> BufReader<File> {
+    BufReader::new(File::open(kt.path(file)).unwrap())
+}
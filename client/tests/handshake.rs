@@ -0,0 +1,203 @@
+use crate::{keytype::KeyType, server};
+use mtls_client::{
+    connection::ClientConnection,
+    handshake::{AuthConfig, ClientHandshake, HelloConfig, Progress},
+};
+use mtls_message::{
+    error::Error as TlsError,
+    handshake::{
+        enums::{CipherSuite, NamedGroup, SignatureScheme},
+        random::Random,
+        transcript::Transcript,
+    },
+    testing::{new_aead, new_hash, RingAead, RingKeyExchange, RingVerifier, NOW},
+    time::FixedTime,
+    x509::{
+        identity::ServerName,
+        path::{PathValidator, TrustAnchor},
+    },
+};
+use rustls::{ProtocolVersion, ServerSession, Session};
+use std::{fs, sync::Arc};
+
+const CONFIG: HelloConfig<'static> = HelloConfig {
+    cipher_suites: &[CipherSuite::TlsAes128GcmSha256],
+    groups: &[NamedGroup::X25519],
+    key_share_group: NamedGroup::X25519,
+    signature_schemes: &[SignatureScheme::EcdsaNistp256Sha256],
+    record_size_limit: None,
+};
+
+/// rustls can't be limited to particular groups, and supports X25519, secp384r1 and secp256r1.
+/// Pre-sharing X448, which it doesn't support, while listing X25519 makes it ask for X25519.
+const RETRY_CONFIG: HelloConfig<'static> = HelloConfig {
+    groups: &[NamedGroup::X448, NamedGroup::X25519],
+    key_share_group: NamedGroup::X448,
+    ..CONFIG
+};
+
+#[test]
+fn hello_retry_request() {
+    let mut server = server();
+    let mut conn = ClientConnection::<RingAead>::new(vec![], vec![], vec![]);
+    let mut kx = RingKeyExchange::default();
+    let ca = fs::read(KeyType::ECDSA.path("ca.der")).unwrap();
+    let anchors = [TrustAnchor::from_certificate(&ca).unwrap()];
+    let validator = PathValidator::new(&anchors, RingVerifier);
+    let mut hs = ClientHandshake::new(
+        RETRY_CONFIG,
+        auth_config(&validator),
+        Random::from([7; 32]),
+        Transcript::new(vec![]),
+        new_hash,
+        new_aead,
+    );
+    hs.start(&mut conn, &mut kx).unwrap();
+
+    transfer(&mut conn, &mut server);
+    assert_eq!(
+        hs.process(&mut conn, &mut kx).unwrap(),
+        Some(Progress::HelloRetry)
+    );
+
+    transfer(&mut conn, &mut server);
+    assert_eq!(
+        hs.process(&mut conn, &mut kx).unwrap(),
+        Some(Progress::ServerHello {
+            cipher_suite: CipherSuite::TlsAes128GcmSha256,
+            group: NamedGroup::X25519,
+        })
+    );
+    assert_eq!(hs.server_key_share().unwrap().len(), 32);
+}
+
+#[test]
+fn middlebox_compatibility() {
+    let mut server = server();
+    let mut conn = ClientConnection::<RingAead>::new(vec![], vec![], vec![]);
+    let mut kx = RingKeyExchange::default();
+    let ca = fs::read(KeyType::ECDSA.path("ca.der")).unwrap();
+    let anchors = [TrustAnchor::from_certificate(&ca).unwrap()];
+    let validator = PathValidator::new(&anchors, RingVerifier);
+    let mut hs = ClientHandshake::new(
+        CONFIG,
+        auth_config(&validator),
+        Random::from([7; 32]),
+        Transcript::new(vec![]),
+        new_hash,
+        new_aead,
+    )
+    .middlebox_compatibility([4; 32]);
+    hs.start(&mut conn, &mut kx).unwrap();
+
+    // The server's ChangeCipherSpec follows the ServerHello, and the client's is sent after it.
+    transfer(&mut conn, &mut server);
+    assert!(matches!(
+        hs.process(&mut conn, &mut kx).unwrap(),
+        Some(Progress::ServerHello { .. })
+    ));
+    assert!(conn.wants_write());
+    transfer(&mut conn, &mut server);
+}
+
+#[test]
+fn full_handshake() {
+    let mut server = server();
+    let mut conn = ClientConnection::<RingAead>::new(vec![], vec![], vec![]);
+    let mut kx = RingKeyExchange::default();
+    let ca = fs::read(KeyType::ECDSA.path("ca.der")).unwrap();
+    let anchors = [TrustAnchor::from_certificate(&ca).unwrap()];
+    let validator = PathValidator::new(&anchors, RingVerifier);
+    let mut hs = ClientHandshake::new(
+        CONFIG,
+        auth_config(&validator),
+        Random::from([7; 32]),
+        Transcript::new(vec![]),
+        new_hash,
+        new_aead,
+    );
+    hs.start(&mut conn, &mut kx).unwrap();
+
+    transfer(&mut conn, &mut server);
+    let mut progress = vec![];
+    while let Some(p) = hs.process(&mut conn, &mut kx).unwrap() {
+        progress.push(p);
+    }
+    assert_eq!(progress.len(), 5);
+    assert_eq!(progress[4], Progress::Connected);
+
+    transfer(&mut conn, &mut server);
+    assert!(!server.is_handshaking());
+
+    // The ChangeCipherSpec of middlebox compatibility mode isn't allowed after the handshake.
+    conn.read_tls(&[0x14, 3, 3, 0, 1, 1]).unwrap();
+    assert!(matches!(
+        hs.process(&mut conn, &mut kx),
+        Err(TlsError::UnexpectedMessage)
+    ));
+}
+
+#[test]
+fn expired_certificate() {
+    let mut server = server();
+    let mut conn = ClientConnection::<RingAead>::new(vec![], vec![], vec![]);
+    let mut kx = RingKeyExchange::default();
+    let ca = fs::read(KeyType::ECDSA.path("ca.der")).unwrap();
+    let anchors = [TrustAnchor::from_certificate(&ca).unwrap()];
+    let validator = PathValidator::new(&anchors, RingVerifier);
+    let auth = AuthConfig {
+        // 2024-01-01, after the server's certificate expires.
+        time: &FixedTime(1_704_067_200),
+        ..auth_config(&validator)
+    };
+    let mut hs = ClientHandshake::new(
+        CONFIG,
+        auth,
+        Random::from([7; 32]),
+        Transcript::new(vec![]),
+        new_hash,
+        new_aead,
+    );
+    hs.start(&mut conn, &mut kx).unwrap();
+
+    transfer(&mut conn, &mut server);
+    let result = loop {
+        match hs.process(&mut conn, &mut kx) {
+            Ok(Some(_)) => {}
+            result => break result,
+        }
+    };
+    assert!(matches!(result, Err(TlsError::CertificateExpired)));
+}
+
+fn auth_config<'c>(validator: &'c PathValidator<'c, RingVerifier>) -> AuthConfig<'c> {
+    AuthConfig {
+        server_name: ServerName::new("localhost").unwrap(),
+        server_verifier: validator,
+        time: &FixedTime(NOW),
+        signature_verifier: &RingVerifier,
+        client_cert: None,
+    }
+}
+
+fn server() -> ServerSession {
+    let kt = KeyType::ECDSA;
+    let config = server::config(ProtocolVersion::TLSv1_3, kt.cert_chain(), kt.priv_key());
+    server::session(&Arc::new(config))
+}
+
+/// Sends the client's queued records to the server, and the server's answer back.
+fn transfer(conn: &mut ClientConnection<'_, RingAead>, server: &mut ServerSession) {
+    let mut buf = [0; 4096];
+    while conn.wants_write() {
+        let len = conn.write_tls(&mut buf);
+        server.read_tls(&mut &buf[..len]).unwrap();
+    }
+    server.process_new_packets().unwrap();
+
+    let mut records = vec![];
+    while server.wants_write() {
+        server.write_tls(&mut records).unwrap();
+    }
+    assert_eq!(conn.read_tls(&records).unwrap(), records.len());
+}
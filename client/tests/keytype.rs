@@ -36,7 +36,7 @@ impl KeyType {
         self.path("ca.cert")
     }
 
-    fn path(&self, key_file: &str) -> PathBuf {
+    pub fn path(&self, key_file: &str) -> PathBuf {
         let test_keys_path = PathBuf::from("../test-keys");
         match self {
             KeyType::RSA => test_keys_path.join("rsa").join(key_file),
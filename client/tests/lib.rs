@@ -3,6 +3,8 @@ use rustls::{ProtocolVersion, Session, SupportedCipherSuite};
 use std::sync::Arc;
 
 mod client;
+mod client_auth;
+mod handshake;
 mod keytype;
 mod server;
 mod suite;
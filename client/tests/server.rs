@@ -1,6 +1,6 @@
 use rustls::{
-    Certificate, NoClientAuth, NoServerSessionStorage, PrivateKey, ProtocolVersion, ServerConfig,
-    ServerSession,
+    Certificate, ClientCertVerifier, NoClientAuth, NoServerSessionStorage, PrivateKey,
+    ProtocolVersion, ServerConfig, ServerSession,
 };
 use std::sync::Arc;
 
@@ -9,7 +9,16 @@ pub fn config(
     cert_chain: Vec<Certificate>,
     priv_key: PrivateKey,
 ) -> ServerConfig {
-    let mut cfg = ServerConfig::new(NoClientAuth::new());
+    config_with_verifier(version, cert_chain, priv_key, NoClientAuth::new())
+}
+
+pub fn config_with_verifier(
+    version: ProtocolVersion,
+    cert_chain: Vec<Certificate>,
+    priv_key: PrivateKey,
+    verifier: Arc<dyn ClientCertVerifier>,
+) -> ServerConfig {
+    let mut cfg = ServerConfig::new(verifier);
 
     cfg.set_single_cert(cert_chain, priv_key)
         .expect("bad certs/private key?");
@@ -1,3 +1,6 @@
+use mtls_message::handshake::enums::{
+    AeadAlgorithm, CipherSuite as MtlsCipherSuite, HashAlgorithm as MtlsHashAlgorithm,
+};
 use rustls::{
     internal::msgs::{
         enums::{HashAlgorithm, SignatureAlgorithm},
@@ -6,35 +9,45 @@ use rustls::{
     BulkAlgorithm, CipherSuite, SupportedCipherSuite,
 };
 
-pub static TLS13_CHACHA20_POLY1305_SHA256: SupportedCipherSuite = SupportedCipherSuite {
-    suite: CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
-    kx: KeyExchangeAlgorithm::BulkOnly,
-    sign: SignatureAlgorithm::Anonymous,
-    bulk: BulkAlgorithm::CHACHA20_POLY1305,
-    hash: HashAlgorithm::SHA256,
-    enc_key_len: 32,
-    fixed_iv_len: 12,
-    explicit_nonce_len: 0,
-};
+pub static TLS13_CHACHA20_POLY1305_SHA256: SupportedCipherSuite = supported_suite(
+    MtlsCipherSuite::TlsChaCha20Poly1305Sha256,
+    CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+);
 
-pub static TLS13_AES_256_GCM_SHA384: SupportedCipherSuite = SupportedCipherSuite {
-    suite: CipherSuite::TLS13_AES_256_GCM_SHA384,
-    kx: KeyExchangeAlgorithm::BulkOnly,
-    sign: SignatureAlgorithm::Anonymous,
-    bulk: BulkAlgorithm::AES_256_GCM,
-    hash: HashAlgorithm::SHA384,
-    enc_key_len: 32,
-    fixed_iv_len: 12,
-    explicit_nonce_len: 0,
-};
+pub static TLS13_AES_256_GCM_SHA384: SupportedCipherSuite = supported_suite(
+    MtlsCipherSuite::TlsAes256GcmSha384,
+    CipherSuite::TLS13_AES_256_GCM_SHA384,
+);
 
-pub static TLS13_AES_128_GCM_SHA256: SupportedCipherSuite = SupportedCipherSuite {
-    suite: CipherSuite::TLS13_AES_128_GCM_SHA256,
-    kx: KeyExchangeAlgorithm::BulkOnly,
-    sign: SignatureAlgorithm::Anonymous,
-    bulk: BulkAlgorithm::AES_128_GCM,
-    hash: HashAlgorithm::SHA256,
-    enc_key_len: 16,
-    fixed_iv_len: 12,
-    explicit_nonce_len: 0,
-};
+pub static TLS13_AES_128_GCM_SHA256: SupportedCipherSuite = supported_suite(
+    MtlsCipherSuite::TlsAes128GcmSha256,
+    CipherSuite::TLS13_AES_128_GCM_SHA256,
+);
+
+/// Builds the rustls description of `suite` from its metadata.
+const fn supported_suite(suite: MtlsCipherSuite, id: CipherSuite) -> SupportedCipherSuite {
+    let info = match suite.info() {
+        Some(info) => info,
+        None => panic!("unknown cipher suite"),
+    };
+
+    SupportedCipherSuite {
+        suite: id,
+        kx: KeyExchangeAlgorithm::BulkOnly,
+        sign: SignatureAlgorithm::Anonymous,
+        bulk: match info.aead {
+            AeadAlgorithm::Aes128Gcm => BulkAlgorithm::AES_128_GCM,
+            AeadAlgorithm::Aes256Gcm => BulkAlgorithm::AES_256_GCM,
+            AeadAlgorithm::ChaCha20Poly1305 => BulkAlgorithm::CHACHA20_POLY1305,
+        },
+        hash: match info.hash {
+            MtlsHashAlgorithm::Sha1 => HashAlgorithm::SHA1,
+            MtlsHashAlgorithm::Sha256 => HashAlgorithm::SHA256,
+            MtlsHashAlgorithm::Sha384 => HashAlgorithm::SHA384,
+            MtlsHashAlgorithm::Sha512 => HashAlgorithm::SHA512,
+        },
+        enc_key_len: info.key_len,
+        fixed_iv_len: info.iv_len,
+        explicit_nonce_len: 0,
+    }
+}
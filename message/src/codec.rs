@@ -6,7 +6,7 @@ pub(crate) use header::HeaderSize;
 use crate::error::Result as TlsResult;
 
 #[macro_use]
-pub(crate) mod array;
+pub mod array;
 
 pub(crate) mod num;
 
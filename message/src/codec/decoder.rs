@@ -13,6 +13,14 @@ impl<'a> Decoder<'a> {
             .map(move |prev| &self.bytes[prev..self.offset])
     }
 
+    /// Returns the next `len` bytes without consuming them.
+    pub fn peek(&self, len: usize) -> Option<&'a [u8]> {
+        if self.remaining() < len {
+            return None;
+        }
+        Some(&self.bytes[self.offset..self.offset + len])
+    }
+
     pub fn sub(&mut self, len: usize) -> Option<Self> {
         self.take(len).map(Self::new)
     }
@@ -64,6 +72,17 @@ mod tests {
         assert!(taken_bytes.is_none());
     }
 
+    #[test]
+    fn peek_bytes() {
+        let bytes = [1, 2, 3];
+        let mut dec = Decoder::new(&bytes);
+        dec.take(1).unwrap();
+
+        assert_eq!(dec.peek(2).unwrap(), [2, 3]);
+        assert_eq!(dec.offset, 1);
+        assert!(dec.peek(3).is_none());
+    }
+
     #[test]
     fn sub_decoder() {
         let bytes = [1, 2, 3];
@@ -65,7 +65,7 @@ impl<'a> Encoder<'a> {
     // TODO: Maybe rename to `as_bytes` to be more consistent with the decoder.
     pub fn bytes(&self) -> &[u8] {
         match self.bytes {
-            ManagedSlice::Borrowed(ref bytes) => bytes,
+            ManagedSlice::Borrowed(ref bytes) => &bytes[..self.len],
             ManagedSlice::Owned(ref bytes) => bytes.as_slice(),
         }
     }
@@ -0,0 +1,220 @@
+use crate::{
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::enums::{NamedGroup, SignatureScheme},
+    x509::{
+        certificate::{PublicKeyAlgorithm, SignatureAlgorithmIdentifier, SubjectPublicKeyInfo},
+        key::PrivateKey,
+    },
+};
+use core::convert::TryFrom;
+
+/// Largest digest produced by any supported hash algorithm (SHA-512).
+pub const MAX_DIGEST_LEN: usize = 64;
+
+/// Length of the per-record nonce used by every TLS 1.3 AEAD.
+pub const NONCE_LEN: usize = 12;
+
+/// Longest signature made by a [`SigningKey`] (an RSA signature with a 4096 bit key).
+pub const MAX_SIGNATURE_LEN: usize = 512;
+
+/// Running hash context supplied by the crypto provider.
+///
+/// Cloning a context forks its state, so the digest of a prefix can be taken while the original
+/// context keeps accumulating.
+pub trait Hash: Clone {
+    fn update(&mut self, data: &[u8]);
+
+    fn finish(self) -> Digest;
+
+    /// Resets the context to the state it was in before any data was added.
+    fn reset(&mut self);
+}
+
+/// AEAD keyed with a traffic key, supplied by the crypto provider.
+///
+/// Both operations work in place so the record layer doesn't need a second buffer.
+pub trait Aead {
+    fn tag_len(&self) -> usize;
+
+    /// Encrypts `in_out[..in_out.len() - self.tag_len()]` in place and writes the tag to the
+    /// remaining bytes.
+    fn seal_in_place(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        in_out: &mut [u8],
+    ) -> TlsResult<()>;
+
+    /// Decrypts and authenticates ciphertext followed by its tag in place, returning the length
+    /// of the plaintext.
+    fn open_in_place(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        in_out: &mut [u8],
+    ) -> TlsResult<usize>;
+}
+
+/// Ephemeral key exchange supplied by the crypto provider.
+pub trait KeyExchange {
+    /// Generates a key pair for `group`, keeping the private key, and writes the public key to
+    /// `out`, returning its length.
+    ///
+    /// A new key pair replaces the one kept from before.
+    fn generate(&mut self, group: NamedGroup, out: &mut [u8]) -> TlsResult<usize>;
+
+    /// Computes the shared secret of the kept private key and the peer's public key, writing it
+    /// to `out` and returning its length.
+    ///
+    /// The private key is only used once, so it can be discarded afterwards.
+    fn agree(&mut self, peer_public_key: &[u8], out: &mut [u8]) -> TlsResult<usize>;
+}
+
+/// Verification of certificate signatures, supplied by the crypto provider.
+pub trait SignatureVerifier {
+    /// Verifies `signature` over `message` was made by the key of `public_key` with `algorithm`.
+    ///
+    /// Fails with [`TlsError::BadCertificate`] if the signature is invalid, or
+    /// [`TlsError::UnsupportedCertificate`] if the key or algorithm isn't supported.
+    ///
+    /// [`TlsError::BadCertificate`]: crate::error::Error::BadCertificate
+    /// [`TlsError::UnsupportedCertificate`]: crate::error::Error::UnsupportedCertificate
+    fn verify(
+        &self,
+        public_key: &SubjectPublicKeyInfo<'_>,
+        algorithm: SignatureAlgorithmIdentifier,
+        message: &[u8],
+        signature: &[u8],
+    ) -> TlsResult<()>;
+}
+
+/// Signing of handshake messages with a private key, supplied by the crypto provider.
+pub trait Signer {
+    /// Signs `message` with `key` using `scheme`, writing the signature to `out` and returning
+    /// its length.
+    ///
+    /// `scheme` is always one that [`PrivateKey::supports`].
+    fn sign(
+        &self,
+        key: &PrivateKey<'_>,
+        scheme: SignatureScheme,
+        message: &[u8],
+        out: &mut [u8],
+    ) -> TlsResult<usize>;
+}
+
+/// Private key an endpoint signs its CertificateVerify with.
+///
+/// The key may be kept where it can't be read, such as in a secure element or TPM, as only
+/// signatures are asked of it. [`SoftwareSigningKey`] is the implementation for keys in memory.
+pub trait SigningKey {
+    /// Schemes the key can sign CertificateVerify messages with.
+    fn schemes(&self) -> &[SignatureScheme];
+
+    /// Signs `message`, the content a CertificateVerify covers, using `scheme`, writing the
+    /// signature to `out` and returning its length.
+    ///
+    /// `scheme` is always one of [`SigningKey::schemes`], and `out` is [`MAX_SIGNATURE_LEN`]
+    /// long.
+    fn sign(&self, scheme: SignatureScheme, message: &[u8], out: &mut [u8]) -> TlsResult<usize>;
+}
+
+/// [`SigningKey`] for a private key in memory, signed with by the crypto provider's [`Signer`].
+pub struct SoftwareSigningKey<'a, S: Signer> {
+    key: PrivateKey<'a>,
+    signer: S,
+}
+
+impl<'a, S: Signer> SoftwareSigningKey<'a, S> {
+    pub fn new(key: PrivateKey<'a>, signer: S) -> Self {
+        Self { key, signer }
+    }
+
+    pub fn key(&self) -> &PrivateKey<'a> {
+        &self.key
+    }
+}
+
+impl<'a, S: Signer> SigningKey for SoftwareSigningKey<'a, S> {
+    /// The TLS 1.3 handshake schemes of the key's algorithm. RSA keys sign with RSASSA-PSS,
+    /// strongest hash last.
+    fn schemes(&self) -> &[SignatureScheme] {
+        use self::SignatureScheme::*;
+
+        match self.key.algorithm() {
+            PublicKeyAlgorithm::Rsa => &[RsaPssSha256, RsaPssSha384, RsaPssSha512],
+            PublicKeyAlgorithm::Ec(NamedGroup::Secp256r1) => &[EcdsaNistp256Sha256],
+            PublicKeyAlgorithm::Ec(NamedGroup::Secp384r1) => &[EcdsaNistp384Sha384],
+            PublicKeyAlgorithm::Ec(NamedGroup::Secp521r1) => &[EcdsaNistp521Sha512],
+            PublicKeyAlgorithm::Ed25519 => &[Ed25519],
+            _ => &[],
+        }
+    }
+
+    fn sign(&self, scheme: SignatureScheme, message: &[u8], out: &mut [u8]) -> TlsResult<usize> {
+        self.signer.sign(&self.key, scheme, message, out)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Digest {
+    data: [u8; MAX_DIGEST_LEN],
+    len: usize,
+}
+
+impl Digest {
+    /// Digest of no bytes, such as the starting secret of the key schedule.
+    pub const fn empty() -> Self {
+        Self {
+            data: [0; MAX_DIGEST_LEN],
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl AsRef<[u8]> for Digest {
+    fn as_ref(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Digest {
+    type Error = TlsError;
+
+    /// Fails if `bytes` is longer than [`MAX_DIGEST_LEN`].
+    fn try_from(bytes: &'a [u8]) -> TlsResult<Self> {
+        let len = bytes.len();
+        if len > MAX_DIGEST_LEN {
+            return Err(TlsError::InternalError("digest exceeds MAX_DIGEST_LEN"));
+        }
+
+        let mut data = [0; MAX_DIGEST_LEN];
+        data[..len].copy_from_slice(bytes);
+        Ok(Self { data, len })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digest_from_slice() {
+        let digest = Digest::try_from(&[7; MAX_DIGEST_LEN][..]).unwrap();
+        assert_eq!(digest.as_ref(), &[7; MAX_DIGEST_LEN][..]);
+        assert!(Digest::empty().is_empty());
+
+        assert!(matches!(
+            Digest::try_from(&[7; MAX_DIGEST_LEN + 1][..]),
+            Err(TlsError::InternalError(_))
+        ));
+    }
+}
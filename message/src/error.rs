@@ -27,6 +27,10 @@ pub enum Error {
     UnknownPskIdentity,
     CertificateRequired,
     NoApplicationProtocol,
+    /// The certificate is valid, but not for the name the peer was expected to have.
+    CertificateNameMismatch,
+    /// No certificate matched a pin of the named pin set.
+    CertificatePinMismatch(&'static str),
 
     #[doc(hidden)]
     __Nonexhaustive,
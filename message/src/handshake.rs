@@ -1,6 +1,15 @@
+pub mod certificate;
+pub mod certificate_request;
+pub mod certificate_verify;
 pub mod client;
+pub mod deframer;
+#[macro_use]
 pub mod enums;
 pub mod extension;
+pub mod grease;
+pub mod key_schedule;
+pub mod message;
 pub mod random;
 pub mod server;
 pub mod session;
+pub mod transcript;
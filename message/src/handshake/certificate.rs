@@ -0,0 +1,270 @@
+use crate::{
+    codec::{
+        array::{iter::ArrayIter, Array},
+        Codec, CodecSized, Decoder, Encoder, HeaderSize,
+    },
+    error::Result as TlsResult,
+    handshake::extension::{certificate::CertificateExtension, Extensions},
+};
+
+/// Certificate message, carrying a peer's certification path (RFC 8446, section 4.4.2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificatePayload<'a> {
+    context: &'a [u8],
+    entries: Array<'a, CertificateEntry<'a>>,
+}
+
+impl<'a> CertificatePayload<'a> {
+    /// Creates the payload from its entries, whose first is the end-entity certificate.
+    /// `context` is empty except in reply to a CertificateRequest.
+    pub fn new(context: &'a [u8], entries: &'a [CertificateEntry<'a>]) -> Self {
+        Self {
+            context,
+            entries: entries.into(),
+        }
+    }
+
+    pub fn context(&self) -> &'a [u8] {
+        self.context
+    }
+
+    /// Entries of the certificates, from the end-entity towards the trust anchor.
+    pub fn entries(&self) -> ArrayIter<'a, CertificateEntry<'a>> {
+        self.entries.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'a> Codec<'a> for CertificatePayload<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        (self.context.len() as u8).encode(enc)?;
+        enc.append(self.context)?;
+        self.entries.encode(enc)
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        let len = u8::decode(dec)?;
+        let context = dec.take(usize::from(len))?;
+        let len = CertificateEntry::decode_len(dec)?;
+        let entries = dec
+            .take(len)
+            .filter(|b| decodes_all::<CertificateEntry<'_>>(b))?;
+
+        Some(Self {
+            context,
+            entries: Array::Bytes(entries),
+        })
+    }
+}
+
+impl<'a> CodecSized<'a> for CertificatePayload<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::U24;
+
+    fn data_size(&self) -> usize {
+        (self.context.len() as u8).data_size()
+            + self.context.len()
+            + CertificateEntry::HEADER_SIZE.size()
+            + self.entries.data_size()
+    }
+}
+
+/// Certificate in a [`CertificatePayload`] and its extensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateEntry<'a> {
+    /// DER encoded X.509 certificate.
+    pub data: &'a [u8],
+    pub extensions: Extensions<'a, CertificateExtension<'a>>,
+}
+
+impl<'a> CertificateEntry<'a> {
+    /// Stapled OCSP response for the certificate, if any.
+    pub fn ocsp_response(&self) -> Option<&'a [u8]> {
+        self.extensions.iter().find_map(|ext| match *ext.as_ref() {
+            CertificateExtension::StatusRequest(response) => Some(response),
+            _ => None,
+        })
+    }
+
+    /// Signed certificate timestamps for the certificate, if any.
+    pub fn signed_certificate_timestamps(&self) -> Option<&'a [u8]> {
+        self.extensions.iter().find_map(|ext| match *ext.as_ref() {
+            CertificateExtension::SignedCertificateTimestamp(list) => Some(list),
+            _ => None,
+        })
+    }
+}
+
+impl<'a> Codec<'a> for CertificateEntry<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        HeaderSize::U24.encode_len(self.data.len(), enc)?;
+        enc.append(self.data)?;
+        self.extensions.encode_extensions(enc)
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        let len = HeaderSize::U24.decode_len(dec)?;
+        let data = dec.take(len).filter(|data| !data.is_empty())?;
+
+        // Unlike in the hellos, the extensions are always present.
+        let len = CertificateExtension::decode_len(dec)?;
+        let extensions = dec
+            .take(len)
+            .filter(|b| decodes_all::<CertificateExtension<'_>>(b))?;
+
+        Some(Self {
+            data,
+            extensions: Array::Bytes(extensions).into(),
+        })
+    }
+}
+
+impl<'a> CodecSized<'a> for CertificateEntry<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::U24;
+
+    fn data_size(&self) -> usize {
+        HeaderSize::U24.size() + self.data.len() + self.extensions.extensions_size()
+    }
+}
+
+/// Whether `bytes` is a whole number of encoded `T`, so that none are skipped when iterating.
+pub(crate) fn decodes_all<'a, T: Codec<'a>>(bytes: &'a [u8]) -> bool {
+    let mut dec = Decoder::new(bytes);
+    while !dec.is_complete() {
+        if T::decode(&mut dec).is_none() {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::{
+        internal::msgs::{
+            base::{PayloadU16, PayloadU24, PayloadU8},
+            codec::Codec as r_Codec,
+            handshake::{
+                CertificateEntry as r_CertificateEntry,
+                CertificateExtension as r_CertificateExtension,
+                CertificatePayloadTLS13 as r_CertificatePayload,
+                CertificateStatus as r_CertificateStatus,
+            },
+        },
+        Certificate as r_Certificate,
+    };
+    use std::vec::Vec;
+
+    #[test]
+    fn encode() {
+        let extensions = [
+            CertificateExtension::StatusRequest(&[4, 5]),
+            CertificateExtension::SignedCertificateTimestamp(&[0, 3, 0, 1, 6]),
+        ];
+        let entries = [
+            CertificateEntry {
+                data: &[1, 2, 3],
+                extensions: Extensions::from(&extensions[..]),
+            },
+            CertificateEntry {
+                data: &[7],
+                extensions: Extensions::empty(),
+            },
+        ];
+
+        assert_eq!(
+            embed_bytes(CertificatePayload::new(&[9], &entries)),
+            rustls_bytes(r_CertificatePayload {
+                context: PayloadU8(vec![9]),
+                list: vec![
+                    r_CertificateEntry {
+                        cert: r_Certificate(vec![1, 2, 3]),
+                        exts: vec![
+                            r_CertificateExtension::CertificateStatus(r_CertificateStatus {
+                                ocsp_response: PayloadU24(vec![4, 5]),
+                            }),
+                            r_CertificateExtension::SignedCertificateTimestamp(vec![PayloadU16(
+                                vec![6]
+                            )]),
+                        ],
+                    },
+                    r_CertificateEntry {
+                        cert: r_Certificate(vec![7]),
+                        exts: vec![],
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn encode_empty() {
+        assert_eq!(embed_bytes(CertificatePayload::new(&[], &[])), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn decode() {
+        let bytes = rustls_bytes(r_CertificatePayload {
+            context: PayloadU8(vec![]),
+            list: vec![
+                r_CertificateEntry {
+                    cert: r_Certificate(vec![1, 2, 3]),
+                    exts: vec![r_CertificateExtension::CertificateStatus(
+                        r_CertificateStatus {
+                            ocsp_response: PayloadU24(vec![4, 5]),
+                        },
+                    )],
+                },
+                r_CertificateEntry {
+                    cert: r_Certificate(vec![7]),
+                    exts: vec![],
+                },
+            ],
+        });
+        let mut dec = Decoder::new(&bytes);
+        let payload = CertificatePayload::decode(&mut dec).unwrap();
+        let entries = payload.entries().collect::<Vec<_>>();
+
+        assert!(dec.is_complete());
+        assert_eq!(payload.context(), &[]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].as_ref().data, &[1, 2, 3]);
+        assert_eq!(entries[0].as_ref().ocsp_response(), Some(&[4, 5][..]));
+        assert_eq!(entries[0].as_ref().signed_certificate_timestamps(), None);
+        assert_eq!(entries[1].as_ref().data, &[7]);
+        assert_eq!(entries[1].as_ref().ocsp_response(), None);
+    }
+
+    #[test]
+    fn decode_malformed_entry() {
+        // The second entry is missing its extensions.
+        let bytes = [0, 0, 0, 9, 0, 0, 1, 1, 0, 0, 0, 0, 1, 2];
+
+        assert!(CertificatePayload::decode(&mut Decoder::new(&bytes)).is_none());
+        assert!(CertificatePayload::decode(&mut Decoder::new(&bytes[..9])).is_none());
+    }
+
+    #[test]
+    fn decode_empty_certificate() {
+        let bytes = [0, 0, 0, 5, 0, 0, 0, 0, 0];
+
+        assert!(CertificatePayload::decode(&mut Decoder::new(&bytes)).is_none());
+    }
+
+    fn rustls_bytes<T: r_Codec>(payload: T) -> Vec<u8> {
+        let mut enc = vec![];
+        payload.encode(&mut enc);
+        enc
+    }
+
+    fn embed_bytes<'a, T: CodecSized<'a>>(payload: T) -> Vec<u8> {
+        let mut enc = Encoder::new(vec![]);
+        payload.encode(&mut enc).unwrap();
+        assert_eq!(enc.bytes().len(), payload.data_size());
+
+        enc.bytes().into()
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    codec::{array::Array, Codec, CodecSized, Decoder, Encoder, HeaderSize},
+    error::Result as TlsResult,
+    handshake::{
+        certificate::decodes_all,
+        extension::{
+            certificate_request::CertificateRequestExtension, Extensions, SignatureSchemes,
+        },
+    },
+};
+
+/// CertificateRequest message, asking the client to authenticate with a certificate (RFC 8446,
+/// section 4.3.2).
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateRequestPayload<'a> {
+    context: &'a [u8],
+    extensions: Extensions<'a, CertificateRequestExtension<'a>>,
+}
+
+impl<'a> CertificateRequestPayload<'a> {
+    /// Creates the payload, where `context` is echoed in the client's Certificate message.
+    pub fn new(
+        context: &'a [u8],
+        extensions: Extensions<'a, CertificateRequestExtension<'a>>,
+    ) -> Self {
+        Self {
+            context,
+            extensions,
+        }
+    }
+
+    pub fn context(&self) -> &'a [u8] {
+        self.context
+    }
+
+    pub fn extensions(&self) -> &Extensions<'a, CertificateRequestExtension<'a>> {
+        &self.extensions
+    }
+
+    /// Schemes the server accepts the CertificateVerify signed with, in order of preference.
+    /// The extension must always be present.
+    pub fn signature_schemes(&self) -> Option<SignatureSchemes<'a>> {
+        self.extensions.iter().find_map(|ext| match *ext.as_ref() {
+            CertificateRequestExtension::SignatureAlgorithms(ref schemes) => Some(schemes.clone()),
+            _ => None,
+        })
+    }
+
+    /// Encoded DistinguishedNames of the certificate authorities the server accepts, if it
+    /// named any.
+    pub fn certificate_authorities(&self) -> Option<&'a [u8]> {
+        self.extensions.iter().find_map(|ext| match *ext.as_ref() {
+            CertificateRequestExtension::CertificateAuthorities(names) => Some(names),
+            _ => None,
+        })
+    }
+}
+
+impl<'a> Codec<'a> for CertificateRequestPayload<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        (self.context.len() as u8).encode(enc)?;
+        enc.append(self.context)?;
+        self.extensions.encode_extensions(enc)
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        let len = u8::decode(dec)?;
+        let context = dec.take(usize::from(len))?;
+        let len = CertificateRequestExtension::decode_len(dec)?;
+        let extensions = dec
+            .take(len)
+            .filter(|b| decodes_all::<CertificateRequestExtension<'_>>(b))?;
+
+        Some(Self {
+            context,
+            extensions: Array::Bytes(extensions).into(),
+        })
+    }
+}
+
+impl<'a> CodecSized<'a> for CertificateRequestPayload<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::U24;
+
+    fn data_size(&self) -> usize {
+        (self.context.len() as u8).data_size()
+            + self.context.len()
+            + self.extensions.extensions_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handshake::enums::SignatureScheme;
+    use rustls::internal::msgs::{
+        base::PayloadU8,
+        codec::Codec as r_Codec,
+        enums::SignatureScheme as r_SignatureScheme,
+        handshake::{
+            CertReqExtension as r_CertReqExtension,
+            CertificateRequestPayloadTLS13 as r_CertificateRequestPayload,
+            DistinguishedName as r_DistinguishedName,
+        },
+    };
+    use std::vec::Vec;
+
+    #[test]
+    fn encode() {
+        let schemes = [
+            SignatureScheme::EcdsaNistp256Sha256,
+            SignatureScheme::RsaPssSha256,
+        ];
+        let extensions = [
+            CertificateRequestExtension::SignatureAlgorithms(Array::from(&schemes[..]).into()),
+            CertificateRequestExtension::CertificateAuthorities(&[0, 3, 0, 1, 9]),
+        ];
+
+        assert_eq!(
+            embed_bytes(CertificateRequestPayload::new(
+                &[7, 8],
+                Extensions::from(&extensions[..])
+            )),
+            rustls_bytes(r_CertificateRequestPayload {
+                context: PayloadU8(vec![7, 8]),
+                extensions: vec![
+                    r_CertReqExtension::SignatureAlgorithms(vec![
+                        r_SignatureScheme::ECDSA_NISTP256_SHA256,
+                        r_SignatureScheme::RSA_PSS_SHA256,
+                    ]),
+                    r_CertReqExtension::AuthorityNames(vec![r_DistinguishedName::new(vec![9])]),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let bytes = rustls_bytes(r_CertificateRequestPayload {
+            context: PayloadU8(vec![]),
+            extensions: vec![r_CertReqExtension::SignatureAlgorithms(vec![
+                r_SignatureScheme::ED25519,
+            ])],
+        });
+        let mut dec = Decoder::new(&bytes);
+        let payload = CertificateRequestPayload::decode(&mut dec).unwrap();
+
+        assert!(dec.is_complete());
+        assert_eq!(payload.context(), &[]);
+        assert_eq!(
+            payload
+                .signature_schemes()
+                .unwrap()
+                .iter()
+                .map(|scheme| *scheme.as_ref())
+                .collect::<Vec<_>>(),
+            [SignatureScheme::Ed25519]
+        );
+        assert_eq!(payload.certificate_authorities(), None);
+    }
+
+    #[test]
+    fn decode_without_signature_algorithms() {
+        let bytes = [0, 0, 0];
+        let payload = CertificateRequestPayload::decode(&mut Decoder::new(&bytes)).unwrap();
+
+        assert!(payload.signature_schemes().is_none());
+    }
+
+    #[test]
+    fn decode_malformed_extension() {
+        // The extension is shorter than its length.
+        let bytes = [0, 0, 5, 0, 0x0d, 0, 4, 0];
+
+        assert!(CertificateRequestPayload::decode(&mut Decoder::new(&bytes)).is_none());
+    }
+
+    fn rustls_bytes<T: r_Codec>(payload: T) -> Vec<u8> {
+        let mut enc = vec![];
+        payload.encode(&mut enc);
+        enc
+    }
+
+    fn embed_bytes<'a, T: CodecSized<'a>>(payload: T) -> Vec<u8> {
+        let mut enc = Encoder::new(vec![]);
+        payload.encode(&mut enc).unwrap();
+        assert_eq!(enc.bytes().len(), payload.data_size());
+
+        enc.bytes().into()
+    }
+}
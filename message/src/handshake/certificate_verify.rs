@@ -0,0 +1,258 @@
+use crate::{
+    codec::{Codec, CodecSized, Decoder, Encoder, HeaderSize},
+    crypto::{Digest, SigningKey, MAX_DIGEST_LEN, MAX_SIGNATURE_LEN},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::enums::SignatureScheme,
+};
+
+const CLIENT_CONTEXT: &[u8] = b"TLS 1.3, client CertificateVerify";
+const SERVER_CONTEXT: &[u8] = b"TLS 1.3, server CertificateVerify";
+
+/// Octets of padding the signed content starts with.
+const PADDING_LEN: usize = 64;
+
+/// Longest content a CertificateVerify signs.
+pub const MAX_SIGNED_CONTENT_LEN: usize = PADDING_LEN + SERVER_CONTEXT.len() + 1 + MAX_DIGEST_LEN;
+
+/// Endpoint that sends a CertificateVerify.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sender {
+    Client,
+    Server,
+}
+
+/// CertificateVerify message, proving the sender holds the private key of its certificate (RFC
+/// 8446, section 4.4.3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CertificateVerifyPayload<'a> {
+    pub scheme: SignatureScheme,
+    pub signature: &'a [u8],
+}
+
+impl<'a> CertificateVerifyPayload<'a> {
+    /// Writes the content `sender` signs to `out`, given the transcript hash of the messages
+    /// before the CertificateVerify, and returns it.
+    pub fn signed_content<'b>(
+        sender: Sender,
+        transcript_hash: &Digest,
+        out: &'b mut [u8; MAX_SIGNED_CONTENT_LEN],
+    ) -> &'b [u8] {
+        let context = match sender {
+            Sender::Client => CLIENT_CONTEXT,
+            Sender::Server => SERVER_CONTEXT,
+        };
+        let hash = transcript_hash.as_ref();
+
+        let (padding, rest) = out.split_at_mut(PADDING_LEN);
+        padding.iter_mut().for_each(|b| *b = 0x20);
+        rest[..context.len()].copy_from_slice(context);
+        rest[context.len()] = 0;
+        rest[context.len() + 1..][..hash.len()].copy_from_slice(hash);
+
+        &out[..PADDING_LEN + context.len() + 1 + hash.len()]
+    }
+
+    /// Signs the content `sender` signs with `key` using `scheme`, given the transcript hash of
+    /// the messages before the CertificateVerify. The signature is written to `out`.
+    pub fn sign(
+        sender: Sender,
+        transcript_hash: &Digest,
+        key: &dyn SigningKey,
+        scheme: SignatureScheme,
+        out: &'a mut [u8; MAX_SIGNATURE_LEN],
+    ) -> TlsResult<Self> {
+        if !scheme.is_tls13_handshake() || !key.schemes().contains(&scheme) {
+            return Err(TlsError::InternalError("key doesn't sign with scheme"));
+        }
+
+        let mut buf = [0; MAX_SIGNED_CONTENT_LEN];
+        let content = Self::signed_content(sender, transcript_hash, &mut buf);
+        let len = key.sign(scheme, content, out)?;
+
+        let out: &'a [u8] = out;
+        let signature = out
+            .get(..len)
+            .ok_or(TlsError::InternalError("signature exceeds buffer"))?;
+        Ok(Self { scheme, signature })
+    }
+}
+
+impl<'a> Codec<'a> for CertificateVerifyPayload<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        self.scheme.encode(enc)?;
+        (self.signature.len() as u16).encode(enc)?;
+        enc.append(self.signature)
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        let scheme = SignatureScheme::decode(dec)?;
+        let len = u16::decode(dec)?;
+        let signature = dec.take(usize::from(len))?;
+        Some(Self { scheme, signature })
+    }
+}
+
+impl<'a> CodecSized<'a> for CertificateVerifyPayload<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::U24;
+
+    fn data_size(&self) -> usize {
+        self.scheme.data_size() + (self.signature.len() as u16).data_size() + self.signature.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::{SignatureVerifier, SoftwareSigningKey},
+        handshake::enums::SignatureAlgorithm,
+        testing::{RingSigner, RingVerifier},
+        x509::{
+            certificate::{Certificate, SignatureAlgorithmIdentifier},
+            key::PrivateKey,
+            pem::PemSections,
+        },
+    };
+    use core::convert::TryFrom;
+    use rustls::internal::msgs::{
+        codec::Codec as r_Codec, enums::SignatureScheme as r_SignatureScheme,
+        handshake::DigitallySignedStruct as r_DigitallySignedStruct,
+    };
+
+    #[test]
+    fn encode() {
+        let payload = CertificateVerifyPayload {
+            scheme: SignatureScheme::RsaPssSha256,
+            signature: &[1, 2, 3],
+        };
+        let mut enc = Encoder::new(vec![]);
+        payload.encode(&mut enc).unwrap();
+
+        let mut expected = vec![];
+        r_DigitallySignedStruct::new(r_SignatureScheme::RSA_PSS_SHA256, vec![1, 2, 3])
+            .encode(&mut expected);
+        assert_eq!(enc.bytes(), &expected[..]);
+        assert_eq!(payload.data_size(), expected.len());
+    }
+
+    #[test]
+    fn decode() {
+        let bytes = [0x04, 0x03, 0, 2, 9, 9];
+        let mut dec = Decoder::new(&bytes);
+
+        assert_eq!(
+            CertificateVerifyPayload::decode(&mut dec).unwrap(),
+            CertificateVerifyPayload {
+                scheme: SignatureScheme::EcdsaNistp256Sha256,
+                signature: &[9, 9],
+            }
+        );
+        assert!(dec.is_complete());
+    }
+
+    #[test]
+    fn signed_content() {
+        let hash = Digest::try_from(&[0xab; 32][..]).unwrap();
+        let mut buf = [0; MAX_SIGNED_CONTENT_LEN];
+        let content = CertificateVerifyPayload::signed_content(Sender::Server, &hash, &mut buf);
+
+        assert_eq!(content.len(), 64 + 33 + 1 + 32);
+        assert_eq!(content[..64], [0x20; 64][..]);
+        assert_eq!(&content[64..97], SERVER_CONTEXT);
+        assert_eq!(content[97], 0);
+        assert_eq!(content[98..], [0xab; 32][..]);
+    }
+
+    fn pem(file: &'static [u8], out: &mut [u8]) -> usize {
+        let section = PemSections::new(file).next().unwrap().unwrap();
+        section.decode(out).unwrap().len()
+    }
+
+    #[test]
+    fn sign() {
+        let mut key_der = [0; 1024];
+        let len = pem(
+            include_bytes!("../../../test-keys/ecdsa/end.key"),
+            &mut key_der,
+        );
+        let key =
+            SoftwareSigningKey::new(PrivateKey::from_pkcs8(&key_der[..len]).unwrap(), RingSigner);
+        assert_eq!(key.schemes(), [SignatureScheme::EcdsaNistp256Sha256]);
+
+        let hash = Digest::try_from(&[0xab; 32][..]).unwrap();
+        let mut out = [0; MAX_SIGNATURE_LEN];
+        let payload = CertificateVerifyPayload::sign(
+            Sender::Server,
+            &hash,
+            &key,
+            SignatureScheme::EcdsaNistp256Sha256,
+            &mut out,
+        )
+        .unwrap();
+        assert_eq!(payload.scheme, SignatureScheme::EcdsaNistp256Sha256);
+
+        let mut cert_der = [0; 2048];
+        let len = pem(
+            include_bytes!("../../../test-keys/ecdsa/end.cert"),
+            &mut cert_der,
+        );
+        let cert = Certificate::parse(&cert_der[..len]).unwrap();
+        let mut buf = [0; MAX_SIGNED_CONTENT_LEN];
+        let content = CertificateVerifyPayload::signed_content(Sender::Server, &hash, &mut buf);
+        let algorithm = SignatureAlgorithmIdentifier {
+            algorithm: SignatureAlgorithm::Ecdsa,
+            hash: Some(crate::handshake::enums::HashAlgorithm::Sha256),
+        };
+        assert!(RingVerifier
+            .verify(cert.public_key(), algorithm, content, payload.signature)
+            .is_ok());
+    }
+
+    #[test]
+    fn sign_with_unsupported_scheme() {
+        let mut key_der = [0; 1024];
+        let len = pem(
+            include_bytes!("../../../test-keys/ecdsa/end.key"),
+            &mut key_der,
+        );
+        let key =
+            SoftwareSigningKey::new(PrivateKey::from_pkcs8(&key_der[..len]).unwrap(), RingSigner);
+        let hash = Digest::try_from(&[0xab; 32][..]).unwrap();
+
+        for scheme in &[
+            SignatureScheme::RsaPssSha256,
+            SignatureScheme::EcdsaSha1Legacy,
+        ] {
+            assert!(matches!(
+                CertificateVerifyPayload::sign(
+                    Sender::Client,
+                    &hash,
+                    &key,
+                    *scheme,
+                    &mut [0; MAX_SIGNATURE_LEN]
+                ),
+                Err(TlsError::InternalError(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn software_key_schemes() {
+        let mut key_der = [0; 4096];
+        let len = pem(
+            include_bytes!("../../../test-keys/rsa/end.key"),
+            &mut key_der,
+        );
+        let key =
+            SoftwareSigningKey::new(PrivateKey::from_pkcs8(&key_der[..len]).unwrap(), RingSigner);
+
+        assert_eq!(
+            key.schemes(),
+            [
+                SignatureScheme::RsaPssSha256,
+                SignatureScheme::RsaPssSha384,
+                SignatureScheme::RsaPssSha512
+            ]
+        );
+    }
+}
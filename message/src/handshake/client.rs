@@ -4,10 +4,12 @@ use crate::{
     handshake::{
         enums::{CipherSuite, CompressionMethod, ProtocolVersion},
         extension::{client::ClientExtension, Extensions},
+        grease::Grease,
         random::Random,
         session::SessionId,
     },
 };
+use core::ops::Range;
 
 #[derive(Debug, Default, PartialEq)]
 pub struct ClientHelloPayload<'a> {
@@ -17,6 +19,142 @@ pub struct ClientHelloPayload<'a> {
     cipher_suites: Array<'a, CipherSuite>,
     compression_methods: Array<'a, CompressionMethod>,
     extensions: Extensions<'a, ClientExtension<'a>>,
+    grease: Option<Grease>,
+    padding: bool,
+}
+
+/// Size of the type and length in front of a handshake message.
+const HANDSHAKE_HEADER_SIZE: usize = 4;
+
+/// ClientHellos with handshake messages in `PADDING_RANGE` are padded up to its end, as some
+/// middleboxes hang on them.
+const PADDING_RANGE: Range<usize> = 256..512;
+
+impl<'a> ClientHelloPayload<'a> {
+    pub fn builder() -> ClientHelloBuilder<'a> {
+        ClientHelloBuilder::new()
+    }
+
+    pub fn client_version(&self) -> ProtocolVersion {
+        self.client_version
+    }
+
+    pub fn random(&self) -> &Random {
+        &self.random
+    }
+
+    pub fn session_id(&self) -> &SessionId {
+        &self.session_id
+    }
+
+    pub fn cipher_suites(&self) -> &Array<'a, CipherSuite> {
+        &self.cipher_suites
+    }
+
+    pub fn compression_methods(&self) -> &Array<'a, CompressionMethod> {
+        &self.compression_methods
+    }
+
+    pub fn extensions(&self) -> &Extensions<'a, ClientExtension<'a>> {
+        &self.extensions
+    }
+
+    /// GREASE values added when encoding. These aren't part of the getters above, and decoded
+    /// hellos never have them: received GREASE values are left in place as `Unknown` values.
+    pub fn grease(&self) -> Option<&Grease> {
+        self.grease.as_ref()
+    }
+
+    /// Length of the padding extension added when encoding, if any.
+    pub fn padding_len(&self) -> Option<usize> {
+        if !self.padding {
+            return None;
+        }
+
+        let unpadded = HANDSHAKE_HEADER_SIZE + self.size_with(None);
+        if !PADDING_RANGE.contains(&unpadded) {
+            return None;
+        }
+
+        // Always send at least one byte, as some servers fail on an empty padding extension.
+        let padded = HANDSHAKE_HEADER_SIZE + self.size_with(Some(0));
+        Some(PADDING_RANGE.end.saturating_sub(padded).max(1))
+    }
+
+    fn size_with(&self, padding: Option<usize>) -> usize {
+        let grease_size = self
+            .grease
+            .map_or(0, |grease| grease.cipher_suite.data_size());
+        let extensions_size = if self.grease.is_none() && padding.is_none() {
+            self.extensions.data_size()
+        } else {
+            self.extensions.size_with(self.grease.as_ref(), padding)
+        };
+
+        self.client_version.data_size()
+            + self.random.data_size()
+            + self.session_id.data_size()
+            + CipherSuite::HEADER_SIZE.size()
+            + grease_size
+            + self.cipher_suites.data_size()
+            + CompressionMethod::HEADER_SIZE.size()
+            + self.compression_methods.data_size()
+            + extensions_size
+    }
+}
+
+/// Builds a ClientHello. Compression methods are always just null, as TLS 1.3 requires.
+#[derive(Debug, Default)]
+pub struct ClientHelloBuilder<'a> {
+    payload: ClientHelloPayload<'a>,
+}
+
+impl<'a> ClientHelloBuilder<'a> {
+    pub fn new() -> Self {
+        ClientHelloBuilder {
+            payload: ClientHelloPayload {
+                compression_methods: Array::Typed(&[CompressionMethod::Null]),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn random(mut self, random: Random) -> Self {
+        self.payload.random = random;
+        self
+    }
+
+    pub fn session_id(mut self, session_id: SessionId) -> Self {
+        self.payload.session_id = session_id;
+        self
+    }
+
+    pub fn cipher_suites(mut self, cipher_suites: Array<'a, CipherSuite>) -> Self {
+        self.payload.cipher_suites = cipher_suites;
+        self
+    }
+
+    pub fn extensions(mut self, extensions: Extensions<'a, ClientExtension<'a>>) -> Self {
+        self.payload.extensions = extensions;
+        self
+    }
+
+    /// Adds GREASE values (RFC 8701) to the hello when it's encoded.
+    pub fn grease(mut self, grease: Grease) -> Self {
+        self.payload.grease = Some(grease);
+        self
+    }
+
+    /// Adds a padding extension (RFC 7685) when encoding if the ClientHello would otherwise be
+    /// between 256 and 511 bytes long, bringing it to 512.
+    pub fn auto_padding(mut self) -> Self {
+        self.payload.padding = true;
+        self
+    }
+
+    pub fn build(self) -> ClientHelloPayload<'a> {
+        self.payload
+    }
 }
 
 impl<'a> Codec<'a> for ClientHelloPayload<'a> {
@@ -24,9 +162,25 @@ impl<'a> Codec<'a> for ClientHelloPayload<'a> {
         self.client_version.encode(enc)?;
         self.random.encode(enc)?;
         self.session_id.encode(enc)?;
-        self.cipher_suites.encode(enc)?;
+        match self.grease {
+            Some(ref grease) => {
+                CipherSuite::HEADER_SIZE.encode_len(
+                    grease.cipher_suite.data_size() + self.cipher_suites.data_size(),
+                    enc,
+                )?;
+                grease.cipher_suite.encode(enc)?;
+                self.cipher_suites.encode_items(enc)?;
+            }
+            None => self.cipher_suites.encode(enc)?,
+        }
         self.compression_methods.encode(enc)?;
-        self.extensions.encode(enc)
+
+        let padding = self.padding_len();
+        if self.grease.is_none() && padding.is_none() {
+            return self.extensions.encode(enc);
+        }
+        self.extensions
+            .encode_with(self.grease.as_ref(), padding, enc)
     }
 
     fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
@@ -37,6 +191,8 @@ impl<'a> Codec<'a> for ClientHelloPayload<'a> {
             cipher_suites: Array::decode(dec)?,
             compression_methods: Array::decode(dec)?,
             extensions: Extensions::decode(dec)?,
+            grease: None,
+            padding: false,
         })
     }
 }
@@ -45,14 +201,7 @@ impl<'a> CodecSized<'a> for ClientHelloPayload<'a> {
     const HEADER_SIZE: HeaderSize = HeaderSize::U24;
 
     fn data_size(&self) -> usize {
-        self.client_version.data_size()
-            + self.random.data_size()
-            + self.session_id.data_size()
-            + CipherSuite::HEADER_SIZE.size()
-            + self.cipher_suites.data_size()
-            + CompressionMethod::HEADER_SIZE.size()
-            + self.compression_methods.data_size()
-            + self.extensions.data_size()
+        self.size_with(self.padding_len())
     }
 }
 
@@ -60,16 +209,21 @@ impl<'a> CodecSized<'a> for ClientHelloPayload<'a> {
 mod tests {
     use super::*;
     use crate::handshake::{
-        enums::SignatureScheme,
+        enums::{ExtensionType, NamedGroup, SignatureScheme},
         extension::{ProtocolVersions, SignatureSchemes},
     };
     use rustls::{
         internal::msgs::{
+            base::Payload as r_Payload,
             codec::Codec as r_Codec,
-            enums::Compression as r_Compression,
+            enums::{
+                Compression as r_Compression, ExtensionType as r_ExtensionType,
+                NamedGroup as r_NamedGroup,
+            },
             handshake::{
                 ClientExtension as r_ClientExtension, ClientHelloPayload as r_ClientHelloPayload,
                 Random as r_Random, SessionID as r_SessionId,
+                UnknownExtension as r_UnknownExtension,
             },
         },
         CipherSuite as r_CipherSuite, ProtocolVersion as r_ProtocolVersion,
@@ -376,6 +530,198 @@ mod tests {
                 }),
             )
         }
+
+        #[test]
+        fn builder() {
+            assert_eq!(
+                embed_bytes(
+                    ClientHelloPayload::builder()
+                        .random([9; 32].into())
+                        .session_id([97, 98, 99].into())
+                        .cipher_suites(arr![CipherSuite::TlsAes128GcmSha256])
+                        .extensions(Extensions::from(arr![ClientExtension::from(arr![
+                            ProtocolVersion::TLSv1_3,
+                        ])]))
+                        .build()
+                ),
+                rustls_bytes(r_ClientHelloPayload {
+                    client_version: r_ProtocolVersion::TLSv1_2,
+                    random: r_Random::from_slice(&[9; 32]),
+                    session_id: r_SessionId::new(&[97, 98, 99]),
+                    cipher_suites: vec![r_CipherSuite::TLS13_AES_128_GCM_SHA256],
+                    compression_methods: vec![r_Compression::Null],
+                    extensions: vec![r_ClientExtension::SupportedVersions(vec![
+                        r_ProtocolVersion::TLSv1_3,
+                    ])],
+                }),
+            )
+        }
+
+        #[test]
+        fn hello_grease() {
+            assert_eq!(
+                embed_bytes(
+                    ClientHelloPayload::builder()
+                        .cipher_suites(arr![CipherSuite::TlsAes128GcmSha256])
+                        .extensions(Extensions::from(arr![
+                            ClientExtension::from(arr![ProtocolVersion::TLSv1_3]),
+                            ClientExtension::from(arr![NamedGroup::X25519]),
+                            ClientExtension::from(arr![SignatureScheme::EcdsaNistp256Sha256]),
+                            ClientExtension::RecordSizeLimit(1024),
+                        ]))
+                        .grease(Grease::new([0x00, 0x10, 0x20, 0x30, 0x40, 0x50]))
+                        .build()
+                ),
+                rustls_bytes(r_ClientHelloPayload {
+                    client_version: r_ProtocolVersion::TLSv1_2,
+                    random: r_Random::from_slice(&[0; 32]),
+                    session_id: r_SessionId::empty(),
+                    cipher_suites: vec![
+                        r_CipherSuite::Unknown(0x0a0a),
+                        r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+                    ],
+                    compression_methods: vec![r_Compression::Null],
+                    extensions: vec![
+                        r_ClientExtension::Unknown(r_UnknownExtension {
+                            typ: r_ExtensionType::Unknown(0x1a1a),
+                            payload: r_Payload::new(vec![]),
+                        }),
+                        r_ClientExtension::SupportedVersions(vec![
+                            r_ProtocolVersion::Unknown(0x4a4a),
+                            r_ProtocolVersion::TLSv1_3,
+                        ]),
+                        r_ClientExtension::NamedGroups(vec![
+                            r_NamedGroup::Unknown(0x3a3a),
+                            r_NamedGroup::X25519,
+                        ]),
+                        r_ClientExtension::SignatureAlgorithms(vec![
+                            r_SignatureScheme::Unknown(0x5a5a),
+                            r_SignatureScheme::ECDSA_NISTP256_SHA256,
+                        ]),
+                        r_ClientExtension::Unknown(r_UnknownExtension {
+                            typ: r_ExtensionType::Unknown(0x001c),
+                            payload: r_Payload::new(vec![0x04, 0x00]),
+                        }),
+                        r_ClientExtension::Unknown(r_UnknownExtension {
+                            typ: r_ExtensionType::Unknown(0x2a2a),
+                            payload: r_Payload::new(vec![0]),
+                        }),
+                    ],
+                }),
+            )
+        }
+
+        #[test]
+        fn hello_grease_no_extensions() {
+            let payload = ClientHelloPayload::builder()
+                .grease(Grease::new([0xf0; 6]))
+                .build();
+            let bytes = embed_bytes(payload);
+            let payload = ClientHelloPayload::decode(&mut Decoder::new(&bytes)).unwrap();
+
+            assert_eq!(
+                payload.cipher_suites,
+                Array::Typed(&[CipherSuite::Unknown(0xfafa)])
+            );
+            assert_eq!(payload.extensions.iter().count(), 2);
+        }
+
+        #[test]
+        fn hello_auto_padding() {
+            let payload = ClientHelloPayload::builder()
+                .extensions(Extensions::from(arr![ClientExtension::Unknown(
+                    ExtensionType::Unknown(0xff01),
+                    &[1; 220],
+                )]))
+                .auto_padding()
+                .build();
+            let bytes = embed_bytes(payload);
+            let payload = ClientHelloPayload::decode(&mut Decoder::new(&bytes)).unwrap();
+
+            assert_eq!(HANDSHAKE_HEADER_SIZE + bytes.len(), 512);
+            assert_eq!(
+                payload.extensions,
+                Extensions::from(arr![
+                    ClientExtension::Unknown(ExtensionType::Unknown(0xff01), &[1; 220]),
+                    ClientExtension::Padding(239),
+                ])
+            );
+        }
+
+        #[test]
+        fn hello_auto_padding_without_extensions() {
+            let payload = ClientHelloPayload::builder()
+                .cipher_suites(Array::Bytes(&[0x13; 300]))
+                .auto_padding()
+                .build();
+            let bytes = embed_bytes(payload);
+
+            assert_eq!(HANDSHAKE_HEADER_SIZE + bytes.len(), 512);
+        }
+
+        #[test]
+        fn hello_auto_padding_at_least_one_byte() {
+            let payload = ClientHelloPayload::builder()
+                .extensions(Extensions::from(arr![ClientExtension::Unknown(
+                    ExtensionType::Unknown(0xff01),
+                    &[1; 460],
+                )]))
+                .auto_padding()
+                .build();
+
+            assert_eq!(payload.padding_len(), Some(1));
+            assert_eq!(HANDSHAKE_HEADER_SIZE + embed_bytes(payload).len(), 514);
+        }
+
+        #[test]
+        fn hello_auto_padding_out_of_range() {
+            let short = ClientHelloPayload::builder().auto_padding().build();
+            let long = ClientHelloPayload::builder()
+                .extensions(Extensions::from(arr![ClientExtension::Unknown(
+                    ExtensionType::Unknown(0xff01),
+                    &[1; 500],
+                )]))
+                .auto_padding()
+                .build();
+
+            assert_eq!(short.padding_len(), None);
+            assert_eq!(long.padding_len(), None);
+            assert_eq!(
+                embed_bytes(short),
+                embed_bytes(ClientHelloPayload::builder().build())
+            );
+        }
+
+        #[test]
+        fn hello_padding_before_pre_shared_key() {
+            let extensions = [
+                ClientExtension::from(arr![ProtocolVersion::TLSv1_3]),
+                ClientExtension::Unknown(ExtensionType::PreSharedKey, &[2; 200]),
+            ];
+            let payload = ClientHelloPayload::builder()
+                .extensions(Extensions::from(Array::from(&extensions[..])))
+                .grease(Grease::new([0x00, 0x10, 0x20, 0x30, 0x40, 0x50]))
+                .auto_padding()
+                .build();
+            let bytes = embed_bytes(payload);
+            let payload = ClientHelloPayload::decode(&mut Decoder::new(&bytes)).unwrap();
+
+            assert_eq!(HANDSHAKE_HEADER_SIZE + bytes.len(), 512);
+            assert_eq!(
+                payload
+                    .extensions
+                    .iter()
+                    .map(|ext| ext.as_ref().ty())
+                    .collect::<Vec<_>>(),
+                vec![
+                    ExtensionType::Unknown(0x1a1a),
+                    ExtensionType::SupportedVersions,
+                    ExtensionType::Unknown(0x2a2a),
+                    ExtensionType::Padding,
+                    ExtensionType::PreSharedKey,
+                ]
+            );
+        }
     }
 
     mod decode {
@@ -678,6 +1024,55 @@ mod tests {
                 ])
             );
         }
+
+        #[test]
+        fn hello_grease() {
+            let bytes = rustls_bytes(r_ClientHelloPayload {
+                client_version: r_ProtocolVersion::TLSv1_2,
+                random: r_Random::from_slice(&[0; 32]),
+                session_id: r_SessionId::empty(),
+                cipher_suites: vec![
+                    r_CipherSuite::Unknown(0x8a8a),
+                    r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+                ],
+                compression_methods: vec![r_Compression::Null],
+                extensions: vec![
+                    r_ClientExtension::Unknown(r_UnknownExtension {
+                        typ: r_ExtensionType::Unknown(0xbaba),
+                        payload: r_Payload::new(vec![]),
+                    }),
+                    r_ClientExtension::NamedGroups(vec![
+                        r_NamedGroup::Unknown(0xcaca),
+                        r_NamedGroup::X25519,
+                    ]),
+                ],
+            });
+            let mut dec = Decoder::new(&bytes);
+            let payload = ClientHelloPayload::decode(&mut dec).unwrap();
+
+            assert!(payload.grease().is_none());
+            assert_eq!(
+                payload.cipher_suites,
+                arr![
+                    CipherSuite::Unknown(0x8a8a),
+                    CipherSuite::TlsAes128GcmSha256
+                ],
+            );
+            assert!(payload
+                .cipher_suites
+                .iter()
+                .next()
+                .unwrap()
+                .as_ref()
+                .is_grease());
+            assert_eq!(
+                payload.extensions,
+                Extensions::from(arr![
+                    ClientExtension::Unknown(ExtensionType::Unknown(0xbaba), &[]),
+                    ClientExtension::from(arr![NamedGroup::Unknown(0xcaca), NamedGroup::X25519]),
+                ])
+            );
+        }
     }
 
     fn rustls_bytes(payload: r_ClientHelloPayload) -> Vec<u8> {
@@ -0,0 +1,308 @@
+use crate::{
+    error::{Error as TlsError, Result as TlsResult},
+    record::ContentType,
+};
+use managed::ManagedSlice;
+
+/// Largest handshake message accepted unless configured otherwise.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 0xffff;
+
+const MESSAGE_HEADER_SIZE: usize = 4;
+
+/// Reassembles handshake messages from the content of handshake records.
+///
+/// A record may carry several handshake messages, and a message may be fragmented over several
+/// records. Complete messages are returned with their header so they can be passed on to
+/// [`Transcript::decode_message`](crate::handshake::transcript::Transcript::decode_message).
+pub struct HandshakeDeframer<'a> {
+    buf: ManagedSlice<'a, u8>,
+    start: usize,
+    end: usize,
+    max_message_size: usize,
+}
+
+impl<'a> HandshakeDeframer<'a> {
+    /// Creates a deframer that reassembles messages in `buf`.
+    ///
+    /// A borrowed buffer limits the largest message to what fits in it.
+    pub fn new<T: Into<ManagedSlice<'a, u8>>>(buf: T) -> Self {
+        let buf = buf.into();
+        let max_message_size = match buf {
+            ManagedSlice::Borrowed(ref b) => b.len().saturating_sub(MESSAGE_HEADER_SIZE),
+            ManagedSlice::Owned(_) => DEFAULT_MAX_MESSAGE_SIZE,
+        };
+
+        Self {
+            buf,
+            start: 0,
+            end: 0,
+            max_message_size: max_message_size.min(DEFAULT_MAX_MESSAGE_SIZE),
+        }
+    }
+
+    /// Sets the largest handshake message body that will be reassembled.
+    pub fn set_max_message_size(&mut self, size: usize) {
+        self.max_message_size = size;
+    }
+
+    /// Whether part of a handshake message is buffered, waiting for the rest to arrive.
+    pub fn has_partial(&self) -> bool {
+        self.start != self.end
+    }
+
+    /// Whether a complete handshake message is buffered.
+    pub fn has_message(&self) -> bool {
+        self.body_len(self.start)
+            .is_some_and(|len| self.buffered() >= MESSAGE_HEADER_SIZE + len)
+    }
+
+    /// Number of bytes needed to complete the header of the first buffered message, or the
+    /// message itself once the header is complete.
+    pub fn missing(&self) -> usize {
+        match self.body_len(self.start) {
+            Some(len) => (MESSAGE_HEADER_SIZE + len).saturating_sub(self.buffered()),
+            None => MESSAGE_HEADER_SIZE - self.buffered(),
+        }
+    }
+
+    /// Number of bytes currently buffered.
+    pub fn buffered(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Adds the content of a record.
+    ///
+    /// Records of every content type must be passed in, so that other records arriving in the
+    /// middle of a fragmented handshake message can be rejected. Only the content of handshake
+    /// records is buffered.
+    pub fn push(&mut self, ty: ContentType, fragment: &[u8]) -> TlsResult<()> {
+        if ty != ContentType::Handshake {
+            if self.has_partial() {
+                return Err(TlsError::UnexpectedMessage);
+            }
+            return Ok(());
+        }
+        if fragment.is_empty() {
+            return Err(TlsError::UnexpectedMessage);
+        }
+
+        self.compact();
+        match self.buf {
+            ManagedSlice::Borrowed(ref mut buf) => {
+                if buf.len() - self.end < fragment.len() {
+                    return Err(TlsError::InternalError(
+                        "handshake message exceeds reassembly buffer",
+                    ));
+                }
+                buf[self.end..self.end + fragment.len()].copy_from_slice(fragment);
+            }
+            ManagedSlice::Owned(ref mut buf) => {
+                buf.truncate(self.end);
+                buf.extend_from_slice(fragment);
+            }
+        }
+        self.end += fragment.len();
+
+        self.check_message_size()
+    }
+
+    /// Returns the next complete handshake message, including its header.
+    pub fn next_message(&mut self) -> Option<&[u8]> {
+        if !self.has_message() {
+            return None;
+        }
+        let len = MESSAGE_HEADER_SIZE + self.body_len(self.start)?;
+
+        let start = self.start;
+        self.start += len;
+        if self.start == self.end {
+            self.start = 0;
+            self.end = 0;
+        }
+        Some(&self.buf[start..start + len])
+    }
+
+    /// Length of the body of the message buffered at `offset`, if its header has arrived.
+    fn body_len(&self, offset: usize) -> Option<usize> {
+        if self.end < offset + MESSAGE_HEADER_SIZE {
+            return None;
+        }
+
+        let header = &self.buf[offset..offset + MESSAGE_HEADER_SIZE];
+        Some(
+            header[1..]
+                .iter()
+                .fold(0, |len, b| (len << 8) | usize::from(*b)),
+        )
+    }
+
+    /// Checks every message whose header has been buffered is within the size limit.
+    fn check_message_size(&self) -> TlsResult<()> {
+        let mut offset = self.start;
+        while let Some(len) = self.body_len(offset) {
+            if len > self.max_message_size {
+                return Err(TlsError::InternalError(
+                    "handshake message exceeds maximum size",
+                ));
+            }
+            offset += MESSAGE_HEADER_SIZE + len;
+        }
+        Ok(())
+    }
+
+    /// Moves buffered bytes to the start of the buffer.
+    fn compact(&mut self) {
+        if self.start == 0 {
+            return;
+        }
+
+        let (start, end) = (self.start, self.end);
+        self.buf.copy_within(start..end, 0);
+        self.start = 0;
+        self.end = end - start;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FINISHED: [u8; 7] = [0x14, 0, 0, 3, 1, 2, 3];
+    const KEY_UPDATE: [u8; 5] = [0x18, 0, 0, 1, 0];
+
+    #[test]
+    fn single_message() {
+        let mut deframer = HandshakeDeframer::new(vec![]);
+        deframer.push(ContentType::Handshake, &FINISHED).unwrap();
+
+        assert!(deframer.has_message());
+        assert_eq!(deframer.next_message().unwrap(), FINISHED);
+        assert!(!deframer.has_message());
+        assert!(deframer.next_message().is_none());
+        assert!(!deframer.has_partial());
+    }
+
+    #[test]
+    fn coalesced_messages() {
+        let mut bytes = FINISHED.to_vec();
+        bytes.extend_from_slice(&KEY_UPDATE);
+        let mut deframer = HandshakeDeframer::new(vec![]);
+        deframer.push(ContentType::Handshake, &bytes).unwrap();
+
+        assert_eq!(deframer.next_message().unwrap(), FINISHED);
+        assert_eq!(deframer.next_message().unwrap(), KEY_UPDATE);
+        assert!(deframer.next_message().is_none());
+    }
+
+    #[test]
+    fn fragmented_message() {
+        let mut buf = [0; 16];
+        let mut deframer = HandshakeDeframer::new(buf.as_mut());
+        deframer
+            .push(ContentType::Handshake, &FINISHED[..2])
+            .unwrap();
+        assert!(deframer.next_message().is_none());
+        assert_eq!(deframer.missing(), 2);
+
+        deframer
+            .push(ContentType::Handshake, &FINISHED[2..5])
+            .unwrap();
+        assert!(deframer.next_message().is_none());
+        assert!(deframer.has_partial());
+        assert_eq!(deframer.missing(), 2);
+
+        deframer
+            .push(ContentType::Handshake, &FINISHED[5..])
+            .unwrap();
+        assert_eq!(deframer.next_message().unwrap(), FINISHED);
+        assert!(!deframer.has_partial());
+    }
+
+    #[test]
+    fn message_spanning_records_after_complete_message() {
+        let mut buf = [0; 12];
+        let mut deframer = HandshakeDeframer::new(buf.as_mut());
+
+        let mut bytes = KEY_UPDATE.to_vec();
+        bytes.extend_from_slice(&FINISHED);
+        deframer.push(ContentType::Handshake, &bytes[..8]).unwrap();
+        assert_eq!(deframer.next_message().unwrap(), KEY_UPDATE);
+        assert!(deframer.next_message().is_none());
+
+        deframer.push(ContentType::Handshake, &bytes[8..]).unwrap();
+        assert_eq!(deframer.next_message().unwrap(), FINISHED);
+    }
+
+    #[test]
+    fn interleaved_content_type() {
+        let mut deframer = HandshakeDeframer::new(vec![]);
+        deframer
+            .push(ContentType::Handshake, &FINISHED[..5])
+            .unwrap();
+
+        assert!(matches!(
+            deframer.push(ContentType::ApplicationData, &[1]),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn other_content_type_between_messages() {
+        let mut deframer = HandshakeDeframer::new(vec![]);
+        deframer.push(ContentType::Handshake, &FINISHED).unwrap();
+        deframer.next_message().unwrap();
+
+        assert!(deframer.push(ContentType::Alert, &[1, 0]).is_ok());
+        assert!(deframer.next_message().is_none());
+    }
+
+    #[test]
+    fn empty_fragment() {
+        let mut deframer = HandshakeDeframer::new(vec![]);
+
+        assert!(matches!(
+            deframer.push(ContentType::Handshake, &[]),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn message_too_large() {
+        let mut deframer = HandshakeDeframer::new(vec![]);
+        deframer.set_max_message_size(2);
+
+        assert!(deframer
+            .push(ContentType::Handshake, &FINISHED[..4])
+            .is_err());
+    }
+
+    #[test]
+    fn coalesced_message_too_large() {
+        let mut deframer = HandshakeDeframer::new(vec![]);
+        deframer.set_max_message_size(2);
+
+        let mut bytes = KEY_UPDATE.to_vec();
+        bytes.extend_from_slice(&FINISHED);
+        assert!(deframer.push(ContentType::Handshake, &bytes).is_err());
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut buf = [0; 6];
+        let mut deframer = HandshakeDeframer::new(buf.as_mut());
+
+        assert!(deframer
+            .push(ContentType::Handshake, &FINISHED[..4])
+            .is_err());
+    }
+
+    #[test]
+    fn buffer_too_small_for_coalesced_messages() {
+        let mut buf = [0; 8];
+        let mut deframer = HandshakeDeframer::new(buf.as_mut());
+
+        let mut bytes = KEY_UPDATE.to_vec();
+        bytes.extend_from_slice(&FINISHED);
+        assert!(deframer.push(ContentType::Handshake, &bytes).is_err());
+    }
+}
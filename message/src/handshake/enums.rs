@@ -57,8 +57,62 @@ enum_default!(CipherSuite, TlsAes128GcmSha256);
 msg_enum! {
     ExtensionType, u16;
     {
+        ServerName => 0x0000,
+        MaxFragmentLength => 0x0001,
+        StatusRequest => 0x0005,
+        SupportedGroups => 0x000a,
         SignatureAlgorithms => 0x000d,
+        SignedCertificateTimestamp => 0x0012,
+        Padding => 0x0015,
+        RecordSizeLimit => 0x001c,
+        PreSharedKey => 0x0029,
         SupportedVersions => 0x002b,
+        Cookie => 0x002c,
+        PskKeyExchangeModes => 0x002d,
+        CertificateAuthorities => 0x002f,
+        KeyShare => 0x0033,
+    }
+}
+
+msg_enum! {
+    NamedGroup, u16;
+    {
+        // Elliptic curve groups.
+        Secp256r1 => 0x0017,
+        Secp384r1 => 0x0018,
+        Secp521r1 => 0x0019,
+        X25519 => 0x001d,
+        X448 => 0x001e,
+
+        // Finite field groups.
+        Ffdhe2048 => 0x0100,
+        Ffdhe3072 => 0x0101,
+        Ffdhe4096 => 0x0102,
+        Ffdhe6144 => 0x0103,
+        Ffdhe8192 => 0x0104,
+    }
+}
+
+msg_enum! {
+    MaxFragmentLength, u8;
+    {
+        Max512 => 0x01,
+        Max1024 => 0x02,
+        Max2048 => 0x03,
+        Max4096 => 0x04,
+    }
+}
+
+impl MaxFragmentLength {
+    /// Largest record content allowed, or `None` for values not defined by RFC 6066.
+    pub fn fragment_len(self) -> Option<usize> {
+        match self {
+            MaxFragmentLength::Max512 => Some(512),
+            MaxFragmentLength::Max1024 => Some(1024),
+            MaxFragmentLength::Max2048 => Some(2048),
+            MaxFragmentLength::Max4096 => Some(4096),
+            MaxFragmentLength::Unknown(_) => None,
+        }
     }
 }
 
@@ -89,3 +143,196 @@ msg_enum! {
         EcdsaSha1Legacy => 0x0203,
     }
 }
+
+/// Hash function used by a cipher suite or signature scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Size of the digest in bytes.
+    pub const fn output_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+            HashAlgorithm::Sha384 => 48,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+
+    /// Size of the blocks the hash function processes, in bytes.
+    pub const fn block_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 | HashAlgorithm::Sha256 => 64,
+            HashAlgorithm::Sha384 | HashAlgorithm::Sha512 => 128,
+        }
+    }
+}
+
+/// AEAD algorithm used to protect records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AeadAlgorithm {
+    Aes128Gcm,
+    Aes256Gcm,
+    ChaCha20Poly1305,
+}
+
+/// Algorithms and key sizes a cipher suite protects records with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CipherSuiteInfo {
+    pub hash: HashAlgorithm,
+    pub aead: AeadAlgorithm,
+    pub key_len: usize,
+    pub iv_len: usize,
+    pub tag_len: usize,
+    pub min_version: ProtocolVersion,
+}
+
+impl CipherSuite {
+    /// Metadata of the cipher suite, or `None` if it isn't known.
+    pub const fn info(self) -> Option<CipherSuiteInfo> {
+        let (hash, aead, key_len) = match self {
+            CipherSuite::TlsAes128GcmSha256 => {
+                (HashAlgorithm::Sha256, AeadAlgorithm::Aes128Gcm, 16)
+            }
+            CipherSuite::TlsAes256GcmSha384 => {
+                (HashAlgorithm::Sha384, AeadAlgorithm::Aes256Gcm, 32)
+            }
+            CipherSuite::TlsChaCha20Poly1305Sha256 => {
+                (HashAlgorithm::Sha256, AeadAlgorithm::ChaCha20Poly1305, 32)
+            }
+            CipherSuite::Unknown(_) => return None,
+        };
+
+        Some(CipherSuiteInfo {
+            hash,
+            aead,
+            key_len,
+            iv_len: 12,
+            tag_len: 16,
+            min_version: ProtocolVersion::TLSv1_3,
+        })
+    }
+
+    /// Hash function of the cipher suite, or `None` if it isn't known.
+    pub fn hash(self) -> Option<HashAlgorithm> {
+        self.info().map(|info| info.hash)
+    }
+}
+
+/// Signature algorithm of a signature scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureAlgorithm {
+    RsaPkcs1,
+    RsaPss,
+    Ecdsa,
+    Ed25519,
+    Ed448,
+}
+
+/// Algorithms of a signature scheme and where it may be used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureSchemeInfo {
+    pub algorithm: SignatureAlgorithm,
+    /// Hash the message is signed with, or `None` for EdDSA which hashes internally.
+    pub hash: Option<HashAlgorithm>,
+    /// Whether the scheme may be negotiated in TLS 1.3.
+    pub tls13: bool,
+    /// Whether the scheme may only sign certificates, not handshake messages (RFC 8446,
+    /// section 4.2.3).
+    pub certificate_only: bool,
+}
+
+impl SignatureScheme {
+    /// Metadata of the signature scheme, or `None` if it isn't known.
+    pub const fn info(self) -> Option<SignatureSchemeInfo> {
+        use self::{HashAlgorithm::*, SignatureAlgorithm::*};
+
+        let (algorithm, hash, tls13, certificate_only) = match self {
+            SignatureScheme::RsaPkcs1Sha256 => (RsaPkcs1, Some(Sha256), true, true),
+            SignatureScheme::RsaPkcs1Sha384 => (RsaPkcs1, Some(Sha384), true, true),
+            SignatureScheme::RsaPkcs1Sha512 => (RsaPkcs1, Some(Sha512), true, true),
+            SignatureScheme::EcdsaNistp256Sha256 => (Ecdsa, Some(Sha256), true, false),
+            SignatureScheme::EcdsaNistp384Sha384 => (Ecdsa, Some(Sha384), true, false),
+            SignatureScheme::EcdsaNistp521Sha512 => (Ecdsa, Some(Sha512), true, false),
+            SignatureScheme::RsaPssSha256 => (RsaPss, Some(Sha256), true, false),
+            SignatureScheme::RsaPssSha384 => (RsaPss, Some(Sha384), true, false),
+            SignatureScheme::RsaPssSha512 => (RsaPss, Some(Sha512), true, false),
+            SignatureScheme::Ed25519 => (Ed25519, None, true, false),
+            SignatureScheme::Ed448 => (Ed448, None, true, false),
+            SignatureScheme::RsaPkcs1Sha1 => (RsaPkcs1, Some(Sha1), false, true),
+            SignatureScheme::EcdsaSha1Legacy => (Ecdsa, Some(Sha1), false, true),
+            SignatureScheme::Unknown(_) => return None,
+        };
+
+        Some(SignatureSchemeInfo {
+            algorithm,
+            hash,
+            tls13,
+            certificate_only,
+        })
+    }
+
+    /// Whether the scheme may sign TLS 1.3 handshake messages, such as CertificateVerify.
+    pub fn is_tls13_handshake(self) -> bool {
+        self.info()
+            .is_some_and(|info| info.tls13 && !info.certificate_only)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cipher_suite_info() {
+        assert_eq!(
+            CipherSuite::TlsAes256GcmSha384.info(),
+            Some(CipherSuiteInfo {
+                hash: HashAlgorithm::Sha384,
+                aead: AeadAlgorithm::Aes256Gcm,
+                key_len: 32,
+                iv_len: 12,
+                tag_len: 16,
+                min_version: ProtocolVersion::TLSv1_3,
+            })
+        );
+        assert_eq!(
+            CipherSuite::TlsChaCha20Poly1305Sha256.hash(),
+            Some(HashAlgorithm::Sha256)
+        );
+    }
+
+    #[test]
+    fn unknown_cipher_suite_info() {
+        assert!(CipherSuite::Unknown(0x00ff).info().is_none());
+    }
+
+    #[test]
+    fn signature_scheme_info() {
+        assert_eq!(
+            SignatureScheme::RsaPssSha384.info(),
+            Some(SignatureSchemeInfo {
+                algorithm: SignatureAlgorithm::RsaPss,
+                hash: Some(HashAlgorithm::Sha384),
+                tls13: true,
+                certificate_only: false,
+            })
+        );
+        assert_eq!(SignatureScheme::Ed25519.info().unwrap().hash, None);
+        assert!(SignatureScheme::Unknown(0x0101).info().is_none());
+    }
+
+    #[test]
+    fn tls13_handshake_signature_schemes() {
+        assert!(SignatureScheme::EcdsaNistp256Sha256.is_tls13_handshake());
+        assert!(SignatureScheme::RsaPssSha256.is_tls13_handshake());
+        assert!(!SignatureScheme::RsaPkcs1Sha256.is_tls13_handshake());
+        assert!(!SignatureScheme::RsaPkcs1Sha1.is_tls13_handshake());
+        assert!(!SignatureScheme::Unknown(0x0101).is_tls13_handshake());
+    }
+}
@@ -9,6 +9,16 @@ macro_rules! msg_enum {
             Unknown($ty),
         }
 
+        impl $ident {
+            /// Whether this is a GREASE value (RFC 8701), which peers must ignore.
+            pub fn is_grease(&self) -> bool {
+                match *self {
+                    $ident::Unknown(x) => crate::handshake::grease::GreaseValue::is_grease(x),
+                    _ => false,
+                }
+            }
+        }
+
         impl From<$ident> for $ty {
             fn from(val: $ident) -> Self {
                 match val {
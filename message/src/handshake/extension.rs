@@ -4,18 +4,25 @@ use crate::{
         Codec, CodecSized, Decoder, Encoder, HeaderSize,
     },
     error::Result as TlsResult,
-    handshake::enums::{ProtocolVersion, SignatureScheme},
+    handshake::{
+        enums::{ExtensionType, NamedGroup, ProtocolVersion, SignatureScheme},
+        grease::Grease,
+    },
 };
+use certificate::CertificateExtension;
+use certificate_request::CertificateRequestExtension;
 use client::ClientExtension;
 use server::ServerExtension;
 
+pub mod certificate;
+pub mod certificate_request;
 pub mod client;
 pub mod server;
 
 #[macro_use]
 mod macros;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Extensions<'a, T: CodecSized<'a>>(Array<'a, T>);
 
 impl<'a, T: CodecSized<'a>> Extensions<'a, T> {
@@ -34,6 +41,77 @@ impl<'a, T: CodecSized<'a>> Extensions<'a, T> {
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
+
+    /// Size of the extensions as encoded by `encode_extensions`, which has a header even when
+    /// there are none.
+    pub fn extensions_size(&self) -> usize {
+        T::HEADER_SIZE.size() + self.0.data_size()
+    }
+}
+
+impl<'a> Extensions<'a, ClientExtension<'a>> {
+    /// Size of the extensions as encoded by `encode_with`.
+    pub(crate) fn size_with(&self, grease: Option<&Grease>, padding: Option<usize>) -> usize {
+        ClientExtension::HEADER_SIZE.size() + self.items_size_with(grease, padding)
+    }
+
+    /// Encodes the extensions with GREASE and padding added.
+    ///
+    /// GREASE values go at the front of the lists that take them, and the extensions go between
+    /// an empty and a single byte GREASE extension. Padding comes after those, except that a
+    /// pre_shared_key extension stays last (RFC 8446, section 4.2.11).
+    pub(crate) fn encode_with(
+        &self,
+        grease: Option<&Grease>,
+        padding: Option<usize>,
+        enc: &mut Encoder<'a>,
+    ) -> TlsResult<()> {
+        ClientExtension::HEADER_SIZE.encode_len(self.items_size_with(grease, padding), enc)?;
+
+        let pre_shared_key = self
+            .iter()
+            .last()
+            .filter(|ext| ext.as_ref().ty() == ExtensionType::PreSharedKey);
+        let count = self.iter().count() - pre_shared_key.iter().count();
+
+        if let Some(grease) = grease {
+            grease_extensions(grease).0.encode(enc)?;
+        }
+        self.iter()
+            .take(count)
+            .try_for_each(|ext| ext.as_ref().encode_greased(grease, enc))?;
+        if let Some(grease) = grease {
+            grease_extensions(grease).1.encode(enc)?;
+        }
+        if let Some(len) = padding {
+            ClientExtension::Padding(len).encode(enc)?;
+        }
+        pre_shared_key
+            .iter()
+            .try_for_each(|ext| ext.as_ref().encode(enc))
+    }
+
+    fn items_size_with(&self, grease: Option<&Grease>, padding: Option<usize>) -> usize {
+        let grease_size = grease.map_or(0, |grease| {
+            let (empty, single) = grease_extensions(grease);
+            empty.data_size() + single.data_size()
+        });
+        let padding_size = padding.map_or(0, |len| ClientExtension::Padding(len).data_size());
+
+        grease_size
+            + self
+                .iter()
+                .map(|ext| ext.as_ref().greased_size(grease))
+                .sum::<usize>()
+            + padding_size
+    }
+}
+
+fn grease_extensions<'a>(grease: &Grease) -> (ClientExtension<'a>, ClientExtension<'a>) {
+    (
+        ClientExtension::Unknown(grease.extensions[0], &[]),
+        ClientExtension::Unknown(grease.extensions[1], &[0]),
+    )
 }
 
 impl<'a, T: CodecSized<'a>> Codec<'a> for Extensions<'a, T> {
@@ -75,18 +153,68 @@ where
     }
 }
 
-impl<'a, T> From<T> for Extensions<'a, ServerExtension>
+impl<'a, T> From<T> for Extensions<'a, ServerExtension<'a>>
+where
+    T: Into<Array<'a, ServerExtension<'a>>>,
+{
+    fn from(data: T) -> Self {
+        Self(data.into())
+    }
+}
+
+impl<'a, T> From<T> for Extensions<'a, CertificateExtension<'a>>
 where
-    T: Into<Array<'a, ServerExtension>>,
+    T: Into<Array<'a, CertificateExtension<'a>>>,
 {
     fn from(data: T) -> Self {
         Self(data.into())
     }
 }
 
+impl<'a, T> From<T> for Extensions<'a, CertificateRequestExtension<'a>>
+where
+    T: Into<Array<'a, CertificateRequestExtension<'a>>>,
+{
+    fn from(data: T) -> Self {
+        Self(data.into())
+    }
+}
+
+/// Key exchange share for a group (RFC 8446, section 4.2.8).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyShareEntry<'a> {
+    pub group: NamedGroup,
+    pub payload: &'a [u8],
+}
+
+impl<'a> Codec<'a> for KeyShareEntry<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        self.group.encode(enc)?;
+        (self.payload.len() as u16).encode(enc)?;
+        enc.append(self.payload)
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        let group = NamedGroup::decode(dec)?;
+        let len = u16::decode(dec)?;
+        let payload = dec.take(usize::from(len))?;
+        Some(KeyShareEntry { group, payload })
+    }
+}
+
+impl<'a> CodecSized<'a> for KeyShareEntry<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::U16;
+
+    fn data_size(&self) -> usize {
+        self.group.data_size() + (self.payload.len() as u16).data_size() + self.payload.len()
+    }
+}
+
 // TODO: Make `ext_array` macro more expressive/explicit
 ext_array!(SignatureSchemes, HeaderSize::U16, SignatureScheme);
 ext_array!(ProtocolVersions, HeaderSize::U8, ProtocolVersion);
+ext_array!(NamedGroups, HeaderSize::U16, NamedGroup);
+ext_array!(KeyShareEntries, HeaderSize::U16, KeyShareEntry<'a>);
 
 #[cfg(test)]
 mod tests {
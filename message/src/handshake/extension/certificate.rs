@@ -0,0 +1,135 @@
+use crate::{
+    codec::{Codec, CodecSized, Decoder, Encoder, HeaderSize},
+    error::Result as TlsResult,
+    handshake::enums::ExtensionType,
+};
+
+/// CertificateStatusType of an OCSP response (RFC 6066, section 8).
+const OCSP: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CertificateExtension<'a> {
+    /// DER encoded OCSP response stapled to the certificate (RFC 8446, section 4.4.2.1).
+    StatusRequest(&'a [u8]),
+    /// SignedCertificateTimestampList for the certificate, as encoded in the extension (RFC 6962,
+    /// section 3.3).
+    SignedCertificateTimestamp(&'a [u8]),
+    Unknown(ExtensionType, &'a [u8]),
+}
+
+impl<'a> CertificateExtension<'a> {
+    pub fn ty(&self) -> ExtensionType {
+        match self {
+            CertificateExtension::StatusRequest(_) => ExtensionType::StatusRequest,
+            CertificateExtension::SignedCertificateTimestamp(_) => {
+                ExtensionType::SignedCertificateTimestamp
+            }
+            CertificateExtension::Unknown(ty, _) => *ty,
+        }
+    }
+
+    fn ext_size(&self) -> usize {
+        match self {
+            CertificateExtension::StatusRequest(response) => {
+                OCSP.data_size() + HeaderSize::U24.size() + response.len()
+            }
+            CertificateExtension::SignedCertificateTimestamp(data) => data.len(),
+            CertificateExtension::Unknown(_, data) => data.len(),
+        }
+    }
+}
+
+impl<'a> Codec<'a> for CertificateExtension<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        self.ty().encode(enc)?;
+        (self.ext_size() as u16).encode(enc)?;
+
+        match self {
+            CertificateExtension::StatusRequest(response) => {
+                OCSP.encode(enc)?;
+                HeaderSize::U24.encode_len(response.len(), enc)?;
+                enc.append(response)
+            }
+            CertificateExtension::SignedCertificateTimestamp(data) => enc.append(data),
+            CertificateExtension::Unknown(_, data) => enc.append(data),
+        }
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        let ty = ExtensionType::decode(dec)?;
+        let len = Self::decode_len(dec)?;
+        let data = dec.take(len)?;
+        let mut sub = Decoder::new(data);
+
+        match ty {
+            ExtensionType::StatusRequest => {
+                if u8::decode(&mut sub)? != OCSP {
+                    return None;
+                }
+                let len = HeaderSize::U24.decode_len(&mut sub)?;
+                let response = sub.take(len).filter(|response| !response.is_empty())?;
+                if !sub.is_complete() {
+                    return None;
+                }
+                Some(CertificateExtension::StatusRequest(response))
+            }
+            ExtensionType::SignedCertificateTimestamp => {
+                Some(CertificateExtension::SignedCertificateTimestamp(data))
+            }
+            _ => Some(CertificateExtension::Unknown(ty, data)),
+        }
+    }
+}
+
+impl<'a> CodecSized<'a> for CertificateExtension<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::U16;
+
+    fn data_size(&self) -> usize {
+        Self::HEADER_SIZE.size() + self.ty().data_size() + self.ext_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_status_request() {
+        let ext = CertificateExtension::StatusRequest(&[1, 2]);
+        let mut enc = Encoder::new(vec![]);
+        ext.encode(&mut enc).unwrap();
+
+        assert_eq!(ext.data_size(), 10);
+        assert_eq!(enc.bytes(), [0x00, 0x05, 0, 6, 1, 0, 0, 2, 1, 2]);
+    }
+
+    #[test]
+    fn decode_status_request() {
+        let bytes = [0x00, 0x05, 0, 6, 1, 0, 0, 2, 1, 2];
+        let mut dec = Decoder::new(&bytes);
+
+        assert_eq!(
+            CertificateExtension::decode(&mut dec).unwrap(),
+            CertificateExtension::StatusRequest(&[1, 2])
+        );
+        assert!(dec.is_complete());
+    }
+
+    #[test]
+    fn decode_status_request_not_ocsp() {
+        let bytes = [0x00, 0x05, 0, 6, 2, 0, 0, 2, 1, 2];
+
+        assert!(CertificateExtension::decode(&mut Decoder::new(&bytes)).is_none());
+    }
+
+    #[test]
+    fn decode_signed_certificate_timestamp() {
+        let bytes = [0x00, 0x12, 0, 3, 0, 1, 9];
+        let mut dec = Decoder::new(&bytes);
+
+        assert_eq!(
+            CertificateExtension::decode(&mut dec).unwrap(),
+            CertificateExtension::SignedCertificateTimestamp(&[0, 1, 9])
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{
+    codec::{Codec, CodecSized, Decoder, Encoder, HeaderSize},
+    error::Result as TlsResult,
+    handshake::{enums::ExtensionType, extension::SignatureSchemes},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CertificateRequestExtension<'a> {
+    /// Schemes the server accepts signatures in, including in the CertificateVerify.
+    SignatureAlgorithms(SignatureSchemes<'a>),
+    /// DistinguishedNames of the certificate authorities the server accepts, as encoded in the
+    /// extension (RFC 8446, section 4.2.4).
+    CertificateAuthorities(&'a [u8]),
+    Unknown(ExtensionType, &'a [u8]),
+}
+
+impl<'a> CertificateRequestExtension<'a> {
+    pub fn ty(&self) -> ExtensionType {
+        match self {
+            CertificateRequestExtension::SignatureAlgorithms(_) => {
+                ExtensionType::SignatureAlgorithms
+            }
+            CertificateRequestExtension::CertificateAuthorities(_) => {
+                ExtensionType::CertificateAuthorities
+            }
+            CertificateRequestExtension::Unknown(ty, _) => *ty,
+        }
+    }
+
+    fn ext_size(&self) -> usize {
+        match self {
+            CertificateRequestExtension::SignatureAlgorithms(ref r) => {
+                SignatureSchemes::HEADER_SIZE.size() + r.data_size()
+            }
+            CertificateRequestExtension::CertificateAuthorities(data) => data.len(),
+            CertificateRequestExtension::Unknown(_, data) => data.len(),
+        }
+    }
+}
+
+impl<'a> Codec<'a> for CertificateRequestExtension<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        self.ty().encode(enc)?;
+        (self.ext_size() as u16).encode(enc)?;
+
+        match self {
+            CertificateRequestExtension::SignatureAlgorithms(ref r) => r.encode(enc),
+            CertificateRequestExtension::CertificateAuthorities(data) => enc.append(data),
+            CertificateRequestExtension::Unknown(_, data) => enc.append(data),
+        }
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        let ty = ExtensionType::decode(dec)?;
+        let len = Self::decode_len(dec)?;
+        let data = dec.take(len)?;
+        let mut sub = Decoder::new(data);
+
+        match ty {
+            ExtensionType::SignatureAlgorithms => {
+                let schemes = SignatureSchemes::decode(&mut sub)?;
+                if !sub.is_complete() {
+                    return None;
+                }
+                Some(CertificateRequestExtension::SignatureAlgorithms(schemes))
+            }
+            ExtensionType::CertificateAuthorities => {
+                Some(CertificateRequestExtension::CertificateAuthorities(data))
+            }
+            _ => Some(CertificateRequestExtension::Unknown(ty, data)),
+        }
+    }
+}
+
+impl<'a> CodecSized<'a> for CertificateRequestExtension<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::U16;
+
+    fn data_size(&self) -> usize {
+        Self::HEADER_SIZE.size() + self.ty().data_size() + self.ext_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codec::array::Array, handshake::enums::SignatureScheme};
+
+    #[test]
+    fn encode_signature_algorithms() {
+        let schemes = [SignatureScheme::EcdsaNistp256Sha256];
+        let ext =
+            CertificateRequestExtension::SignatureAlgorithms(Array::from(&schemes[..]).into());
+        let mut enc = Encoder::new(vec![]);
+        ext.encode(&mut enc).unwrap();
+
+        assert_eq!(ext.data_size(), 8);
+        assert_eq!(enc.bytes(), [0x00, 0x0d, 0, 4, 0, 2, 0x04, 0x03]);
+    }
+
+    #[test]
+    fn decode_signature_algorithms_trailing_bytes() {
+        let bytes = [0x00, 0x0d, 0, 5, 0, 2, 0x04, 0x03, 0];
+
+        assert!(CertificateRequestExtension::decode(&mut Decoder::new(&bytes)).is_none());
+    }
+}
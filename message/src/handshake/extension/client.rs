@@ -2,39 +2,157 @@ use crate::{
     codec::{array::Array, Codec, CodecSized, Decoder, Encoder, HeaderSize},
     error::Result as TlsResult,
     handshake::{
-        enums::{ExtensionType, ProtocolVersion, SignatureScheme},
-        extension::{ProtocolVersions, SignatureSchemes},
+        enums::{ExtensionType, MaxFragmentLength, NamedGroup, ProtocolVersion, SignatureScheme},
+        extension::{KeyShareEntries, NamedGroups, ProtocolVersions, SignatureSchemes},
+        grease::Grease,
     },
 };
 
-// TODO: Add unknown client extension
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ClientExtension<'a> {
+    /// Host name from the server_name extension (RFC 6066, section 3).
+    ServerName(&'a str),
+    MaxFragmentLength(MaxFragmentLength),
+    SupportedGroups(NamedGroups<'a>),
     SignatureAlgorithms(SignatureSchemes<'a>),
+    /// Number of zero bytes to pad the ClientHello with (RFC 7685).
+    Padding(usize),
+    RecordSizeLimit(u16),
     SupportedVersions(ProtocolVersions<'a>),
+    /// Cookie echoed from a HelloRetryRequest.
+    Cookie(&'a [u8]),
+    KeyShare(KeyShareEntries<'a>),
+    Unknown(ExtensionType, &'a [u8]),
 }
 
 impl<'a> ClientExtension<'a> {
     pub fn ty(&self) -> ExtensionType {
         match self {
+            ClientExtension::ServerName(_) => ExtensionType::ServerName,
+            ClientExtension::MaxFragmentLength(_) => ExtensionType::MaxFragmentLength,
+            ClientExtension::SupportedGroups(_) => ExtensionType::SupportedGroups,
             ClientExtension::SignatureAlgorithms(_) => ExtensionType::SignatureAlgorithms,
+            ClientExtension::Padding(_) => ExtensionType::Padding,
+            ClientExtension::RecordSizeLimit(_) => ExtensionType::RecordSizeLimit,
             ClientExtension::SupportedVersions(_) => ExtensionType::SupportedVersions,
+            ClientExtension::Cookie(_) => ExtensionType::Cookie,
+            ClientExtension::KeyShare(_) => ExtensionType::KeyShare,
+            ClientExtension::Unknown(ty, _) => *ty,
         }
     }
 
     // TODO: Document this.
     fn ext_size(&self) -> usize {
         match self {
+            // The list holds one host_name entry, of a type, length and the name.
+            ClientExtension::ServerName(name) => 2 + 1 + 2 + name.len(),
+            ClientExtension::MaxFragmentLength(ref r) => r.data_size(),
+            ClientExtension::SupportedGroups(ref r) => {
+                NamedGroups::HEADER_SIZE.size() + r.data_size()
+            }
             ClientExtension::SignatureAlgorithms(ref r) => {
                 SignatureSchemes::HEADER_SIZE.size() + r.data_size()
             }
+            ClientExtension::Padding(len) => *len,
+            ClientExtension::RecordSizeLimit(ref r) => r.data_size(),
             ClientExtension::SupportedVersions(ref r) => {
                 ProtocolVersions::HEADER_SIZE.size() + r.data_size()
             }
+            ClientExtension::Cookie(cookie) => (cookie.len() as u16).data_size() + cookie.len(),
+            ClientExtension::KeyShare(ref r) => KeyShareEntries::HEADER_SIZE.size() + r.data_size(),
+            ClientExtension::Unknown(_, data) => data.len(),
+        }
+    }
+
+    /// Size of the extension as encoded by `encode_greased`.
+    pub(crate) fn greased_size(&self, grease: Option<&Grease>) -> usize {
+        let grease = match grease {
+            Some(grease) => grease,
+            None => return self.data_size(),
+        };
+        match self {
+            ClientExtension::SupportedGroups(_) => self.data_size() + grease.group.data_size(),
+            ClientExtension::SignatureAlgorithms(_) => {
+                self.data_size() + grease.signature_scheme.data_size()
+            }
+            ClientExtension::SupportedVersions(_) => self.data_size() + grease.version.data_size(),
+            _ => self.data_size(),
+        }
+    }
+
+    /// Encodes the extension with the matching GREASE value at the front of its list, if it has
+    /// one.
+    pub(crate) fn encode_greased(
+        &self,
+        grease: Option<&Grease>,
+        enc: &mut Encoder<'a>,
+    ) -> TlsResult<()> {
+        let grease = match grease {
+            Some(grease) => grease,
+            None => return self.encode(enc),
+        };
+        match self {
+            ClientExtension::SupportedGroups(ref r) => {
+                encode_greased_list(self.ty(), NamedGroups::HEADER_SIZE, grease.group, &r.0, enc)
+            }
+            ClientExtension::SignatureAlgorithms(ref r) => encode_greased_list(
+                self.ty(),
+                SignatureSchemes::HEADER_SIZE,
+                grease.signature_scheme,
+                &r.0,
+                enc,
+            ),
+            ClientExtension::SupportedVersions(ref r) => encode_greased_list(
+                self.ty(),
+                ProtocolVersions::HEADER_SIZE,
+                grease.version,
+                &r.0,
+                enc,
+            ),
+            _ => self.encode(enc),
         }
     }
 }
 
+/// NameType of a DNS host name in the server_name extension.
+const HOST_NAME: u8 = 0;
+
+/// Decodes the host name from a ServerNameList. The list may hold names of other types, but at
+/// most one of each (RFC 6066, section 3).
+fn decode_server_name<'a>(dec: &mut Decoder<'a>) -> Option<&'a str> {
+    let len = u16::decode(dec)?;
+    let mut list = Decoder::new(dec.take(usize::from(len))?);
+    let mut host_name = None;
+    while list.remaining() > 0 {
+        let ty = u8::decode(&mut list)?;
+        let len = u16::decode(&mut list)?;
+        let name = list.take(usize::from(len))?;
+        if ty == HOST_NAME {
+            if host_name.is_some() || name.is_empty() {
+                return None;
+            }
+            host_name = Some(core::str::from_utf8(name).ok()?);
+        }
+    }
+    host_name
+}
+
+fn encode_greased_list<'a, T: CodecSized<'a>>(
+    ty: ExtensionType,
+    header_size: HeaderSize,
+    grease: T,
+    items: &Array<'a, T>,
+    enc: &mut Encoder<'a>,
+) -> TlsResult<()> {
+    let list_size = grease.data_size() + items.data_size();
+
+    ty.encode(enc)?;
+    ((header_size.size() + list_size) as u16).encode(enc)?;
+    header_size.encode_len(list_size, enc)?;
+    grease.encode(enc)?;
+    items.encode_items(enc)
+}
+
 impl<'a> Codec<'a> for ClientExtension<'a> {
     fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
         self.ty().encode(enc)?;
@@ -43,25 +161,59 @@ impl<'a> Codec<'a> for ClientExtension<'a> {
         (self.ext_size() as u16).encode(enc)?;
 
         match self {
+            ClientExtension::ServerName(name) => {
+                ((1 + 2 + name.len()) as u16).encode(enc)?;
+                HOST_NAME.encode(enc)?;
+                (name.len() as u16).encode(enc)?;
+                enc.append(name.as_bytes())
+            }
+            ClientExtension::MaxFragmentLength(ref r) => r.encode(enc),
+            ClientExtension::SupportedGroups(ref r) => r.encode(enc),
             ClientExtension::SignatureAlgorithms(ref r) => r.encode(enc),
+            ClientExtension::Padding(len) => (0..*len).try_for_each(|_| 0u8.encode(enc)),
+            ClientExtension::RecordSizeLimit(ref r) => r.encode(enc),
             ClientExtension::SupportedVersions(ref r) => r.encode(enc),
+            ClientExtension::Cookie(cookie) => {
+                (cookie.len() as u16).encode(enc)?;
+                enc.append(cookie)
+            }
+            ClientExtension::KeyShare(ref r) => r.encode(enc),
+            ClientExtension::Unknown(_, data) => enc.append(data),
         }
     }
 
     fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
         let ty = ExtensionType::decode(dec)?;
         let len = Self::decode_len(dec)?;
-        let mut sub = dec.sub(len)?;
+        let data = dec.take(len)?;
+        let mut sub = Decoder::new(data);
 
         match ty {
+            ExtensionType::ServerName => {
+                decode_server_name(&mut sub).map(ClientExtension::ServerName)
+            }
+            ExtensionType::MaxFragmentLength => {
+                MaxFragmentLength::decode(&mut sub).map(ClientExtension::from)
+            }
+            ExtensionType::SupportedGroups => {
+                NamedGroups::decode(&mut sub).map(ClientExtension::from)
+            }
             ExtensionType::SignatureAlgorithms => {
                 SignatureSchemes::decode(&mut sub).map(ClientExtension::from)
             }
+            ExtensionType::Padding => Some(ClientExtension::Padding(data.len())),
+            ExtensionType::RecordSizeLimit => {
+                u16::decode(&mut sub).map(ClientExtension::RecordSizeLimit)
+            }
             ExtensionType::SupportedVersions => {
                 ProtocolVersions::decode(&mut sub).map(ClientExtension::from)
             }
-            // TODO: Handle unknown client extension type
-            ExtensionType::Unknown(_) => unimplemented!(),
+            ExtensionType::Cookie => {
+                let len = u16::decode(&mut sub)?;
+                sub.take(usize::from(len)).map(ClientExtension::Cookie)
+            }
+            ExtensionType::KeyShare => KeyShareEntries::decode(&mut sub).map(ClientExtension::from),
+            _ => Some(ClientExtension::Unknown(ty, data)),
         }
     }
 }
@@ -74,6 +226,24 @@ impl<'a> CodecSized<'a> for ClientExtension<'a> {
     }
 }
 
+impl<'a> From<MaxFragmentLength> for ClientExtension<'a> {
+    fn from(data: MaxFragmentLength) -> Self {
+        ClientExtension::MaxFragmentLength(data)
+    }
+}
+
+impl<'a> From<Array<'a, NamedGroup>> for ClientExtension<'a> {
+    fn from(data: Array<'a, NamedGroup>) -> Self {
+        ClientExtension::from(NamedGroups::from(data))
+    }
+}
+
+impl<'a> From<NamedGroups<'a>> for ClientExtension<'a> {
+    fn from(data: NamedGroups<'a>) -> Self {
+        ClientExtension::SupportedGroups(data)
+    }
+}
+
 impl<'a> From<Array<'a, SignatureScheme>> for ClientExtension<'a> {
     fn from(data: Array<'a, SignatureScheme>) -> Self {
         ClientExtension::from(SignatureSchemes::from(data))
@@ -98,14 +268,109 @@ impl<'a> From<ProtocolVersions<'a>> for ClientExtension<'a> {
     }
 }
 
+impl<'a> From<KeyShareEntries<'a>> for ClientExtension<'a> {
+    fn from(data: KeyShareEntries<'a>) -> Self {
+        ClientExtension::KeyShare(data)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::handshake::extension::{ProtocolVersions, SignatureSchemes};
+    use crate::handshake::extension::{KeyShareEntry, ProtocolVersions, SignatureSchemes};
 
     mod encode {
         use super::*;
 
+        #[test]
+        fn supported_groups() {
+            let ext = ClientExtension::from(arr![NamedGroup::X25519, NamedGroup::Secp256r1]);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 10);
+            assert_eq!(enc.bytes(), [0x00, 0x0a, 0, 6, 0, 4, 0, 0x1d, 0, 0x17]);
+        }
+
+        #[test]
+        fn key_share() {
+            let ext = ClientExtension::from(KeyShareEntries::from(arr![KeyShareEntry {
+                group: NamedGroup::X25519,
+                payload: &[1, 2, 3],
+            }]));
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 13);
+            assert_eq!(
+                enc.bytes(),
+                [0x00, 0x33, 0, 9, 0, 7, 0, 0x1d, 0, 3, 1, 2, 3]
+            );
+        }
+
+        #[test]
+        fn unknown() {
+            let ext = ClientExtension::Unknown(ExtensionType::Unknown(0xff01), &[0]);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 5);
+            assert_eq!(enc.bytes(), [0xff, 0x01, 0, 1, 0]);
+        }
+
+        #[test]
+        fn server_name() {
+            let ext = ClientExtension::ServerName("a.com");
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 14);
+            assert_eq!(
+                enc.bytes(),
+                [0x00, 0x00, 0, 10, 0, 8, 0, 0, 5, b'a', b'.', b'c', b'o', b'm']
+            );
+        }
+
+        #[test]
+        fn max_fragment_length() {
+            let ext = ClientExtension::from(MaxFragmentLength::Max1024);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 5);
+            assert_eq!(enc.bytes(), [0x00, 0x01, 0, 1, 2]);
+        }
+
+        #[test]
+        fn record_size_limit() {
+            let ext = ClientExtension::RecordSizeLimit(0x4001);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 6);
+            assert_eq!(enc.bytes(), [0x00, 0x1c, 0, 2, 0x40, 0x01]);
+        }
+
+        #[test]
+        fn cookie() {
+            let ext = ClientExtension::Cookie(&[7, 8, 9]);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 9);
+            assert_eq!(enc.bytes(), [0x00, 0x2c, 0, 5, 0, 3, 7, 8, 9]);
+        }
+
+        #[test]
+        fn padding() {
+            let ext = ClientExtension::Padding(3);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 7);
+            assert_eq!(enc.bytes(), [0x00, 0x15, 0, 3, 0, 0, 0]);
+        }
+
         #[test]
         fn empty_signature_algorithms() {
             let ext = ClientExtension::from(SignatureSchemes::empty());
@@ -174,6 +439,126 @@ mod tests {
     mod decode {
         use super::*;
 
+        #[test]
+        fn supported_groups() {
+            let bytes = [0x00, 0x0a, 0, 6, 0, 4, 0, 0x1d, 0, 0x17];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::from(arr![NamedGroup::X25519, NamedGroup::Secp256r1]),
+            );
+        }
+
+        #[test]
+        fn key_share() {
+            let bytes = [0x00, 0x33, 0, 9, 0, 7, 0, 0x1d, 0, 3, 1, 2, 3];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::from(KeyShareEntries::from(arr![KeyShareEntry {
+                    group: NamedGroup::X25519,
+                    payload: &[1, 2, 3],
+                }])),
+            );
+        }
+
+        #[test]
+        fn unknown() {
+            let bytes = [0xff, 0x01, 0, 3, 1, 2, 3];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::Unknown(ExtensionType::Unknown(0xff01), &[1, 2, 3]),
+            );
+        }
+
+        #[test]
+        fn server_name() {
+            let bytes = [
+                0x00, 0x00, 0, 10, 0, 8, 0, 0, 5, b'a', b'.', b'c', b'o', b'm',
+            ];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::ServerName("a.com"),
+            );
+        }
+
+        #[test]
+        fn server_name_with_other_types() {
+            let bytes = [0x00, 0x00, 0, 11, 0, 9, 7, 0, 1, 0xff, 0, 0, 2, b'a', b'b'];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::ServerName("ab"),
+            );
+        }
+
+        #[test]
+        fn invalid_server_name() {
+            // Duplicate host names.
+            let bytes = [0x00, 0x00, 0, 10, 0, 8, 0, 0, 1, b'a', 0, 0, 1, b'b'];
+            assert!(ClientExtension::decode(&mut Decoder::new(&bytes)).is_none());
+
+            // No host name.
+            let bytes = [0x00, 0x00, 0, 6, 0, 4, 7, 0, 1, 0xff];
+            assert!(ClientExtension::decode(&mut Decoder::new(&bytes)).is_none());
+
+            // Not UTF-8.
+            let bytes = [0x00, 0x00, 0, 6, 0, 4, 0, 0, 1, 0xff];
+            assert!(ClientExtension::decode(&mut Decoder::new(&bytes)).is_none());
+        }
+
+        #[test]
+        fn max_fragment_length() {
+            let bytes = [0x00, 0x01, 0, 1, 4];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::from(MaxFragmentLength::Max4096),
+            );
+        }
+
+        #[test]
+        fn record_size_limit() {
+            let bytes = [0x00, 0x1c, 0, 2, 0x02, 0x00];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::RecordSizeLimit(512),
+            );
+        }
+
+        #[test]
+        fn cookie() {
+            let bytes = [0x00, 0x2c, 0, 5, 0, 3, 7, 8, 9];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::Cookie(&[7, 8, 9]),
+            );
+        }
+
+        #[test]
+        fn padding() {
+            let bytes = [0x00, 0x15, 0, 2, 0, 0];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ClientExtension::decode(&mut dec).unwrap(),
+                ClientExtension::Padding(2),
+            );
+            assert!(dec.is_complete());
+        }
+
         #[test]
         fn empty_signature_algorithms() {
             let bytes = [0x00, 0x0d, 0, 2, 0, 0];
@@ -1,31 +1,54 @@
 use crate::{
     codec::{Codec, CodecSized, Decoder, Encoder, HeaderSize},
     error::Result as TlsResult,
-    handshake::enums::{ExtensionType, ProtocolVersion},
+    handshake::{
+        enums::{ExtensionType, MaxFragmentLength, NamedGroup, ProtocolVersion},
+        extension::KeyShareEntry,
+    },
 };
 
-// TODO: Add unknown server extension
-#[derive(Debug, PartialEq)]
-pub enum ServerExtension {
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerExtension<'a> {
+    MaxFragmentLength(MaxFragmentLength),
+    RecordSizeLimit(u16),
     SupportedVersions(ProtocolVersion),
+    KeyShare(KeyShareEntry<'a>),
+    /// Group the client should send a key share for, in a HelloRetryRequest.
+    HelloRetryKeyShare(NamedGroup),
+    /// Cookie the client must echo, in a HelloRetryRequest.
+    Cookie(&'a [u8]),
+    Unknown(ExtensionType, &'a [u8]),
 }
 
-impl ServerExtension {
-    fn ty(&self) -> ExtensionType {
+impl<'a> ServerExtension<'a> {
+    pub fn ty(&self) -> ExtensionType {
         match self {
+            ServerExtension::MaxFragmentLength(_) => ExtensionType::MaxFragmentLength,
+            ServerExtension::RecordSizeLimit(_) => ExtensionType::RecordSizeLimit,
             ServerExtension::SupportedVersions(_) => ExtensionType::SupportedVersions,
+            ServerExtension::KeyShare(_) | ServerExtension::HelloRetryKeyShare(_) => {
+                ExtensionType::KeyShare
+            }
+            ServerExtension::Cookie(_) => ExtensionType::Cookie,
+            ServerExtension::Unknown(ty, _) => *ty,
         }
     }
 
     // TODO: Document this.
     fn ext_size(&self) -> usize {
         match self {
+            ServerExtension::MaxFragmentLength(ref r) => r.data_size(),
+            ServerExtension::RecordSizeLimit(ref r) => r.data_size(),
             ServerExtension::SupportedVersions(ref r) => r.data_size(),
+            ServerExtension::KeyShare(ref r) => r.data_size(),
+            ServerExtension::HelloRetryKeyShare(ref r) => r.data_size(),
+            ServerExtension::Cookie(cookie) => (cookie.len() as u16).data_size() + cookie.len(),
+            ServerExtension::Unknown(_, data) => data.len(),
         }
     }
 }
 
-impl<'a> Codec<'a> for ServerExtension {
+impl<'a> Codec<'a> for ServerExtension<'a> {
     fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
         self.ty().encode(enc)?;
 
@@ -33,26 +56,50 @@ impl<'a> Codec<'a> for ServerExtension {
         (self.ext_size() as u16).encode(enc)?;
 
         match self {
+            ServerExtension::MaxFragmentLength(ref r) => r.encode(enc),
+            ServerExtension::RecordSizeLimit(ref r) => r.encode(enc),
             ServerExtension::SupportedVersions(ref r) => r.encode(enc),
+            ServerExtension::KeyShare(ref r) => r.encode(enc),
+            ServerExtension::HelloRetryKeyShare(ref r) => r.encode(enc),
+            ServerExtension::Cookie(cookie) => {
+                (cookie.len() as u16).encode(enc)?;
+                enc.append(cookie)
+            }
+            ServerExtension::Unknown(_, data) => enc.append(data),
         }
     }
 
     fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
         let ty = ExtensionType::decode(dec)?;
         let len = Self::decode_len(dec)?;
-        let mut sub = dec.sub(len)?;
+        let data = dec.take(len)?;
+        let mut sub = Decoder::new(data);
 
         match ty {
+            ExtensionType::MaxFragmentLength => {
+                MaxFragmentLength::decode(&mut sub).map(ServerExtension::from)
+            }
+            ExtensionType::RecordSizeLimit => {
+                u16::decode(&mut sub).map(ServerExtension::RecordSizeLimit)
+            }
             ExtensionType::SupportedVersions => {
                 ProtocolVersion::decode(&mut sub).map(ServerExtension::from)
             }
-            // TODO: Handle unknown server extension type
-            ExtensionType::Unknown(_) | _ => unimplemented!(),
+            ExtensionType::Cookie => {
+                let len = u16::decode(&mut sub)?;
+                sub.take(usize::from(len)).map(ServerExtension::Cookie)
+            }
+            // A HelloRetryRequest carries only the selected group.
+            ExtensionType::KeyShare if len == 2 => {
+                NamedGroup::decode(&mut sub).map(ServerExtension::HelloRetryKeyShare)
+            }
+            ExtensionType::KeyShare => KeyShareEntry::decode(&mut sub).map(ServerExtension::from),
+            _ => Some(ServerExtension::Unknown(ty, data)),
         }
     }
 }
 
-impl<'a> CodecSized<'a> for ServerExtension {
+impl<'a> CodecSized<'a> for ServerExtension<'a> {
     const HEADER_SIZE: HeaderSize = HeaderSize::U16;
 
     fn data_size(&self) -> usize {
@@ -60,12 +107,24 @@ impl<'a> CodecSized<'a> for ServerExtension {
     }
 }
 
-impl From<ProtocolVersion> for ServerExtension {
+impl<'a> From<MaxFragmentLength> for ServerExtension<'a> {
+    fn from(data: MaxFragmentLength) -> Self {
+        ServerExtension::MaxFragmentLength(data)
+    }
+}
+
+impl<'a> From<ProtocolVersion> for ServerExtension<'a> {
     fn from(data: ProtocolVersion) -> Self {
         ServerExtension::SupportedVersions(data)
     }
 }
 
+impl<'a> From<KeyShareEntry<'a>> for ServerExtension<'a> {
+    fn from(data: KeyShareEntry<'a>) -> Self {
+        ServerExtension::KeyShare(data)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,6 +132,59 @@ mod tests {
     mod encode {
         use super::*;
 
+        #[test]
+        fn key_share() {
+            let ext = ServerExtension::from(KeyShareEntry {
+                group: NamedGroup::Secp256r1,
+                payload: &[1, 2],
+            });
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 10);
+            assert_eq!(enc.bytes(), [0x00, 0x33, 0, 6, 0, 0x17, 0, 2, 1, 2]);
+        }
+
+        #[test]
+        fn hello_retry_key_share() {
+            let ext = ServerExtension::HelloRetryKeyShare(NamedGroup::X25519);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 6);
+            assert_eq!(enc.bytes(), [0x00, 0x33, 0, 2, 0, 0x1d]);
+        }
+
+        #[test]
+        fn cookie() {
+            let ext = ServerExtension::Cookie(&[7, 8, 9]);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 9);
+            assert_eq!(enc.bytes(), [0x00, 0x2c, 0, 5, 0, 3, 7, 8, 9]);
+        }
+
+        #[test]
+        fn max_fragment_length() {
+            let ext = ServerExtension::from(MaxFragmentLength::Max512);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 5);
+            assert_eq!(enc.bytes(), [0x00, 0x01, 0, 1, 1]);
+        }
+
+        #[test]
+        fn record_size_limit() {
+            let ext = ServerExtension::RecordSizeLimit(1024);
+            let mut enc = Encoder::new(vec![]);
+            ext.encode(&mut enc).unwrap();
+
+            assert_eq!(ext.data_size(), 6);
+            assert_eq!(enc.bytes(), [0x00, 0x1c, 0, 2, 0x04, 0x00]);
+        }
+
         #[test]
         fn supported_versions() {
             let ext = ServerExtension::from(ProtocolVersion::TLSv1_2);
@@ -87,6 +199,75 @@ mod tests {
     mod decode {
         use super::*;
 
+        #[test]
+        fn key_share() {
+            let bytes = [0x00, 0x33, 0, 6, 0, 0x17, 0, 2, 1, 2];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ServerExtension::decode(&mut dec).unwrap(),
+                ServerExtension::from(KeyShareEntry {
+                    group: NamedGroup::Secp256r1,
+                    payload: &[1, 2],
+                }),
+            );
+        }
+
+        #[test]
+        fn hello_retry_key_share() {
+            let bytes = [0x00, 0x33, 0, 2, 0, 0x1d];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ServerExtension::decode(&mut dec).unwrap(),
+                ServerExtension::HelloRetryKeyShare(NamedGroup::X25519),
+            );
+        }
+
+        #[test]
+        fn cookie() {
+            let bytes = [0x00, 0x2c, 0, 5, 0, 3, 7, 8, 9];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ServerExtension::decode(&mut dec).unwrap(),
+                ServerExtension::Cookie(&[7, 8, 9]),
+            );
+        }
+
+        #[test]
+        fn unknown() {
+            let bytes = [0x00, 0x0d, 0, 1, 9];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ServerExtension::decode(&mut dec).unwrap(),
+                ServerExtension::Unknown(ExtensionType::SignatureAlgorithms, &[9]),
+            );
+        }
+
+        #[test]
+        fn max_fragment_length() {
+            let bytes = [0x00, 0x01, 0, 1, 3];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ServerExtension::decode(&mut dec).unwrap(),
+                ServerExtension::from(MaxFragmentLength::Max2048),
+            );
+        }
+
+        #[test]
+        fn record_size_limit() {
+            let bytes = [0x00, 0x1c, 0, 2, 0x40, 0x01];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                ServerExtension::decode(&mut dec).unwrap(),
+                ServerExtension::RecordSizeLimit(0x4001),
+            );
+        }
+
         #[test]
         fn supported_versions() {
             let bytes = [0x00, 0x2b, 0, 2, 3, 3];
@@ -0,0 +1,115 @@
+use crate::handshake::enums::{
+    CipherSuite, ExtensionType, NamedGroup, ProtocolVersion, SignatureScheme,
+};
+
+/// Wire values that can be GREASE (RFC 8701, section 2).
+pub(crate) trait GreaseValue: Copy {
+    fn is_grease(self) -> bool;
+}
+
+impl GreaseValue for u8 {
+    fn is_grease(self) -> bool {
+        false
+    }
+}
+
+impl GreaseValue for u16 {
+    fn is_grease(self) -> bool {
+        self & 0x0f0f == 0x0a0a && self >> 8 == self & 0xff
+    }
+}
+
+/// GREASE values a client sends to check peers tolerate unknown values (RFC 8701).
+///
+/// When set on a ClientHello, a value is put at the front of the cipher suites, supported groups,
+/// supported versions and signature algorithms, and an empty and a single byte extension are put
+/// either side of the other extensions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grease {
+    pub(crate) cipher_suite: CipherSuite,
+    pub(crate) extensions: [ExtensionType; 2],
+    pub(crate) group: NamedGroup,
+    pub(crate) version: ProtocolVersion,
+    pub(crate) signature_scheme: SignatureScheme,
+}
+
+impl Grease {
+    /// Picks the GREASE values from `seed`, which should be freshly random for each connection.
+    pub fn new(seed: [u8; 6]) -> Self {
+        let first = grease_value(seed[1]);
+        let mut second = grease_value(seed[2]);
+        // The same extension can't be sent twice.
+        if second == first {
+            second ^= 0x1010;
+        }
+
+        Grease {
+            cipher_suite: grease_value(seed[0]).into(),
+            extensions: [first.into(), second.into()],
+            group: grease_value(seed[3]).into(),
+            version: grease_value(seed[4]).into(),
+            signature_scheme: grease_value(seed[5]).into(),
+        }
+    }
+}
+
+/// One of the 16 two byte GREASE values, picked by the high nibble of `byte`.
+fn grease_value(byte: u8) -> u16 {
+    let byte = u16::from(byte & 0xf0 | 0x0a);
+    byte << 8 | byte
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grease_values() {
+        for byte in 0..=255 {
+            assert!(grease_value(byte).is_grease());
+        }
+        assert_eq!(grease_value(0x00), 0x0a0a);
+        assert_eq!(grease_value(0xf3), 0xfafa);
+    }
+
+    #[test]
+    fn not_grease() {
+        assert!(!0x0a1a_u16.is_grease());
+        assert!(!0x0b0b_u16.is_grease());
+        assert!(!0x1301_u16.is_grease());
+        assert!(!0x0a_u8.is_grease());
+    }
+
+    #[test]
+    fn enums() {
+        assert!(CipherSuite::from(0x2a2a).is_grease());
+        assert!(ExtensionType::from(0xdada).is_grease());
+        assert!(!CipherSuite::TlsAes128GcmSha256.is_grease());
+        assert!(!NamedGroup::X25519.is_grease());
+    }
+
+    #[test]
+    fn distinct_extensions() {
+        let grease = Grease::new([0, 0x30, 0x30, 0, 0, 0]);
+
+        assert_ne!(grease.extensions[0], grease.extensions[1]);
+        assert!(grease.extensions.iter().all(ExtensionType::is_grease));
+    }
+
+    #[test]
+    fn seeded() {
+        let grease = Grease::new([0x00, 0x10, 0x20, 0x30, 0x40, 0x50]);
+
+        assert_eq!(grease.cipher_suite, CipherSuite::Unknown(0x0a0a));
+        assert_eq!(
+            grease.extensions,
+            [
+                ExtensionType::Unknown(0x1a1a),
+                ExtensionType::Unknown(0x2a2a)
+            ]
+        );
+        assert_eq!(grease.group, NamedGroup::Unknown(0x3a3a));
+        assert_eq!(grease.version, ProtocolVersion::Unknown(0x4a4a));
+        assert_eq!(grease.signature_scheme, SignatureScheme::Unknown(0x5a5a));
+    }
+}
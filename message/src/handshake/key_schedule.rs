@@ -0,0 +1,307 @@
+use crate::{
+    crypto::{Digest, Hash, MAX_DIGEST_LEN, NONCE_LEN},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::enums::HashAlgorithm,
+};
+use core::convert::TryFrom;
+
+/// Largest block of any supported hash function (SHA-384 and SHA-512).
+const MAX_BLOCK_LEN: usize = 128;
+
+const LABEL_PREFIX: &[u8] = b"tls13 ";
+
+/// Key schedule of a full handshake, without a pre-shared key (RFC 8446, section 7.1).
+///
+/// HKDF (RFC 5869) is computed with HMAC over the crypto provider's hash, so the provider only
+/// needs to supply the hash function of the cipher suite.
+pub struct KeySchedule<H: Hash> {
+    /// Context that is reset before each use, for hashing with the cipher suite's hash.
+    hash: H,
+    algorithm: HashAlgorithm,
+    /// Early, handshake or master secret, depending on how far the handshake is.
+    secret: Digest,
+}
+
+impl<H: Hash> KeySchedule<H> {
+    /// Starts at the early secret, where `hash` is a context for `algorithm`.
+    pub fn new(hash: H, algorithm: HashAlgorithm) -> Self {
+        let mut schedule = Self {
+            hash,
+            algorithm,
+            secret: Digest::empty(),
+        };
+        schedule.secret = schedule.extract(&[], schedule.zeros());
+        schedule
+    }
+
+    /// Moves on to the handshake secret, with the shared secret of the key exchange.
+    pub fn handshake(&mut self, shared_secret: &[u8]) -> TlsResult<()> {
+        self.advance(shared_secret)
+    }
+
+    /// Moves on to the master secret, once the handshake traffic secrets are derived.
+    pub fn master(&mut self) -> TlsResult<()> {
+        self.advance(self.zeros())
+    }
+
+    /// Derives a secret, such as a traffic secret, from the current secret and the transcript
+    /// hash of the messages it covers.
+    pub fn derive_secret(&self, label: &[u8], transcript_hash: &Digest) -> TlsResult<Digest> {
+        self.expand_secret(&self.secret, label, transcript_hash.as_ref())
+    }
+
+    /// Key to protect records with, from a traffic secret (RFC 8446, section 7.3). The key fills
+    /// `out`, which must be as long as the AEAD's key.
+    pub fn traffic_key(&self, secret: &Digest, out: &mut [u8]) -> TlsResult<()> {
+        self.expand_label(secret.as_ref(), b"key", &[], out)
+    }
+
+    /// IV to protect records with, from a traffic secret.
+    pub fn traffic_iv(&self, secret: &Digest) -> TlsResult<[u8; NONCE_LEN]> {
+        let mut iv = [0; NONCE_LEN];
+        self.expand_label(secret.as_ref(), b"iv", &[], &mut iv)?;
+        Ok(iv)
+    }
+
+    /// Contents of the Finished message sent by the owner of the handshake traffic secret
+    /// `secret`, given the transcript hash of the messages before it (RFC 8446, section 4.4.4).
+    pub fn finished_verify_data(
+        &self,
+        secret: &Digest,
+        transcript_hash: &Digest,
+    ) -> TlsResult<Digest> {
+        let finished_key = self.expand_secret(secret, b"finished", &[])?;
+        Ok(self.hmac(finished_key.as_ref(), &[transcript_hash.as_ref()]))
+    }
+
+    /// Checks the contents of a Finished message the peer sent, failing with
+    /// [`TlsError::DecryptError`] if they don't match.
+    pub fn verify_finished(
+        &self,
+        secret: &Digest,
+        transcript_hash: &Digest,
+        verify_data: &[u8],
+    ) -> TlsResult<()> {
+        let expected = self.finished_verify_data(secret, transcript_hash)?;
+        if expected.len() != verify_data.len() {
+            return Err(TlsError::DecodeError);
+        }
+
+        // Compared in constant time, so the position of a mismatch isn't revealed.
+        let diff = expected
+            .as_ref()
+            .iter()
+            .zip(verify_data)
+            .fold(0, |diff, (a, b)| diff | (a ^ b));
+        if diff != 0 {
+            return Err(TlsError::DecryptError);
+        }
+        Ok(())
+    }
+
+    /// Extracts the next secret, salted with one derived from the current secret.
+    fn advance(&mut self, ikm: &[u8]) -> TlsResult<()> {
+        let salt = self.derive_secret(b"derived", &self.empty_hash())?;
+        self.secret = self.extract(salt.as_ref(), ikm);
+        Ok(())
+    }
+
+    /// HKDF-Expand-Label to the length of the hash.
+    fn expand_secret(&self, secret: &Digest, label: &[u8], context: &[u8]) -> TlsResult<Digest> {
+        let mut out = [0; MAX_DIGEST_LEN];
+        let out = &mut out[..self.algorithm.output_len()];
+        self.expand_label(secret.as_ref(), label, context, out)?;
+        Digest::try_from(&*out)
+    }
+
+    /// HKDF-Expand-Label, filling `out`.
+    fn expand_label(
+        &self,
+        secret: &[u8],
+        label: &[u8],
+        context: &[u8],
+        out: &mut [u8],
+    ) -> TlsResult<()> {
+        let label_len = LABEL_PREFIX.len() + label.len();
+        if out.len() > usize::from(u16::MAX) || label_len > 255 || context.len() > 255 {
+            return Err(TlsError::InternalError("HKDF label too long"));
+        }
+
+        // The HkdfLabel struct, encoded in parts.
+        let len = (out.len() as u16).to_be_bytes();
+        let info = [
+            &len[..],
+            &[label_len as u8],
+            LABEL_PREFIX,
+            label,
+            &[context.len() as u8],
+            context,
+        ];
+        self.expand(secret, &info, out)
+    }
+
+    /// HKDF-Expand, with `info` given in parts.
+    fn expand(&self, prk: &[u8], info: &[&[u8]], out: &mut [u8]) -> TlsResult<()> {
+        let hash_len = self.algorithm.output_len();
+        if out.len() > 255 * hash_len {
+            return Err(TlsError::InternalError("HKDF output too long"));
+        }
+
+        let mut block = Digest::empty();
+        for (i, chunk) in out.chunks_mut(hash_len).enumerate() {
+            let counter = [i as u8 + 1];
+            let mut data = [&[][..]; 8];
+            data[0] = block.as_ref();
+            data[1..=info.len()].copy_from_slice(info);
+            data[info.len() + 1] = &counter;
+
+            block = self.hmac(prk, &data[..info.len() + 2]);
+            chunk.copy_from_slice(&block.as_ref()[..chunk.len()]);
+        }
+        Ok(())
+    }
+
+    /// HKDF-Extract.
+    fn extract(&self, salt: &[u8], ikm: &[u8]) -> Digest {
+        self.hmac(salt, &[ikm])
+    }
+
+    /// HMAC (RFC 2104) of the concatenation of `data`.
+    fn hmac(&self, key: &[u8], data: &[&[u8]]) -> Digest {
+        let block_len = self.algorithm.block_len();
+        let mut block = [0; MAX_BLOCK_LEN];
+        if key.len() > block_len {
+            let digest = self.digest(&[key]);
+            block[..digest.len()].copy_from_slice(digest.as_ref());
+        } else {
+            block[..key.len()].copy_from_slice(key);
+        }
+        let block = &mut block[..block_len];
+
+        block.iter_mut().for_each(|b| *b ^= 0x36);
+        let mut inner = self.new_hash();
+        inner.update(block);
+        data.iter().for_each(|data| inner.update(data));
+        let inner = inner.finish();
+
+        // Flips the inner pad to the outer one.
+        block.iter_mut().for_each(|b| *b ^= 0x36 ^ 0x5c);
+        self.digest(&[block, inner.as_ref()])
+    }
+
+    fn digest(&self, data: &[&[u8]]) -> Digest {
+        let mut hash = self.new_hash();
+        data.iter().for_each(|data| hash.update(data));
+        hash.finish()
+    }
+
+    /// Transcript hash of no messages.
+    fn empty_hash(&self) -> Digest {
+        self.digest(&[])
+    }
+
+    fn new_hash(&self) -> H {
+        let mut hash = self.hash.clone();
+        hash.reset();
+        hash
+    }
+
+    fn zeros(&self) -> &'static [u8] {
+        &[0; MAX_DIGEST_LEN][..self.algorithm.output_len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Sha256;
+    use ring::{digest, hkdf, hmac};
+
+    fn schedule() -> KeySchedule<Sha256> {
+        KeySchedule::new(Sha256::default(), HashAlgorithm::Sha256)
+    }
+
+    fn hex(s: &str) -> std::vec::Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn hmac() {
+        let schedule = schedule();
+        for key in &[&[7; 32][..], &[8; 100][..]] {
+            let expected = hmac::sign(&hmac::SigningKey::new(&digest::SHA256, key), b"message");
+
+            assert_eq!(
+                schedule.hmac(key, &[b"mes", b"sage"]).as_ref(),
+                expected.as_ref()
+            );
+        }
+    }
+
+    #[test]
+    fn expand_label() {
+        let schedule = schedule();
+        let mut out = [0; 80];
+        schedule
+            .expand_label(&[3; 32], b"key", &[4; 5], &mut out)
+            .unwrap();
+
+        let mut expected = [0; 80];
+        let info = [&[0, 80, 9][..], b"tls13 key", &[5], &[4; 5]].concat();
+        hkdf::expand(
+            &hmac::SigningKey::new(&digest::SHA256, &[3; 32]),
+            &info,
+            &mut expected,
+        );
+        assert_eq!(out[..], expected[..]);
+    }
+
+    /// Secrets of the simple 1-RTT handshake trace (RFC 8448, section 3).
+    #[test]
+    fn secrets() {
+        let mut schedule = schedule();
+        assert_eq!(
+            schedule.secret.as_ref(),
+            &hex("33ad0a1c607ec03b09e6cd9893680ce210adf300aa1f2660e1b22e10f170f92a")[..]
+        );
+
+        schedule
+            .handshake(&hex(
+                "8bd4054fb55b9d63fdfbacf9f04b9f0d35e6d63f537563efd46272900f89492d",
+            ))
+            .unwrap();
+        assert_eq!(
+            schedule.secret.as_ref(),
+            &hex("1dc826e93606aa6fdc0aadc12f741b01046aa6b99f691ed221a9f0ca043fbeac")[..]
+        );
+
+        schedule.master().unwrap();
+        assert_eq!(
+            schedule.secret.as_ref(),
+            &hex("18df06843d13a08bf2a449844c5f8a478001bc4d4c627984d5a41da8d0402919")[..]
+        );
+    }
+
+    #[test]
+    fn finished() {
+        let schedule = schedule();
+        let secret = Digest::try_from(&[1; 32][..]).unwrap();
+        let hash = Digest::try_from(&[2; 32][..]).unwrap();
+        let verify_data = schedule.finished_verify_data(&secret, &hash).unwrap();
+
+        assert!(schedule
+            .verify_finished(&secret, &hash, verify_data.as_ref())
+            .is_ok());
+        assert!(matches!(
+            schedule.verify_finished(&secret, &hash, &[0; 32]),
+            Err(TlsError::DecryptError)
+        ));
+        assert!(matches!(
+            schedule.verify_finished(&secret, &hash, &verify_data.as_ref()[..31]),
+            Err(TlsError::DecodeError)
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{
+    codec::{Codec, CodecSized, Decoder, Encoder, HeaderSize},
+    error::Result as TlsResult,
+    handshake::{
+        certificate::CertificatePayload, certificate_request::CertificateRequestPayload,
+        certificate_verify::CertificateVerifyPayload, client::ClientHelloPayload,
+        enums::HandshakeType, server::ServerHelloPayload,
+    },
+};
+
+#[derive(Debug, PartialEq)]
+pub struct HandshakeMessage<'a> {
+    pub ty: HandshakeType,
+    pub payload: HandshakePayload<'a>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum HandshakePayload<'a> {
+    ClientHello(ClientHelloPayload<'a>),
+    ServerHello(ServerHelloPayload<'a>),
+    Certificate(CertificatePayload<'a>),
+    CertificateRequest(CertificateRequestPayload<'a>),
+    CertificateVerify(CertificateVerifyPayload<'a>),
+    /// Payload of a message that is not parsed any further.
+    Opaque(&'a [u8]),
+}
+
+impl<'a> Codec<'a> for HandshakeMessage<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        self.ty.encode(enc)?;
+        self.payload.encode_len(enc)?;
+        self.payload.encode(enc)
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        let ty = HandshakeType::decode(dec)?;
+        let len = HandshakePayload::decode_len(dec)?;
+        let mut sub = dec.sub(len)?;
+
+        let payload = match ty {
+            HandshakeType::ClientHello => {
+                ClientHelloPayload::decode(&mut sub).map(HandshakePayload::ClientHello)?
+            }
+            HandshakeType::ServerHello => {
+                ServerHelloPayload::decode(&mut sub).map(HandshakePayload::ServerHello)?
+            }
+            HandshakeType::Certificate => {
+                CertificatePayload::decode(&mut sub).map(HandshakePayload::Certificate)?
+            }
+            HandshakeType::CertificateRequest => CertificateRequestPayload::decode(&mut sub)
+                .map(HandshakePayload::CertificateRequest)?,
+            HandshakeType::CertificateVerify => CertificateVerifyPayload::decode(&mut sub)
+                .map(HandshakePayload::CertificateVerify)?,
+            _ => HandshakePayload::Opaque(sub.take(len)?),
+        };
+
+        if !sub.is_complete() {
+            return None;
+        }
+        Some(Self { ty, payload })
+    }
+}
+
+impl<'a> CodecSized<'a> for HandshakeMessage<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::Zero;
+
+    fn data_size(&self) -> usize {
+        self.ty.data_size() + HandshakePayload::HEADER_SIZE.size() + self.payload.data_size()
+    }
+}
+
+impl<'a> Codec<'a> for HandshakePayload<'a> {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        match self {
+            HandshakePayload::ClientHello(ref p) => p.encode(enc),
+            HandshakePayload::ServerHello(ref p) => p.encode(enc),
+            HandshakePayload::Certificate(ref p) => p.encode(enc),
+            HandshakePayload::CertificateRequest(ref p) => p.encode(enc),
+            HandshakePayload::CertificateVerify(ref p) => p.encode(enc),
+            HandshakePayload::Opaque(b) => enc.append(b),
+        }
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        dec.take(dec.remaining()).map(HandshakePayload::Opaque)
+    }
+}
+
+impl<'a> CodecSized<'a> for HandshakePayload<'a> {
+    const HEADER_SIZE: HeaderSize = HeaderSize::U24;
+
+    fn data_size(&self) -> usize {
+        match self {
+            HandshakePayload::ClientHello(ref p) => p.data_size(),
+            HandshakePayload::ServerHello(ref p) => p.data_size(),
+            HandshakePayload::Certificate(ref p) => p.data_size(),
+            HandshakePayload::CertificateRequest(ref p) => p.data_size(),
+            HandshakePayload::CertificateVerify(ref p) => p.data_size(),
+            HandshakePayload::Opaque(b) => b.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    mod encode {
+        use super::*;
+
+        #[test]
+        fn opaque() {
+            let msg = HandshakeMessage {
+                ty: HandshakeType::Finished,
+                payload: HandshakePayload::Opaque(&[1, 2, 3]),
+            };
+            let mut enc = Encoder::new(vec![]);
+            msg.encode(&mut enc).unwrap();
+
+            assert_eq!(msg.data_size(), 7);
+            assert_eq!(enc.bytes(), [0x14, 0, 0, 3, 1, 2, 3]);
+        }
+
+        #[test]
+        fn client_hello() {
+            let msg = HandshakeMessage {
+                ty: HandshakeType::ClientHello,
+                payload: HandshakePayload::ClientHello(ClientHelloPayload::default()),
+            };
+            let mut enc = Encoder::new(vec![]);
+            msg.encode(&mut enc).unwrap();
+
+            assert_eq!(msg.data_size(), enc.bytes().len());
+            assert_eq!(enc.bytes()[..4], [0x01, 0, 0, 38]);
+        }
+    }
+
+    mod decode {
+        use super::*;
+
+        #[test]
+        fn opaque() {
+            let bytes = [0x14, 0, 0, 3, 1, 2, 3];
+            let mut dec = Decoder::new(&bytes);
+
+            assert_eq!(
+                HandshakeMessage::decode(&mut dec).unwrap(),
+                HandshakeMessage {
+                    ty: HandshakeType::Finished,
+                    payload: HandshakePayload::Opaque(&[1, 2, 3]),
+                }
+            );
+            assert!(dec.is_complete());
+        }
+
+        #[test]
+        fn not_enough_bytes() {
+            let bytes = [0x14, 0, 0, 3, 1, 2];
+            let mut dec = Decoder::new(&bytes);
+
+            assert!(HandshakeMessage::decode(&mut dec).is_none());
+        }
+
+        #[test]
+        fn client_hello_trailing_bytes() {
+            let mut enc = Encoder::new(vec![]);
+            enc.append([0x01, 0, 0, 39]).unwrap();
+            ClientHelloPayload::default().encode(&mut enc).unwrap();
+            enc.push(0).unwrap();
+
+            let mut dec = Decoder::new(enc.bytes());
+            assert!(HandshakeMessage::decode(&mut dec).is_none());
+        }
+    }
+}
@@ -3,9 +3,38 @@ use crate::{
     error::Result as TlsResult,
 };
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// Random of a ServerHello that is a HelloRetryRequest (RFC 8446, section 4.1.3).
+pub const HELLO_RETRY_REQUEST_RANDOM: [u8; 32] = [
+    0xcf, 0x21, 0xad, 0x74, 0xe5, 0x9a, 0x61, 0x11, 0xbe, 0x1d, 0x8c, 0x02, 0x1e, 0x65, 0xb8, 0x91,
+    0xc2, 0xa2, 0x11, 0x16, 0x7a, 0xbb, 0x8c, 0x5e, 0x07, 0x9e, 0x09, 0xe2, 0xc8, 0xa8, 0x33, 0x9c,
+];
+
+/// End of the random of a TLS 1.3 server that negotiated TLS 1.2.
+pub const DOWNGRADE_TLS12: [u8; 8] = *b"DOWNGRD\x01";
+
+/// End of the random of a TLS 1.3 server that negotiated TLS 1.1 or below.
+pub const DOWNGRADE_TLS11: [u8; 8] = *b"DOWNGRD\x00";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Random([u8; 32]);
 
+impl Random {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Whether this is the random of a HelloRetryRequest.
+    pub fn is_hello_retry_request(&self) -> bool {
+        self.0 == HELLO_RETRY_REQUEST_RANDOM
+    }
+
+    /// Whether the random ends with either downgrade sentinel.
+    pub fn has_downgrade_sentinel(&self) -> bool {
+        let end = &self.0[32 - DOWNGRADE_TLS12.len()..];
+        end == DOWNGRADE_TLS12 || end == DOWNGRADE_TLS11
+    }
+}
+
 impl<'a> Codec<'a> for Random {
     fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
         enc.append(self.0)
@@ -50,6 +79,27 @@ mod tests {
         assert_eq!(random.data_size(), 32);
     }
 
+    #[test]
+    fn hello_retry_request() {
+        assert!(Random::from(HELLO_RETRY_REQUEST_RANDOM).is_hello_retry_request());
+        assert!(!Random::default().is_hello_retry_request());
+    }
+
+    #[test]
+    fn downgrade_sentinel() {
+        let mut bytes = [7; 32];
+        assert!(!Random::from(bytes).has_downgrade_sentinel());
+
+        bytes[24..].copy_from_slice(&DOWNGRADE_TLS12);
+        assert!(Random::from(bytes).has_downgrade_sentinel());
+
+        bytes[24..].copy_from_slice(&DOWNGRADE_TLS11);
+        assert!(Random::from(bytes).has_downgrade_sentinel());
+
+        bytes[31] = 2;
+        assert!(!Random::from(bytes).has_downgrade_sentinel());
+    }
+
     mod encode {
         use super::*;
 
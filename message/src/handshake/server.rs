@@ -4,7 +4,7 @@ use crate::{
     handshake::{
         enums::{CipherSuite, CompressionMethod, ProtocolVersion},
         extension::{server::ServerExtension, Extensions},
-        random::Random,
+        random::{Random, HELLO_RETRY_REQUEST_RANDOM},
         session::SessionId,
     },
 };
@@ -16,7 +16,50 @@ pub struct ServerHelloPayload<'a> {
     session_id: SessionId,
     cipher_suite: CipherSuite,
     compression_method: CompressionMethod,
-    extensions: Extensions<'a, ServerExtension>,
+    extensions: Extensions<'a, ServerExtension<'a>>,
+}
+
+impl<'a> ServerHelloPayload<'a> {
+    /// HelloRetryRequest asking the client to try again with what's in `extensions` (RFC 8446,
+    /// section 4.1.4).
+    pub fn hello_retry_request(
+        session_id: SessionId,
+        cipher_suite: CipherSuite,
+        extensions: Extensions<'a, ServerExtension<'a>>,
+    ) -> Self {
+        ServerHelloPayload {
+            server_version: ProtocolVersion::TLSv1_2,
+            random: HELLO_RETRY_REQUEST_RANDOM.into(),
+            session_id,
+            cipher_suite,
+            compression_method: CompressionMethod::Null,
+            extensions,
+        }
+    }
+
+    pub fn server_version(&self) -> ProtocolVersion {
+        self.server_version
+    }
+
+    pub fn random(&self) -> &Random {
+        &self.random
+    }
+
+    pub fn session_id(&self) -> &SessionId {
+        &self.session_id
+    }
+
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    pub fn compression_method(&self) -> CompressionMethod {
+        self.compression_method
+    }
+
+    pub fn extensions(&self) -> &Extensions<'a, ServerExtension<'a>> {
+        &self.extensions
+    }
 }
 
 impl<'a> Codec<'a> for ServerHelloPayload<'a> {
@@ -145,6 +188,29 @@ mod tests {
                 }),
             )
         }
+
+        #[test]
+        fn hello_retry_request() {
+            assert_eq!(
+                embed_bytes(ServerHelloPayload::hello_retry_request(
+                    [1, 2, 3].into(),
+                    CipherSuite::TlsAes256GcmSha384,
+                    Extensions::from(arr![ServerExtension::SupportedVersions(
+                        ProtocolVersion::TLSv1_3
+                    )]),
+                )),
+                rustls_bytes(r_ServerHelloPayload {
+                    legacy_version: r_ProtocolVersion::TLSv1_2,
+                    random: r_Random::from_slice(&HELLO_RETRY_REQUEST_RANDOM),
+                    session_id: r_SessionId::new(&[1, 2, 3]),
+                    cipher_suite: r_CipherSuite::TLS13_AES_256_GCM_SHA384,
+                    compression_method: r_Compression::Null,
+                    extensions: vec![r_ServerExtension::SupportedVersions(
+                        r_ProtocolVersion::TLSv1_3
+                    )],
+                }),
+            )
+        }
     }
 
     mod decode {
@@ -3,7 +3,7 @@ use crate::{
     error::Result as TlsResult,
 };
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct SessionId {
     data: [u8; 32],
     len: usize,
@@ -17,6 +17,10 @@ impl SessionId {
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
 }
 
 impl<'a> Codec<'a> for SessionId {
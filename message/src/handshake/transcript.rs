@@ -0,0 +1,304 @@
+use crate::{
+    codec::{num::u24, Codec, Decoder, Encoder},
+    crypto::{Digest, Hash, MAX_DIGEST_LEN},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::{
+        enums::HandshakeType,
+        message::{HandshakeMessage, HandshakePayload},
+    },
+};
+use managed::ManagedSlice;
+
+/// Running hash of the handshake messages exchanged so far.
+///
+/// The hash algorithm isn't known until the cipher suite has been negotiated, so messages added
+/// before then (i.e. the ClientHello) are buffered and hashed once [`Transcript::start_hash`] is
+/// called.
+pub struct Transcript<'a, H: Hash> {
+    buffer: Encoder<'a>,
+    hash: Option<H>,
+}
+
+impl<'a, H: Hash> Transcript<'a, H> {
+    pub fn new<T: Into<ManagedSlice<'a, u8>>>(buf: T) -> Self {
+        Self {
+            buffer: Encoder::new(buf),
+            hash: None,
+        }
+    }
+
+    /// Starts hashing with the hash algorithm of the negotiated cipher suite. Any buffered
+    /// messages are added to the hash.
+    pub fn start_hash(&mut self, mut hash: H) -> TlsResult<()> {
+        if self.hash.is_some() {
+            return Err(TlsError::InternalError("transcript hash already started"));
+        }
+
+        hash.update(self.buffer.bytes());
+        self.hash = Some(hash);
+        Ok(())
+    }
+
+    pub fn is_hashing(&self) -> bool {
+        self.hash.is_some()
+    }
+
+    /// Adds an encoded handshake message, including its header, to the transcript.
+    pub fn add(&mut self, msg: &[u8]) -> TlsResult<()> {
+        match self.hash {
+            Some(ref mut hash) => {
+                hash.update(msg);
+                Ok(())
+            }
+            None => self.buffer.append(msg),
+        }
+    }
+
+    /// Encodes the message and adds the encoded bytes to the transcript.
+    pub fn encode_message<'b>(
+        &mut self,
+        msg: &HandshakeMessage<'b>,
+        enc: &mut Encoder<'b>,
+    ) -> TlsResult<()> {
+        let start = enc.bytes().len();
+        msg.encode(enc)?;
+        self.add(&enc.bytes()[start..])
+    }
+
+    /// Decodes the next message and adds the bytes it was decoded from to the transcript.
+    pub fn decode_message<'b>(&mut self, dec: &mut Decoder<'b>) -> TlsResult<HandshakeMessage<'b>> {
+        let bytes = Self::take_message(dec).ok_or(TlsError::DecodeError)?;
+        let msg =
+            HandshakeMessage::decode(&mut Decoder::new(bytes)).ok_or(TlsError::DecodeError)?;
+
+        self.add(bytes)?;
+        Ok(msg)
+    }
+
+    /// Hash of the messages added so far.
+    pub fn current_hash(&self) -> TlsResult<Digest> {
+        self.hash
+            .clone()
+            .map(Hash::finish)
+            .ok_or(TlsError::InternalError("transcript hash not started"))
+    }
+
+    /// Replaces ClientHello1 with the synthetic `message_hash` message, as required when the
+    /// server responds with a HelloRetryRequest (RFC 8446, section 4.4.1).
+    ///
+    /// This must be called after ClientHello1 is added and before the HelloRetryRequest is.
+    pub fn hello_retry(&mut self) -> TlsResult<()> {
+        let client_hello_hash = self.current_hash()?;
+        let hash = self
+            .hash
+            .as_mut()
+            .ok_or(TlsError::InternalError("transcript hash not started"))?;
+
+        hash.reset();
+        Self::add_message_hash(hash, &client_hello_hash)
+    }
+
+    /// Starts hashing from the `message_hash` of ClientHello1, for a server that sent a
+    /// HelloRetryRequest without keeping the transcript, and only has the hash of ClientHello1
+    /// from the cookie.
+    ///
+    /// The HelloRetryRequest is expected to be added next.
+    pub fn resume_hello_retry(&mut self, mut hash: H, client_hello_hash: &Digest) -> TlsResult<()> {
+        if self.hash.is_some() || !self.buffer.bytes().is_empty() {
+            return Err(TlsError::InternalError("transcript already started"));
+        }
+
+        Self::add_message_hash(&mut hash, client_hello_hash)?;
+        self.hash = Some(hash);
+        Ok(())
+    }
+
+    fn add_message_hash(hash: &mut H, client_hello_hash: &Digest) -> TlsResult<()> {
+        let mut buf = [0; MESSAGE_HASH_MAX_SIZE];
+        let mut enc = Encoder::new(buf.as_mut());
+        HandshakeMessage {
+            ty: HandshakeType::MessageHash,
+            payload: HandshakePayload::Opaque(client_hello_hash.as_ref()),
+        }
+        .encode(&mut enc)?;
+        hash.update(enc.bytes());
+        Ok(())
+    }
+
+    fn take_message<'b>(dec: &mut Decoder<'b>) -> Option<&'b [u8]> {
+        let mut header = Decoder::new(dec.peek(MESSAGE_HEADER_SIZE)?);
+        HandshakeType::decode(&mut header)?;
+        let len = u24::decode(&mut header)?.as_u32() as usize;
+
+        dec.take(MESSAGE_HEADER_SIZE + len)
+    }
+}
+
+const MESSAGE_HEADER_SIZE: usize = 4;
+const MESSAGE_HASH_MAX_SIZE: usize = MESSAGE_HEADER_SIZE + MAX_DIGEST_LEN;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{sha256, Sha256};
+    use std::vec::Vec;
+
+    const CLIENT_HELLO: [u8; 6] = [0x01, 0, 0, 2, 0xaa, 0xbb];
+    const SERVER_HELLO: [u8; 6] = [0x02, 0, 0, 2, 0xcc, 0xdd];
+
+    #[test]
+    fn buffers_until_hash_started() {
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript.add(&CLIENT_HELLO).unwrap();
+
+        assert!(!transcript.is_hashing());
+        assert!(transcript.current_hash().is_err());
+
+        transcript.start_hash(Sha256::default()).unwrap();
+        transcript.add(&SERVER_HELLO).unwrap();
+
+        assert_eq!(
+            transcript.current_hash().unwrap(),
+            sha256(&[CLIENT_HELLO, SERVER_HELLO].concat())
+        );
+    }
+
+    #[test]
+    fn buffer_too_small() {
+        let mut buf = [0; 4];
+        let mut transcript = Transcript::<Sha256>::new(buf.as_mut());
+
+        assert!(transcript.add(&CLIENT_HELLO).is_err());
+    }
+
+    #[test]
+    fn borrowed_buffer() {
+        let mut buf = [0; 16];
+        let mut transcript = Transcript::<Sha256>::new(buf.as_mut());
+        transcript.add(&CLIENT_HELLO).unwrap();
+        transcript.start_hash(Sha256::default()).unwrap();
+
+        assert_eq!(transcript.current_hash().unwrap(), sha256(&CLIENT_HELLO));
+    }
+
+    #[test]
+    fn hash_started_twice() {
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript.start_hash(Sha256::default()).unwrap();
+
+        assert!(transcript.start_hash(Sha256::default()).is_err());
+    }
+
+    #[test]
+    fn current_hash_forks() {
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript.start_hash(Sha256::default()).unwrap();
+        transcript.add(&CLIENT_HELLO).unwrap();
+
+        let first = transcript.current_hash().unwrap();
+        transcript.add(&SERVER_HELLO).unwrap();
+
+        assert_eq!(first, sha256(&CLIENT_HELLO));
+        assert_ne!(transcript.current_hash().unwrap(), first);
+    }
+
+    #[test]
+    fn hello_retry_message_hash() {
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript.add(&CLIENT_HELLO).unwrap();
+        transcript.start_hash(Sha256::default()).unwrap();
+        transcript.hello_retry().unwrap();
+        transcript.add(&SERVER_HELLO).unwrap();
+
+        let message_hash: Vec<u8> = [0xfe, 0, 0, 32]
+            .iter()
+            .chain(sha256(&CLIENT_HELLO).as_ref())
+            .chain(SERVER_HELLO.iter())
+            .cloned()
+            .collect();
+        assert_eq!(transcript.current_hash().unwrap(), sha256(&message_hash));
+    }
+
+    #[test]
+    fn resume_hello_retry() {
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript
+            .resume_hello_retry(Sha256::default(), &sha256(&CLIENT_HELLO))
+            .unwrap();
+        transcript.add(&SERVER_HELLO).unwrap();
+
+        let mut expected = Transcript::<Sha256>::new(vec![]);
+        expected.add(&CLIENT_HELLO).unwrap();
+        expected.start_hash(Sha256::default()).unwrap();
+        expected.hello_retry().unwrap();
+        expected.add(&SERVER_HELLO).unwrap();
+
+        assert_eq!(
+            transcript.current_hash().unwrap(),
+            expected.current_hash().unwrap()
+        );
+    }
+
+    #[test]
+    fn resume_hello_retry_after_start() {
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript.add(&CLIENT_HELLO).unwrap();
+
+        assert!(transcript
+            .resume_hello_retry(Sha256::default(), &sha256(&CLIENT_HELLO))
+            .is_err());
+    }
+
+    #[test]
+    fn hello_retry_before_hash_started() {
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript.add(&CLIENT_HELLO).unwrap();
+
+        assert!(transcript.hello_retry().is_err());
+    }
+
+    #[test]
+    fn encode_message() {
+        let msg = HandshakeMessage {
+            ty: HandshakeType::ClientHello,
+            payload: HandshakePayload::Opaque(&[0xaa, 0xbb]),
+        };
+        let mut enc = Encoder::new(vec![]);
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript.encode_message(&msg, &mut enc).unwrap();
+        transcript.start_hash(Sha256::default()).unwrap();
+
+        assert_eq!(enc.bytes(), CLIENT_HELLO);
+        assert_eq!(transcript.current_hash().unwrap(), sha256(&CLIENT_HELLO));
+    }
+
+    #[test]
+    fn decode_messages() {
+        let bytes = [
+            0x14, 0, 0, 2, 0xaa, 0xbb, // Finished
+            0x18, 0, 0, 1, 0x00, // KeyUpdate
+        ];
+        let mut dec = Decoder::new(&bytes);
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+        transcript.start_hash(Sha256::default()).unwrap();
+
+        let finished = transcript.decode_message(&mut dec).unwrap();
+        assert_eq!(finished.ty, HandshakeType::Finished);
+        assert_eq!(transcript.current_hash().unwrap(), sha256(&bytes[..6]));
+
+        let key_update = transcript.decode_message(&mut dec).unwrap();
+        assert_eq!(key_update.ty, HandshakeType::KeyUpdate);
+        assert_eq!(transcript.current_hash().unwrap(), sha256(&bytes));
+        assert!(dec.is_complete());
+    }
+
+    #[test]
+    fn decode_truncated_message() {
+        let bytes = [0x14, 0, 0, 2, 0xaa];
+        let mut dec = Decoder::new(&bytes);
+        let mut transcript = Transcript::<Sha256>::new(vec![]);
+
+        assert!(transcript.decode_message(&mut dec).is_err());
+        assert!(transcript.buffer.bytes().is_empty());
+    }
+}
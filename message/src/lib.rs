@@ -1,11 +1,23 @@
 #![no_std]
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std"))]
 #[macro_use]
 extern crate std;
 
 #[macro_use]
 pub(crate) mod codec;
 
+pub mod crypto;
 pub mod error;
+
+#[macro_use]
 pub mod handshake;
+
+pub mod record;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod time;
+pub mod x509;
+
+/// Encoding and decoding of the messages, for the crates implementing each endpoint.
+pub use codec::{array::Array, Codec, CodecSized, Decoder, Encoder};
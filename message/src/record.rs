@@ -0,0 +1,108 @@
+use crate::{
+    codec::{Codec, CodecSized, Decoder, Encoder, HeaderSize},
+    error::Result as TlsResult,
+    handshake::enums::ProtocolVersion,
+};
+
+pub mod change_cipher_spec;
+pub mod padding;
+pub mod protection;
+
+/// Size of the header of every record.
+pub const RECORD_HEADER_SIZE: usize = 5;
+
+/// Largest plaintext fragment allowed in a record (2^14 bytes).
+pub const MAX_FRAGMENT_LEN: usize = 16384;
+
+/// Largest ciphertext allowed in a protected record (2^14 + 256 bytes).
+pub const MAX_CIPHERTEXT_LEN: usize = MAX_FRAGMENT_LEN + CIPHERTEXT_EXPANSION;
+
+/// Smallest value allowed in the record_size_limit extension (RFC 8449, section 4).
+pub const MIN_RECORD_SIZE_LIMIT: u16 = 64;
+
+/// Most the ciphertext of a protected record may exceed its content by.
+const CIPHERTEXT_EXPANSION: usize = 256;
+
+msg_enum! {
+    ContentType, u8;
+    {
+        Invalid => 0,
+        ChangeCipherSpec => 20,
+        Alert => 21,
+        Handshake => 22,
+        ApplicationData => 23,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordHeader {
+    pub ty: ContentType,
+    pub version: ProtocolVersion,
+    pub len: u16,
+}
+
+impl<'a> Codec<'a> for RecordHeader {
+    fn encode(&self, enc: &mut Encoder<'a>) -> TlsResult<()> {
+        self.ty.encode(enc)?;
+        self.version.encode(enc)?;
+        self.len.encode(enc)
+    }
+
+    fn decode(dec: &mut Decoder<'a>) -> Option<Self> {
+        Some(RecordHeader {
+            ty: ContentType::decode(dec)?,
+            version: ProtocolVersion::decode(dec)?,
+            len: u16::decode(dec)?,
+        })
+    }
+}
+
+impl<'a> CodecSized<'a> for RecordHeader {
+    const HEADER_SIZE: HeaderSize = HeaderSize::Zero;
+
+    fn data_size(&self) -> usize {
+        RECORD_HEADER_SIZE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_header() {
+        let header = RecordHeader {
+            ty: ContentType::Handshake,
+            version: ProtocolVersion::TLSv1_2,
+            len: 0x0102,
+        };
+        let mut enc = Encoder::new(vec![]);
+        header.encode(&mut enc).unwrap();
+
+        assert_eq!(header.data_size(), 5);
+        assert_eq!(enc.bytes(), [22, 3, 3, 1, 2]);
+    }
+
+    #[test]
+    fn decode_header() {
+        let bytes = [23, 3, 3, 0, 5];
+        let mut dec = Decoder::new(&bytes);
+
+        assert_eq!(
+            RecordHeader::decode(&mut dec).unwrap(),
+            RecordHeader {
+                ty: ContentType::ApplicationData,
+                version: ProtocolVersion::TLSv1_2,
+                len: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn decode_header_not_enough_bytes() {
+        let bytes = [23, 3, 3, 0];
+        let mut dec = Decoder::new(&bytes);
+
+        assert!(RecordHeader::decode(&mut dec).is_none());
+    }
+}
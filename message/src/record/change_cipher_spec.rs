@@ -0,0 +1,86 @@
+use crate::error::{Error as TlsError, Result as TlsResult};
+
+/// Content of the only ChangeCipherSpec record allowed in TLS 1.3.
+pub const CHANGE_CIPHER_SPEC: [u8; 1] = [0x01];
+
+/// Discards the unprotected ChangeCipherSpec record a peer in middlebox compatibility mode sends
+/// during the handshake (RFC 8446, section 5 and appendix D.4).
+///
+/// Only a single record is tolerated, and only until the handshake completes. Protected
+/// ChangeCipherSpec records are never valid, and must be rejected before getting here.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChangeCipherSpecFilter {
+    received: bool,
+    handshake_complete: bool,
+}
+
+impl ChangeCipherSpecFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks the content of an unprotected ChangeCipherSpec record, which is discarded if this
+    /// succeeds.
+    ///
+    /// On failure, the error is the alert to send to the peer.
+    pub fn discard(&mut self, content: &[u8]) -> TlsResult<()> {
+        if self.received || self.handshake_complete || content != CHANGE_CIPHER_SPEC {
+            return Err(TlsError::UnexpectedMessage);
+        }
+
+        self.received = true;
+        Ok(())
+    }
+
+    /// Whether a ChangeCipherSpec record was discarded.
+    pub fn has_received(&self) -> bool {
+        self.received
+    }
+
+    /// Rejects every ChangeCipherSpec record from now on, once the peer's Finished was received
+    /// (RFC 8446, section 5).
+    pub fn handshake_complete(&mut self) {
+        self.handshake_complete = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn discards_one() {
+        let mut filter = ChangeCipherSpecFilter::new();
+
+        assert!(filter.discard(&CHANGE_CIPHER_SPEC).is_ok());
+        assert!(filter.has_received());
+        assert!(matches!(
+            filter.discard(&CHANGE_CIPHER_SPEC),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn after_handshake() {
+        let mut filter = ChangeCipherSpecFilter::new();
+        filter.handshake_complete();
+
+        assert!(matches!(
+            filter.discard(&CHANGE_CIPHER_SPEC),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn other_content() {
+        for content in [&[][..], &[0x02], &[0x01, 0x01]] {
+            let mut filter = ChangeCipherSpecFilter::new();
+
+            assert!(matches!(
+                filter.discard(content),
+                Err(TlsError::UnexpectedMessage)
+            ));
+            assert!(!filter.has_received());
+        }
+    }
+}
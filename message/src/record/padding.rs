@@ -0,0 +1,112 @@
+use crate::record::{ContentType, MAX_FRAGMENT_LEN};
+
+/// How much zero padding to add to the inner plaintext of protected records, to hide the length
+/// of the content from observers (RFC 8446, section 5.4).
+#[derive(Debug, Clone, Copy, Default)]
+pub enum PaddingPolicy {
+    /// Don't pad records.
+    #[default]
+    None,
+    /// Pad the inner plaintext (the content and its type) to a multiple of the given size.
+    Multiple(usize),
+    /// Pad every record to the largest inner plaintext allowed.
+    Full,
+    /// Number of padding bytes for a record of the given type and content length.
+    Custom(fn(ContentType, usize) -> usize),
+}
+
+impl PaddingPolicy {
+    /// Number of padding bytes to add to content of type `ty` and length `len`.
+    ///
+    /// Padding never takes the inner plaintext past the largest allowed size.
+    pub fn padding_len(&self, ty: ContentType, len: usize) -> usize {
+        let inner_len = len + 1;
+        let max_padding = (MAX_FRAGMENT_LEN + 1).saturating_sub(inner_len);
+
+        let padding = match *self {
+            PaddingPolicy::None | PaddingPolicy::Multiple(0) => 0,
+            PaddingPolicy::Multiple(n) => (n - inner_len % n) % n,
+            PaddingPolicy::Full => max_padding,
+            PaddingPolicy::Custom(f) => f(ty, len),
+        };
+        padding.min(max_padding)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn none() {
+        assert_eq!(
+            PaddingPolicy::None.padding_len(ContentType::Handshake, 10),
+            0
+        );
+    }
+
+    #[test]
+    fn multiple() {
+        let policy = PaddingPolicy::Multiple(16);
+
+        assert_eq!(policy.padding_len(ContentType::Handshake, 10), 5);
+        assert_eq!(policy.padding_len(ContentType::Handshake, 15), 0);
+        assert_eq!(policy.padding_len(ContentType::Handshake, 16), 15);
+    }
+
+    #[test]
+    fn multiple_of_zero() {
+        assert_eq!(
+            PaddingPolicy::Multiple(0).padding_len(ContentType::Handshake, 10),
+            0
+        );
+    }
+
+    #[test]
+    fn multiple_capped() {
+        let policy = PaddingPolicy::Multiple(1000);
+
+        assert_eq!(
+            policy.padding_len(ContentType::Handshake, MAX_FRAGMENT_LEN - 1),
+            1
+        );
+    }
+
+    #[test]
+    fn full() {
+        let policy = PaddingPolicy::Full;
+
+        assert_eq!(
+            policy.padding_len(ContentType::Handshake, 10),
+            MAX_FRAGMENT_LEN - 10
+        );
+        assert_eq!(
+            policy.padding_len(ContentType::Handshake, MAX_FRAGMENT_LEN),
+            0
+        );
+    }
+
+    #[test]
+    fn custom() {
+        fn pad_application_data(ty: ContentType, _len: usize) -> usize {
+            match ty {
+                ContentType::ApplicationData => 32,
+                _ => 0,
+            }
+        }
+        let policy = PaddingPolicy::Custom(pad_application_data);
+
+        assert_eq!(policy.padding_len(ContentType::ApplicationData, 10), 32);
+        assert_eq!(policy.padding_len(ContentType::Handshake, 10), 0);
+    }
+
+    #[test]
+    fn custom_capped() {
+        let policy = PaddingPolicy::Custom(|_, _| usize::MAX);
+
+        assert_eq!(
+            policy.padding_len(ContentType::Handshake, 10),
+            MAX_FRAGMENT_LEN - 10
+        );
+    }
+}
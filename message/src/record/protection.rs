@@ -0,0 +1,500 @@
+use crate::{
+    codec::{Codec, Decoder, Encoder},
+    crypto::{Aead, NONCE_LEN},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::enums::{MaxFragmentLength, ProtocolVersion},
+    record::{
+        padding::PaddingPolicy, ContentType, RecordHeader, CIPHERTEXT_EXPANSION, MAX_FRAGMENT_LEN,
+        MIN_RECORD_SIZE_LIMIT, RECORD_HEADER_SIZE,
+    },
+};
+
+/// Protects records in one direction with a traffic key and IV (RFC 8446, section 5.2).
+///
+/// Records are encrypted and decrypted in place over caller-provided buffers that hold the whole
+/// record, header included.
+pub struct RecordProtection<A: Aead> {
+    aead: A,
+    iv: [u8; NONCE_LEN],
+    seq: u64,
+    padding: PaddingPolicy,
+    max_fragment_len: usize,
+}
+
+impl<A: Aead> RecordProtection<A> {
+    pub fn new(aead: A, iv: [u8; NONCE_LEN]) -> Self {
+        Self {
+            aead,
+            iv,
+            seq: 0,
+            padding: PaddingPolicy::default(),
+            max_fragment_len: MAX_FRAGMENT_LEN,
+        }
+    }
+
+    pub fn set_padding(&mut self, padding: PaddingPolicy) {
+        self.padding = padding;
+    }
+
+    /// Limits the content of records to the length negotiated with the max_fragment_length
+    /// extension (RFC 6066, section 4).
+    pub fn set_max_fragment_length(&mut self, max: MaxFragmentLength) -> TlsResult<()> {
+        self.max_fragment_len = max.fragment_len().ok_or(TlsError::IllegalParameter)?;
+        Ok(())
+    }
+
+    /// Limits records to the size advertised with the record_size_limit extension (RFC 8449).
+    ///
+    /// The limit covers the inner plaintext, so the content type and padding count towards it.
+    /// Limits above what TLS 1.3 allows leave records at their usual size.
+    pub fn set_record_size_limit(&mut self, limit: u16) -> TlsResult<()> {
+        if limit < MIN_RECORD_SIZE_LIMIT {
+            return Err(TlsError::IllegalParameter);
+        }
+        self.max_fragment_len = usize::from(limit - 1).min(MAX_FRAGMENT_LEN);
+        Ok(())
+    }
+
+    /// Largest content allowed in a record.
+    pub fn max_fragment_len(&self) -> usize {
+        self.max_fragment_len
+    }
+
+    /// Largest ciphertext allowed in a record.
+    pub fn max_ciphertext_len(&self) -> usize {
+        self.max_fragment_len + CIPHERTEXT_EXPANSION
+    }
+
+    /// Sequence number of the next record.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// Size of the protected record that [`RecordProtection::seal`] produces for `len` bytes of
+    /// content of type `ty`.
+    pub fn sealed_len(&self, ty: ContentType, len: usize) -> usize {
+        RECORD_HEADER_SIZE + len + 1 + self.padding_len(ty, len) + self.aead.tag_len()
+    }
+
+    /// Protects the `len` bytes of content at `buf[RECORD_HEADER_SIZE..]`, returning the size of
+    /// the protected record.
+    ///
+    /// The content type, padding and tag are written after the content, and the record header
+    /// at the start of `buf`.
+    pub fn seal(&mut self, ty: ContentType, buf: &mut [u8], len: usize) -> TlsResult<usize> {
+        if len > self.max_fragment_len {
+            return Err(TlsError::InternalError("record content too large"));
+        }
+
+        let record_len = self.sealed_len(ty, len);
+        let ciphertext_len = record_len - RECORD_HEADER_SIZE;
+        let inner_len = ciphertext_len - self.aead.tag_len();
+        if buf.len() < record_len {
+            return Err(TlsError::InternalError("not enough space to seal record"));
+        }
+
+        let inner = &mut buf[RECORD_HEADER_SIZE..][..inner_len];
+        inner[len] = u8::from(ty);
+        inner[len + 1..].iter_mut().for_each(|b| *b = 0);
+        let header = RecordHeader {
+            ty: ContentType::ApplicationData,
+            version: ProtocolVersion::TLSv1_2,
+            len: ciphertext_len as u16,
+        };
+        let (aad, in_out) = buf[..record_len].split_at_mut(RECORD_HEADER_SIZE);
+        header.encode(&mut Encoder::new(&mut *aad))?;
+
+        let nonce = self.next_nonce()?;
+        self.aead.seal_in_place(&nonce, aad, in_out)?;
+        Ok(record_len)
+    }
+
+    /// Decrypts the protected record in `buf` in place, returning the inner content type and the
+    /// content with any padding removed.
+    pub fn open<'b>(&mut self, buf: &'b mut [u8]) -> TlsResult<(ContentType, &'b [u8])> {
+        let header = RecordHeader::decode(&mut Decoder::new(buf)).ok_or(TlsError::DecodeError)?;
+        if header.ty != ContentType::ApplicationData {
+            return Err(TlsError::UnexpectedMessage);
+        }
+
+        let record_len = RECORD_HEADER_SIZE + usize::from(header.len);
+        if usize::from(header.len) > self.max_ciphertext_len() {
+            return Err(TlsError::RecordOverflow);
+        }
+        if buf.len() < record_len {
+            return Err(TlsError::DecodeError);
+        }
+
+        let nonce = self.next_nonce()?;
+        let (aad, in_out) = buf[..record_len].split_at_mut(RECORD_HEADER_SIZE);
+        let len = self
+            .aead
+            .open_in_place(&nonce, aad, in_out)
+            .map_err(|_| TlsError::BadRecordMac)?;
+
+        if len > self.max_fragment_len + 1 {
+            return Err(TlsError::RecordOverflow);
+        }
+
+        let plaintext = &in_out[..len];
+        let ty_offset = plaintext
+            .iter()
+            .rposition(|b| *b != 0)
+            .ok_or(TlsError::UnexpectedMessage)?;
+
+        Ok((
+            ContentType::from(plaintext[ty_offset]),
+            &plaintext[..ty_offset],
+        ))
+    }
+
+    /// Padding for the content, keeping the inner plaintext within the record limit.
+    fn padding_len(&self, ty: ContentType, len: usize) -> usize {
+        let max_padding = self.max_fragment_len.saturating_sub(len);
+        self.padding.padding_len(ty, len).min(max_padding)
+    }
+
+    /// Builds the nonce for the current sequence number and advances it, refusing to wrap.
+    fn next_nonce(&mut self) -> TlsResult<[u8; NONCE_LEN]> {
+        let seq = self.seq;
+        self.seq = seq
+            .checked_add(1)
+            .ok_or(TlsError::InternalError("record sequence number exhausted"))?;
+
+        let mut nonce = self.iv;
+        nonce[NONCE_LEN - 8..]
+            .iter_mut()
+            .zip(seq.to_be_bytes().iter())
+            .for_each(|(n, s)| *n ^= s);
+        Ok(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{record::MAX_CIPHERTEXT_LEN, testing::RingAead};
+
+    const KEY: [u8; 16] = [7; 16];
+    const IV: [u8; NONCE_LEN] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+
+    fn protection() -> RecordProtection<RingAead> {
+        RecordProtection::new(RingAead::aes_128_gcm(&KEY), IV)
+    }
+
+    fn sealed(ty: ContentType, content: &[u8], buf: &mut [u8]) -> usize {
+        buf[RECORD_HEADER_SIZE..][..content.len()].copy_from_slice(content);
+        protection().seal(ty, buf, content.len()).unwrap()
+    }
+
+    #[test]
+    fn nonce_xor_sequence_number() {
+        let mut prot = protection();
+        assert_eq!(prot.next_nonce().unwrap(), IV);
+        assert_eq!(
+            prot.next_nonce().unwrap(),
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 13]
+        );
+
+        prot.seq = 0x0102;
+        assert_eq!(
+            prot.next_nonce().unwrap(),
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 10, 14]
+        );
+    }
+
+    #[test]
+    fn sequence_number_wraparound() {
+        let mut prot = protection();
+        prot.seq = u64::MAX;
+
+        let mut buf = [0; 64];
+        assert!(prot.seal(ContentType::Handshake, &mut buf, 1).is_err());
+    }
+
+    #[test]
+    fn seal_header() {
+        let mut buf = [0; 64];
+        let len = sealed(ContentType::Handshake, &[1, 2, 3], &mut buf);
+
+        assert_eq!(len, 5 + 3 + 1 + 16);
+        assert_eq!(len, protection().sealed_len(ContentType::Handshake, 3));
+        assert_eq!(buf[..5], [23, 3, 3, 0, 20]);
+    }
+
+    #[test]
+    fn seal_not_enough_space() {
+        let mut buf = [0; 24];
+        assert!(protection()
+            .seal(ContentType::Handshake, &mut buf, 3)
+            .is_err());
+    }
+
+    #[test]
+    fn seal_open() {
+        let mut buf = [0; 64];
+        let len = sealed(ContentType::Handshake, &[1, 2, 3], &mut buf);
+
+        let (ty, content) = protection().open(&mut buf[..len]).unwrap();
+        assert_eq!(ty, ContentType::Handshake);
+        assert_eq!(content, [1, 2, 3]);
+    }
+
+    #[test]
+    fn seal_open_sequence() {
+        let mut sealer = protection();
+        let mut opener = protection();
+
+        for i in 0..3 {
+            let mut buf = [0; 64];
+            buf[RECORD_HEADER_SIZE] = i;
+            let len = sealer
+                .seal(ContentType::ApplicationData, &mut buf, 1)
+                .unwrap();
+
+            let (ty, content) = opener.open(&mut buf[..len]).unwrap();
+            assert_eq!(ty, ContentType::ApplicationData);
+            assert_eq!(content, [i]);
+        }
+        assert_eq!(opener.seq(), 3);
+    }
+
+    #[test]
+    fn seal_padding() {
+        let mut prot = protection();
+        prot.set_padding(PaddingPolicy::Multiple(32));
+
+        let mut buf = [0xff; 64];
+        let len = prot.seal(ContentType::Handshake, &mut buf, 3).unwrap();
+        assert_eq!(len, 5 + 32 + 16);
+        assert_eq!(buf[..5], [23, 3, 3, 0, 48]);
+
+        let (ty, content) = protection().open(&mut buf[..len]).unwrap();
+        assert_eq!(ty, ContentType::Handshake);
+        assert_eq!(content, [0xff; 3]);
+    }
+
+    #[test]
+    fn seal_full_padding() {
+        let mut prot = protection();
+        prot.set_padding(PaddingPolicy::Full);
+
+        let mut buf = vec![0; RECORD_HEADER_SIZE + MAX_CIPHERTEXT_LEN];
+        let len = prot
+            .seal(ContentType::ApplicationData, &mut buf, 1)
+            .unwrap();
+        assert_eq!(len, RECORD_HEADER_SIZE + MAX_FRAGMENT_LEN + 1 + 16);
+
+        let (ty, content) = protection().open(&mut buf[..len]).unwrap();
+        assert_eq!(ty, ContentType::ApplicationData);
+        assert_eq!(content, [0]);
+    }
+
+    #[test]
+    fn seal_padding_not_enough_space() {
+        let mut prot = protection();
+        prot.set_padding(PaddingPolicy::Full);
+
+        let mut buf = [0; 64];
+        assert!(prot.seal(ContentType::Handshake, &mut buf, 3).is_err());
+    }
+
+    #[test]
+    fn open_strips_zero_padding() {
+        // Content [0, 1, 0] of type Handshake followed by 7 bytes of padding, sealed as if it
+        // were 11 bytes of content with no padding.
+        let mut buf = [0; 64];
+        let inner = [0, 1, 0, 22, 0, 0, 0, 0, 0, 0, 0];
+        buf[RECORD_HEADER_SIZE..][..inner.len() - 1].copy_from_slice(&inner[..inner.len() - 1]);
+        let len = protection()
+            .seal(ContentType::Invalid, &mut buf, inner.len() - 1)
+            .unwrap();
+
+        let (ty, content) = protection().open(&mut buf[..len]).unwrap();
+        assert_eq!(ty, ContentType::Handshake);
+        assert_eq!(content, [0, 1, 0]);
+    }
+
+    #[test]
+    fn open_all_zero_plaintext() {
+        let mut buf = [0; 64];
+        let len = sealed(ContentType::Invalid, &[0, 0, 0], &mut buf);
+
+        assert!(matches!(
+            protection().open(&mut buf[..len]),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn open_out_of_order() {
+        let mut buf = [0; 64];
+        let len = sealed(ContentType::Handshake, &[1, 2, 3], &mut buf);
+
+        let mut opener = protection();
+        opener.seq = 1;
+        assert!(matches!(
+            opener.open(&mut buf[..len]),
+            Err(TlsError::BadRecordMac)
+        ));
+    }
+
+    #[test]
+    fn open_tampered_header() {
+        let mut buf = [0; 64];
+        let len = sealed(ContentType::Handshake, &[1, 2, 3], &mut buf);
+        buf[2] = 1;
+
+        assert!(matches!(
+            protection().open(&mut buf[..len]),
+            Err(TlsError::BadRecordMac)
+        ));
+    }
+
+    #[test]
+    fn open_tampered_ciphertext() {
+        let mut buf = [0; 64];
+        let len = sealed(ContentType::Handshake, &[1, 2, 3], &mut buf);
+        buf[RECORD_HEADER_SIZE] ^= 1;
+
+        assert!(matches!(
+            protection().open(&mut buf[..len]),
+            Err(TlsError::BadRecordMac)
+        ));
+    }
+
+    #[test]
+    fn open_too_short_for_tag() {
+        let mut buf = [23, 3, 3, 0, 2, 0, 0];
+
+        assert!(matches!(
+            protection().open(&mut buf),
+            Err(TlsError::BadRecordMac)
+        ));
+    }
+
+    #[test]
+    fn open_plaintext_record() {
+        let mut buf = [22, 3, 3, 0, 1, 0];
+
+        assert!(matches!(
+            protection().open(&mut buf),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn open_overflow() {
+        let mut buf = [23, 3, 3, 0x41, 0x01];
+
+        assert!(matches!(
+            protection().open(&mut buf),
+            Err(TlsError::RecordOverflow)
+        ));
+    }
+
+    #[test]
+    fn open_truncated_record() {
+        let mut buf = [0; 64];
+        let len = sealed(ContentType::Handshake, &[1, 2, 3], &mut buf);
+
+        assert!(protection().open(&mut buf[..len - 1]).is_err());
+    }
+
+    #[test]
+    fn max_fragment_length() {
+        let mut prot = protection();
+        prot.set_max_fragment_length(MaxFragmentLength::Max512)
+            .unwrap();
+
+        assert_eq!(prot.max_fragment_len(), 512);
+        assert_eq!(prot.max_ciphertext_len(), 512 + 256);
+    }
+
+    #[test]
+    fn unknown_max_fragment_length() {
+        assert!(matches!(
+            protection().set_max_fragment_length(MaxFragmentLength::Unknown(5)),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn record_size_limit() {
+        let mut prot = protection();
+        prot.set_record_size_limit(64).unwrap();
+        assert_eq!(prot.max_fragment_len(), 63);
+
+        prot.set_record_size_limit(u16::MAX).unwrap();
+        assert_eq!(prot.max_fragment_len(), MAX_FRAGMENT_LEN);
+    }
+
+    #[test]
+    fn record_size_limit_too_small() {
+        assert!(matches!(
+            protection().set_record_size_limit(63),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn seal_over_limit() {
+        let mut prot = protection();
+        prot.set_record_size_limit(64).unwrap();
+
+        let mut buf = [0; 128];
+        assert!(prot.seal(ContentType::Handshake, &mut buf, 63).is_ok());
+        assert!(prot.seal(ContentType::Handshake, &mut buf, 64).is_err());
+    }
+
+    #[test]
+    fn seal_padding_within_limit() {
+        let mut prot = protection();
+        prot.set_record_size_limit(64).unwrap();
+        prot.set_padding(PaddingPolicy::Full);
+
+        let mut buf = [0; 128];
+        let len = prot.seal(ContentType::Handshake, &mut buf, 3).unwrap();
+        assert_eq!(len, 5 + 64 + 16);
+    }
+
+    #[test]
+    fn open_over_limit() {
+        let mut buf = [0; 128];
+        let len = sealed(ContentType::Handshake, &[1; 64], &mut buf);
+
+        let mut opener = protection();
+        opener.set_record_size_limit(64).unwrap();
+        assert!(matches!(
+            opener.open(&mut buf[..len]),
+            Err(TlsError::RecordOverflow)
+        ));
+    }
+
+    #[test]
+    fn open_padding_over_limit() {
+        let mut sealer = protection();
+        sealer.set_padding(PaddingPolicy::Multiple(128));
+        let mut buf = [0; 256];
+        let len = sealer.seal(ContentType::Handshake, &mut buf, 3).unwrap();
+
+        let mut opener = protection();
+        opener.set_record_size_limit(64).unwrap();
+        assert!(matches!(
+            opener.open(&mut buf[..len]),
+            Err(TlsError::RecordOverflow)
+        ));
+    }
+
+    #[test]
+    fn open_ciphertext_over_limit() {
+        let mut buf = [23, 3, 3, 0x01, 0x41];
+
+        let mut opener = protection();
+        opener.set_record_size_limit(64).unwrap();
+        assert!(matches!(
+            opener.open(&mut buf),
+            Err(TlsError::RecordOverflow)
+        ));
+    }
+}
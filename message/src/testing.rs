@@ -0,0 +1,281 @@
+//! Crypto provider built on ring, shared by the tests of every crate in the workspace.
+//!
+//! Only built for this crate's own tests, or for other crates with the `testing` feature.
+
+use crate::{
+    crypto::{Aead, Digest, Hash, KeyExchange, SignatureVerifier, Signer, NONCE_LEN},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::enums::{
+        AeadAlgorithm, HashAlgorithm, NamedGroup, SignatureAlgorithm, SignatureScheme,
+    },
+    x509::{
+        certificate::{PublicKeyAlgorithm, SignatureAlgorithmIdentifier, SubjectPublicKeyInfo},
+        key::PrivateKey,
+        pem::PemSections,
+    },
+};
+use core::convert::TryFrom;
+use ring::{aead, agreement, digest, rand::SystemRandom, signature};
+
+/// 2020-01-01, when all the test certificates are valid.
+pub const NOW: u64 = 1_577_836_800;
+
+#[derive(Clone)]
+pub struct Sha256(digest::Context);
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self(digest::Context::new(&digest::SHA256))
+    }
+}
+
+impl Hash for Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data)
+    }
+
+    fn finish(self) -> Digest {
+        Digest::try_from(self.0.finish().as_ref()).unwrap()
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+pub fn sha256(data: &[u8]) -> Digest {
+    Digest::try_from(digest::digest(&digest::SHA256, data).as_ref()).unwrap()
+}
+
+/// Hash constructor for the handshakes. Only SHA-256 is supported.
+pub fn new_hash(hash: HashAlgorithm) -> Option<Sha256> {
+    match hash {
+        HashAlgorithm::Sha256 => Some(Sha256::default()),
+        _ => None,
+    }
+}
+
+pub struct RingAead {
+    algorithm: &'static aead::Algorithm,
+    sealing: aead::SealingKey,
+    opening: aead::OpeningKey,
+}
+
+impl RingAead {
+    pub fn aes_128_gcm(key: &[u8]) -> Self {
+        new_aead(AeadAlgorithm::Aes128Gcm, key).unwrap()
+    }
+}
+
+/// AEAD constructor for the handshakes.
+pub fn new_aead(algorithm: AeadAlgorithm, key: &[u8]) -> Option<RingAead> {
+    let algorithm = match algorithm {
+        AeadAlgorithm::Aes128Gcm => &aead::AES_128_GCM,
+        AeadAlgorithm::Aes256Gcm => &aead::AES_256_GCM,
+        AeadAlgorithm::ChaCha20Poly1305 => &aead::CHACHA20_POLY1305,
+    };
+    Some(RingAead {
+        algorithm,
+        sealing: aead::SealingKey::new(algorithm, key).ok()?,
+        opening: aead::OpeningKey::new(algorithm, key).ok()?,
+    })
+}
+
+impl Aead for RingAead {
+    fn tag_len(&self) -> usize {
+        self.algorithm.tag_len()
+    }
+
+    fn seal_in_place(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        in_out: &mut [u8],
+    ) -> TlsResult<()> {
+        aead::seal_in_place(
+            &self.sealing,
+            aead::Nonce::assume_unique_for_key(*nonce),
+            aead::Aad::from(aad),
+            in_out,
+            self.tag_len(),
+        )
+        .map(|_| ())
+        .map_err(|_| TlsError::InternalError("seal failed"))
+    }
+
+    fn open_in_place(
+        &self,
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        in_out: &mut [u8],
+    ) -> TlsResult<usize> {
+        aead::open_in_place(
+            &self.opening,
+            aead::Nonce::assume_unique_for_key(*nonce),
+            aead::Aad::from(aad),
+            0,
+            in_out,
+        )
+        .map(|plaintext| plaintext.len())
+        .map_err(|_| TlsError::DecryptError)
+    }
+}
+
+/// X25519 key exchange, with a placeholder X448 key that's never used.
+#[derive(Default)]
+pub struct RingKeyExchange {
+    private_key: Option<agreement::EphemeralPrivateKey>,
+}
+
+impl KeyExchange for RingKeyExchange {
+    fn generate(&mut self, group: NamedGroup, out: &mut [u8]) -> TlsResult<usize> {
+        let public_key = match group {
+            NamedGroup::X25519 => {
+                let private_key = agreement::EphemeralPrivateKey::generate(
+                    &agreement::X25519,
+                    &SystemRandom::new(),
+                )
+                .unwrap();
+                let public_key = private_key.compute_public_key().unwrap();
+                self.private_key = Some(private_key);
+                public_key
+            }
+            NamedGroup::X448 => {
+                out[..56].copy_from_slice(&[1; 56]);
+                return Ok(56);
+            }
+            _ => return Err(TlsError::InternalError("unsupported group")),
+        };
+
+        let len = public_key.as_ref().len();
+        out[..len].copy_from_slice(public_key.as_ref());
+        Ok(len)
+    }
+
+    fn agree(&mut self, peer_public_key: &[u8], out: &mut [u8]) -> TlsResult<usize> {
+        let private_key = self
+            .private_key
+            .take()
+            .ok_or(TlsError::InternalError("no private key"))?;
+        agreement::agree_ephemeral(
+            private_key,
+            &agreement::X25519,
+            untrusted::Input::from(peer_public_key),
+            TlsError::IllegalParameter,
+            |secret| {
+                out[..secret.len()].copy_from_slice(secret);
+                Ok(secret.len())
+            },
+        )
+    }
+}
+
+/// Verifies the signatures of the test certificates.
+pub struct RingVerifier;
+
+impl SignatureVerifier for RingVerifier {
+    fn verify(
+        &self,
+        public_key: &SubjectPublicKeyInfo<'_>,
+        algorithm: SignatureAlgorithmIdentifier,
+        message: &[u8],
+        signature: &[u8],
+    ) -> TlsResult<()> {
+        use self::{HashAlgorithm::*, NamedGroup::*, PublicKeyAlgorithm as Key};
+
+        let verification: &dyn signature::VerificationAlgorithm =
+            match (public_key.algorithm(), algorithm.algorithm, algorithm.hash) {
+                (Key::Ec(Secp256r1), SignatureAlgorithm::Ecdsa, Some(Sha256)) => {
+                    &signature::ECDSA_P256_SHA256_ASN1
+                }
+                (Key::Ec(Secp256r1), SignatureAlgorithm::Ecdsa, Some(Sha384)) => {
+                    &signature::ECDSA_P256_SHA384_ASN1
+                }
+                (Key::Ec(Secp384r1), SignatureAlgorithm::Ecdsa, Some(Sha256)) => {
+                    &signature::ECDSA_P384_SHA256_ASN1
+                }
+                (Key::Ec(Secp384r1), SignatureAlgorithm::Ecdsa, Some(Sha384)) => {
+                    &signature::ECDSA_P384_SHA384_ASN1
+                }
+                (Key::Rsa, SignatureAlgorithm::RsaPkcs1, Some(Sha256)) => {
+                    &signature::RSA_PKCS1_2048_8192_SHA256
+                }
+                (Key::Rsa, SignatureAlgorithm::RsaPkcs1, Some(Sha384)) => {
+                    &signature::RSA_PKCS1_2048_8192_SHA384
+                }
+                (Key::Rsa, SignatureAlgorithm::RsaPkcs1, Some(Sha512)) => {
+                    &signature::RSA_PKCS1_2048_8192_SHA512
+                }
+                (Key::Rsa, SignatureAlgorithm::RsaPss, Some(Sha256)) => {
+                    &signature::RSA_PSS_2048_8192_SHA256
+                }
+                (Key::Rsa, SignatureAlgorithm::RsaPss, Some(Sha384)) => {
+                    &signature::RSA_PSS_2048_8192_SHA384
+                }
+                (Key::Rsa, SignatureAlgorithm::RsaPss, Some(Sha512)) => {
+                    &signature::RSA_PSS_2048_8192_SHA512
+                }
+                (Key::Ed25519, SignatureAlgorithm::Ed25519, None) => &signature::ED25519,
+                _ => return Err(TlsError::UnsupportedCertificate),
+            };
+
+        signature::verify(
+            verification,
+            untrusted::Input::from(public_key.key()),
+            untrusted::Input::from(message),
+            untrusted::Input::from(signature),
+        )
+        .map_err(|_| TlsError::BadCertificate)
+    }
+}
+
+/// Signs with PKCS#8 ECDSA and RSA keys.
+pub struct RingSigner;
+
+impl Signer for RingSigner {
+    fn sign(
+        &self,
+        key: &PrivateKey<'_>,
+        scheme: SignatureScheme,
+        message: &[u8],
+        out: &mut [u8],
+    ) -> TlsResult<usize> {
+        let rng = SystemRandom::new();
+        let der = untrusted::Input::from(key.as_bytes());
+        let failed = |_| TlsError::InternalError("signing failed");
+
+        let ecdsa = match scheme {
+            SignatureScheme::EcdsaNistp256Sha256 => &signature::ECDSA_P256_SHA256_ASN1_SIGNING,
+            SignatureScheme::EcdsaNistp384Sha384 => &signature::ECDSA_P384_SHA384_ASN1_SIGNING,
+            _ => {
+                let encoding: &'static dyn signature::RsaEncoding = match scheme {
+                    SignatureScheme::RsaPssSha256 => &signature::RSA_PSS_SHA256,
+                    SignatureScheme::RsaPssSha384 => &signature::RSA_PSS_SHA384,
+                    SignatureScheme::RsaPssSha512 => &signature::RSA_PSS_SHA512,
+                    _ => return Err(TlsError::InternalError("unsupported scheme")),
+                };
+                let key_pair = signature::RsaKeyPair::from_pkcs8(der)
+                    .map_err(|_| TlsError::InternalError("bad RSA key"))?;
+                let len = key_pair.public_modulus_len();
+                key_pair
+                    .sign(encoding, &rng, message, &mut out[..len])
+                    .map_err(failed)?;
+                return Ok(len);
+            }
+        };
+
+        let key_pair = signature::EcdsaKeyPair::from_pkcs8(ecdsa, der)
+            .map_err(|_| TlsError::InternalError("bad ECDSA key"))?;
+        let signature = key_pair
+            .sign(&rng, untrusted::Input::from(message))
+            .map_err(failed)?;
+        out[..signature.as_ref().len()].copy_from_slice(signature.as_ref());
+        Ok(signature.as_ref().len())
+    }
+}
+
+/// Decodes the first section of a PEM file into `out`.
+pub fn pem<'b>(file: &[u8], out: &'b mut [u8]) -> &'b [u8] {
+    let section = PemSections::new(file).next().unwrap().unwrap();
+    section.decode(out).unwrap()
+}
@@ -0,0 +1,111 @@
+//! Sources of the current time, which certificate validity and expiry are checked against.
+//!
+//! The crate has no clock of its own, so one is supplied through [`TimeSource`]. Devices without
+//! one can opt out of time checks with [`DangerousSkipTimeChecks`].
+
+use crate::error::Result as TlsResult;
+
+/// Time to check validity periods against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Now {
+    /// Seconds since the Unix epoch.
+    UnixTime(u64),
+    /// Validity periods aren't checked, so expired and not yet valid credentials are accepted.
+    DangerousSkipTimeChecks,
+}
+
+impl Now {
+    /// Whether the time is within `not_before` to `not_after`, inclusive. Always true when time
+    /// checks are skipped.
+    pub fn is_within(self, not_before: u64, not_after: u64) -> bool {
+        match self {
+            Now::UnixTime(now) => not_before <= now && now <= not_after,
+            Now::DangerousSkipTimeChecks => true,
+        }
+    }
+}
+
+/// Clock supplied by the platform.
+pub trait TimeSource {
+    /// Reads the current time, failing if the clock can't be read.
+    fn now(&self) -> TlsResult<Now>;
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for &T {
+    fn now(&self) -> TlsResult<Now> {
+        (**self).now()
+    }
+}
+
+/// Time source that is always at the same time, in seconds since the Unix epoch.
+///
+/// Useful for tests, or for checks against a time the caller already has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedTime(pub u64);
+
+impl TimeSource for FixedTime {
+    fn now(&self) -> TlsResult<Now> {
+        Ok(Now::UnixTime(self.0))
+    }
+}
+
+/// Time source for devices without a real time clock, which skips every time check.
+///
+/// Certificates are then accepted however long ago they expired, so this should only be used
+/// when there is no way of knowing the time and the risk has been accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DangerousSkipTimeChecks;
+
+impl TimeSource for DangerousSkipTimeChecks {
+    fn now(&self) -> TlsResult<Now> {
+        Ok(Now::DangerousSkipTimeChecks)
+    }
+}
+
+/// Time source reading the system clock.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SystemTime;
+
+#[cfg(feature = "std")]
+impl TimeSource for SystemTime {
+    fn now(&self) -> TlsResult<Now> {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|since| Now::UnixTime(since.as_secs()))
+            .map_err(|_| crate::error::Error::InternalError("system time before Unix epoch"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_time() {
+        assert_eq!(FixedTime(10).now().unwrap(), Now::UnixTime(10));
+        assert!(Now::UnixTime(10).is_within(10, 20));
+        assert!(Now::UnixTime(20).is_within(10, 20));
+        assert!(!Now::UnixTime(9).is_within(10, 20));
+        assert!(!Now::UnixTime(21).is_within(10, 20));
+    }
+
+    #[test]
+    fn skip_time_checks() {
+        let now = DangerousSkipTimeChecks.now().unwrap();
+
+        assert_eq!(now, Now::DangerousSkipTimeChecks);
+        assert!(now.is_within(10, 20));
+        assert!(now.is_within(u64::MAX, 0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn system_time() {
+        // 2020-01-01, before this was written.
+        match SystemTime.now().unwrap() {
+            Now::UnixTime(now) => assert!(now > 1_577_836_800),
+            now => panic!("unexpected time {:?}", now),
+        }
+    }
+}
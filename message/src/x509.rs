@@ -0,0 +1,12 @@
+//! X.509 certificates (RFC 5280), read in place from their DER encoding, and validation of the
+//! certification path a peer presents. Also reads the PEM files and private keys that
+//! certificates are usually stored with.
+
+pub mod certificate;
+pub mod der;
+pub mod identity;
+pub mod key;
+pub mod path;
+pub mod pem;
+pub mod pinning;
+pub mod verifier;
@@ -0,0 +1,886 @@
+use crate::{
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::enums::{HashAlgorithm, NamedGroup, SignatureAlgorithm, SignatureScheme},
+    time::Now,
+    x509::der::{tag, Der},
+};
+
+/// Object identifiers, as the contents of their DER encoding.
+mod oid {
+    pub const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+    pub const ECDSA_WITH_SHA384: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+    pub const ECDSA_WITH_SHA512: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04];
+    pub const SHA256_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+    pub const SHA384_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c];
+    pub const SHA512_WITH_RSA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d];
+    pub const ED25519: &[u8] = &[0x2b, 0x65, 0x70];
+
+    pub const RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+    pub const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+    pub const SECP256R1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+    pub const SECP384R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+    pub const SECP521R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x23];
+
+    pub const BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+    pub const KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x0f];
+    pub const EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25];
+    pub const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+    pub const ANY_EXTENDED_KEY_USAGE: &[u8] = &[0x55, 0x1d, 0x25, 0x00];
+    pub const SERVER_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+    pub const CLIENT_AUTH: &[u8] = &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
+}
+
+/// X.509 v3 certificate, read in place from its DER encoding (RFC 5280, section 4.1).
+#[derive(Debug, Clone, Copy)]
+pub struct Certificate<'a> {
+    tbs: &'a [u8],
+    signature_algorithm: SignatureAlgorithmIdentifier,
+    signature: &'a [u8],
+    issuer: &'a [u8],
+    subject: &'a [u8],
+    validity: Validity,
+    public_key: SubjectPublicKeyInfo<'a>,
+    basic_constraints: Option<BasicConstraints>,
+    key_usage: Option<KeyUsage>,
+    extended_key_usage: Option<ExtendedKeyUsage<'a>>,
+    subject_alt_name: Option<SubjectAltName<'a>>,
+}
+
+impl<'a> Certificate<'a> {
+    /// Reads the certificate encoded in `der`.
+    ///
+    /// Malformed certificates fail with [`TlsError::BadCertificate`], and ones using algorithms,
+    /// versions or critical extensions that aren't supported with
+    /// [`TlsError::UnsupportedCertificate`].
+    pub fn parse(der: &'a [u8]) -> TlsResult<Self> {
+        let mut outer = Der::new(der);
+        let mut cert = outer.sub(tag::SEQUENCE).ok_or(TlsError::BadCertificate)?;
+        if !outer.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+
+        let tbs = cert
+            .take_encoded(tag::SEQUENCE)
+            .ok_or(TlsError::BadCertificate)?;
+        let signature_algorithm = cert
+            .take_encoded(tag::SEQUENCE)
+            .ok_or(TlsError::BadCertificate)?;
+        let signature = cert.bit_string().ok_or(TlsError::BadCertificate)?;
+        if !cert.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+
+        let mut parsed = Self::parse_tbs(tbs, signature_algorithm)?;
+        parsed.signature = signature;
+        Ok(parsed)
+    }
+
+    /// Encoding of the tbsCertificate, which the signature is over.
+    pub fn tbs(&self) -> &'a [u8] {
+        self.tbs
+    }
+
+    pub fn signature_algorithm(&self) -> SignatureAlgorithmIdentifier {
+        self.signature_algorithm
+    }
+
+    pub fn signature(&self) -> &'a [u8] {
+        self.signature
+    }
+
+    /// Encoding of the issuer's distinguished name.
+    pub fn issuer(&self) -> &'a [u8] {
+        self.issuer
+    }
+
+    /// Encoding of the subject's distinguished name.
+    pub fn subject(&self) -> &'a [u8] {
+        self.subject
+    }
+
+    pub fn validity(&self) -> Validity {
+        self.validity
+    }
+
+    pub fn public_key(&self) -> &SubjectPublicKeyInfo<'a> {
+        &self.public_key
+    }
+
+    pub fn basic_constraints(&self) -> Option<BasicConstraints> {
+        self.basic_constraints
+    }
+
+    pub fn key_usage(&self) -> Option<KeyUsage> {
+        self.key_usage
+    }
+
+    pub fn extended_key_usage(&self) -> Option<ExtendedKeyUsage<'a>> {
+        self.extended_key_usage
+    }
+
+    pub fn subject_alt_name(&self) -> Option<SubjectAltName<'a>> {
+        self.subject_alt_name
+    }
+
+    fn parse_tbs(tbs: &'a [u8], outer_algorithm: &'a [u8]) -> TlsResult<Self> {
+        let mut tbs_der = Der::new(tbs);
+        let mut der = tbs_der.sub(tag::SEQUENCE).ok_or(TlsError::BadCertificate)?;
+
+        // Only v3 certificates (version 2) have extensions.
+        let mut version = der
+            .sub(tag::context(0))
+            .ok_or(TlsError::UnsupportedCertificate)?;
+        if version.small_uint() != Some(2) || !version.is_complete() {
+            return Err(TlsError::UnsupportedCertificate);
+        }
+        der.take(tag::INTEGER).ok_or(TlsError::BadCertificate)?;
+
+        // The algorithm inside the signed data must match the one outside it.
+        let algorithm = der
+            .take_encoded(tag::SEQUENCE)
+            .ok_or(TlsError::BadCertificate)?;
+        if algorithm != outer_algorithm {
+            return Err(TlsError::BadCertificate);
+        }
+        let signature_algorithm = SignatureAlgorithmIdentifier::parse(algorithm)?;
+
+        let issuer = der
+            .take_encoded(tag::SEQUENCE)
+            .ok_or(TlsError::BadCertificate)?;
+        let validity = Validity::parse(&mut der).ok_or(TlsError::BadCertificate)?;
+        let subject = der
+            .take_encoded(tag::SEQUENCE)
+            .ok_or(TlsError::BadCertificate)?;
+        let public_key = der
+            .take_encoded(tag::SEQUENCE)
+            .and_then(SubjectPublicKeyInfo::parse)
+            .ok_or(TlsError::BadCertificate)?;
+
+        // Unique identifiers are obsolete and ignored.
+        for n in 1..=2 {
+            if der.peek_tag() == Some(tag::context_primitive(n)) {
+                der.read().ok_or(TlsError::BadCertificate)?;
+            }
+        }
+
+        let mut cert = Certificate {
+            tbs,
+            signature_algorithm,
+            signature: &[],
+            issuer,
+            subject,
+            validity,
+            public_key,
+            basic_constraints: None,
+            key_usage: None,
+            extended_key_usage: None,
+            subject_alt_name: None,
+        };
+        if let Some(extensions) = der
+            .take_optional(tag::context(3))
+            .ok_or(TlsError::BadCertificate)?
+        {
+            cert.parse_extensions(extensions)?;
+        }
+
+        if !der.is_complete() || !tbs_der.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+        Ok(cert)
+    }
+
+    fn parse_extensions(&mut self, extensions: &'a [u8]) -> TlsResult<()> {
+        let mut outer = Der::new(extensions);
+        let mut der = outer.sub(tag::SEQUENCE).ok_or(TlsError::BadCertificate)?;
+        if !outer.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+
+        while !der.is_complete() {
+            let mut extension = der.sub(tag::SEQUENCE).ok_or(TlsError::BadCertificate)?;
+            let id = extension.take(tag::OID).ok_or(TlsError::BadCertificate)?;
+            let critical = match extension.peek_tag() {
+                Some(tag::BOOLEAN) => extension.boolean().ok_or(TlsError::BadCertificate)?,
+                _ => false,
+            };
+            let value = extension
+                .take(tag::OCTET_STRING)
+                .ok_or(TlsError::BadCertificate)?;
+            if !extension.is_complete() {
+                return Err(TlsError::BadCertificate);
+            }
+
+            match id {
+                oid::BASIC_CONSTRAINTS if self.basic_constraints.is_none() => {
+                    self.basic_constraints = Some(BasicConstraints::parse(value)?);
+                }
+                oid::KEY_USAGE if self.key_usage.is_none() => {
+                    self.key_usage = Some(KeyUsage::parse(value)?);
+                }
+                oid::EXTENDED_KEY_USAGE if self.extended_key_usage.is_none() => {
+                    self.extended_key_usage = Some(ExtendedKeyUsage::parse(value)?);
+                }
+                oid::SUBJECT_ALT_NAME if self.subject_alt_name.is_none() => {
+                    self.subject_alt_name = Some(SubjectAltName::parse(value)?);
+                }
+                // Each extension may only appear once (RFC 5280, section 4.2).
+                oid::BASIC_CONSTRAINTS
+                | oid::KEY_USAGE
+                | oid::EXTENDED_KEY_USAGE
+                | oid::SUBJECT_ALT_NAME => return Err(TlsError::BadCertificate),
+                // Extensions that change how the certificate must be validated can't be ignored.
+                _ if critical => return Err(TlsError::UnsupportedCertificate),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Algorithm a certificate is signed with, from its AlgorithmIdentifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureAlgorithmIdentifier {
+    pub algorithm: SignatureAlgorithm,
+    /// Hash the signed data is hashed with, or `None` for EdDSA which hashes internally.
+    pub hash: Option<HashAlgorithm>,
+}
+
+impl SignatureAlgorithmIdentifier {
+    fn parse(encoded: &[u8]) -> TlsResult<Self> {
+        use self::{HashAlgorithm::*, SignatureAlgorithm::*};
+
+        let mut outer = Der::new(encoded);
+        let mut der = outer.sub(tag::SEQUENCE).ok_or(TlsError::BadCertificate)?;
+        let id = der.take(tag::OID).ok_or(TlsError::BadCertificate)?;
+
+        let (algorithm, hash) = match id {
+            oid::ECDSA_WITH_SHA256 => (Ecdsa, Some(Sha256)),
+            oid::ECDSA_WITH_SHA384 => (Ecdsa, Some(Sha384)),
+            oid::ECDSA_WITH_SHA512 => (Ecdsa, Some(Sha512)),
+            oid::SHA256_WITH_RSA => (RsaPkcs1, Some(Sha256)),
+            oid::SHA384_WITH_RSA => (RsaPkcs1, Some(Sha384)),
+            oid::SHA512_WITH_RSA => (RsaPkcs1, Some(Sha512)),
+            oid::ED25519 => (Ed25519, None),
+            _ => return Err(TlsError::UnsupportedCertificate),
+        };
+
+        // RSA algorithms have NULL parameters, which some encoders leave out (RFC 4055,
+        // section 5). The others have none.
+        if algorithm == RsaPkcs1 && der.peek_tag() == Some(tag::NULL) {
+            der.take(tag::NULL)
+                .filter(|params| params.is_empty())
+                .ok_or(TlsError::BadCertificate)?;
+        }
+        if !der.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+        Ok(Self { algorithm, hash })
+    }
+}
+
+/// Algorithm of a certificate's public key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublicKeyAlgorithm {
+    Rsa,
+    /// Elliptic curve key on the given curve.
+    Ec(NamedGroup),
+    Ed25519,
+    Unknown,
+}
+
+impl PublicKeyAlgorithm {
+    /// Reads the algorithm from the contents of an AlgorithmIdentifier, leaving any parameters
+    /// other than an elliptic curve unread.
+    pub(crate) fn parse(algorithm_id: &mut Der<'_>) -> Option<Self> {
+        let algorithm = match algorithm_id.take(tag::OID)? {
+            oid::RSA_ENCRYPTION => PublicKeyAlgorithm::Rsa,
+            oid::EC_PUBLIC_KEY => match algorithm_id.take_optional(tag::OID)? {
+                Some(curve) => PublicKeyAlgorithm::from_curve(curve),
+                None => PublicKeyAlgorithm::Unknown,
+            },
+            oid::ED25519 => PublicKeyAlgorithm::Ed25519,
+            _ => PublicKeyAlgorithm::Unknown,
+        };
+        Some(algorithm)
+    }
+
+    /// Whether a key of this algorithm signs with `scheme`. Elliptic curve keys only sign with
+    /// the ECDSA scheme of their curve.
+    pub fn supports(self, scheme: SignatureScheme) -> bool {
+        use self::SignatureScheme::*;
+
+        match self {
+            PublicKeyAlgorithm::Rsa => matches!(
+                scheme,
+                RsaPkcs1Sha256
+                    | RsaPkcs1Sha384
+                    | RsaPkcs1Sha512
+                    | RsaPssSha256
+                    | RsaPssSha384
+                    | RsaPssSha512
+            ),
+            PublicKeyAlgorithm::Ec(NamedGroup::Secp256r1) => scheme == EcdsaNistp256Sha256,
+            PublicKeyAlgorithm::Ec(NamedGroup::Secp384r1) => scheme == EcdsaNistp384Sha384,
+            PublicKeyAlgorithm::Ec(NamedGroup::Secp521r1) => scheme == EcdsaNistp521Sha512,
+            PublicKeyAlgorithm::Ed25519 => scheme == Ed25519,
+            _ => false,
+        }
+    }
+
+    /// Elliptic curve key on the named curve with object identifier `curve`.
+    pub(crate) fn from_curve(curve: &[u8]) -> Self {
+        match curve {
+            oid::SECP256R1 => PublicKeyAlgorithm::Ec(NamedGroup::Secp256r1),
+            oid::SECP384R1 => PublicKeyAlgorithm::Ec(NamedGroup::Secp384r1),
+            oid::SECP521R1 => PublicKeyAlgorithm::Ec(NamedGroup::Secp521r1),
+            _ => PublicKeyAlgorithm::Unknown,
+        }
+    }
+}
+
+/// Public key of a certificate's subject.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubjectPublicKeyInfo<'a> {
+    encoded: &'a [u8],
+    algorithm: PublicKeyAlgorithm,
+    key: &'a [u8],
+}
+
+impl<'a> SubjectPublicKeyInfo<'a> {
+    /// Whole encoding of the SubjectPublicKeyInfo.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.encoded
+    }
+
+    pub fn algorithm(&self) -> PublicKeyAlgorithm {
+        self.algorithm
+    }
+
+    /// Contents of the subjectPublicKey, such as an RSAPublicKey or an uncompressed point.
+    pub fn key(&self) -> &'a [u8] {
+        self.key
+    }
+
+    /// Parses a DER encoded SubjectPublicKeyInfo, such as one kept from a certificate.
+    pub fn parse(encoded: &'a [u8]) -> Option<Self> {
+        let mut outer = Der::new(encoded);
+        let mut der = outer.sub(tag::SEQUENCE)?;
+        let mut algorithm_id = der.sub(tag::SEQUENCE)?;
+        let key = der.bit_string()?;
+        if !der.is_complete() {
+            return None;
+        }
+
+        let algorithm = PublicKeyAlgorithm::parse(&mut algorithm_id)?;
+
+        Some(Self {
+            encoded,
+            algorithm,
+            key,
+        })
+    }
+}
+
+/// Period a certificate is valid for, in seconds since the Unix epoch, inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Validity {
+    pub not_before: u64,
+    pub not_after: u64,
+}
+
+impl Validity {
+    pub fn contains(&self, now: Now) -> bool {
+        now.is_within(self.not_before, self.not_after)
+    }
+
+    fn parse(der: &mut Der<'_>) -> Option<Self> {
+        let mut validity = der.sub(tag::SEQUENCE)?;
+        let not_before = parse_time(&mut validity)?;
+        let not_after = parse_time(&mut validity)?;
+        if !validity.is_complete() {
+            return None;
+        }
+
+        Some(Self {
+            not_before,
+            not_after,
+        })
+    }
+}
+
+/// Whether the subject is a CA, and how many intermediate CAs may follow it in a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BasicConstraints {
+    pub ca: bool,
+    pub path_len: Option<u8>,
+}
+
+impl BasicConstraints {
+    fn parse(value: &[u8]) -> TlsResult<Self> {
+        let mut outer = Der::new(value);
+        let mut der = outer.sub(tag::SEQUENCE).ok_or(TlsError::BadCertificate)?;
+
+        let ca = match der.peek_tag() {
+            Some(tag::BOOLEAN) => der.boolean().ok_or(TlsError::BadCertificate)?,
+            _ => false,
+        };
+        let path_len = match der.peek_tag() {
+            Some(tag::INTEGER) => Some(der.small_uint().ok_or(TlsError::BadCertificate)?),
+            _ => None,
+        };
+
+        if !der.is_complete() || !outer.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+        Ok(Self { ca, path_len })
+    }
+}
+
+/// Purposes the subject's key may be used for (RFC 5280, section 4.2.1.3).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyUsage(u16);
+
+impl KeyUsage {
+    pub const DIGITAL_SIGNATURE: KeyUsage = KeyUsage(1 << 0);
+    pub const KEY_CERT_SIGN: KeyUsage = KeyUsage(1 << 5);
+
+    pub fn contains(self, usage: KeyUsage) -> bool {
+        self.0 & usage.0 == usage.0
+    }
+
+    fn parse(value: &[u8]) -> TlsResult<Self> {
+        let mut der = Der::new(value);
+        let bits = der.take(tag::BIT_STRING).ok_or(TlsError::BadCertificate)?;
+        if !der.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+
+        // The first bit is the most significant bit of the first byte after the count of
+        // unused bits.
+        match *bits {
+            [unused, ref bytes @ ..] if unused < 8 && bytes.len() <= 2 => {
+                let usage = bytes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| u16::from(b.reverse_bits()) << (8 * i))
+                    .fold(0, |usage, bits| usage | bits);
+                Ok(Self(usage))
+            }
+            _ => Err(TlsError::BadCertificate),
+        }
+    }
+}
+
+/// What a certificate is used to authenticate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPurpose {
+    ServerAuth,
+    ClientAuth,
+}
+
+/// Purposes the subject's key may be used for, in addition to or in place of its key usage
+/// (RFC 5280, section 4.2.1.12).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedKeyUsage<'a>(&'a [u8]);
+
+impl<'a> ExtendedKeyUsage<'a> {
+    /// Whether `purpose`, or any purpose, is listed.
+    pub fn permits(&self, purpose: KeyPurpose) -> bool {
+        let wanted = match purpose {
+            KeyPurpose::ServerAuth => oid::SERVER_AUTH,
+            KeyPurpose::ClientAuth => oid::CLIENT_AUTH,
+        };
+
+        let mut der = Der::new(self.0);
+        while let Some(id) = der.take(tag::OID) {
+            if id == wanted || id == oid::ANY_EXTENDED_KEY_USAGE {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn parse(value: &'a [u8]) -> TlsResult<Self> {
+        let mut outer = Der::new(value);
+        let purposes = outer.take(tag::SEQUENCE).ok_or(TlsError::BadCertificate)?;
+
+        let mut der = Der::new(purposes);
+        while !der.is_complete() {
+            der.take(tag::OID).ok_or(TlsError::BadCertificate)?;
+        }
+        if purposes.is_empty() || !outer.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+        Ok(Self(purposes))
+    }
+}
+
+/// Identities bound to the subject, in addition to or in place of its name (RFC 5280, section
+/// 4.2.1.6).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubjectAltName<'a>(&'a [u8]);
+
+impl<'a> SubjectAltName<'a> {
+    pub fn names(&self) -> GeneralNames<'a> {
+        GeneralNames(Der::new(self.0))
+    }
+
+    pub(crate) fn parse(value: &'a [u8]) -> TlsResult<Self> {
+        let mut outer = Der::new(value);
+        let names = outer.take(tag::SEQUENCE).ok_or(TlsError::BadCertificate)?;
+        if names.is_empty() || !outer.is_complete() {
+            return Err(TlsError::BadCertificate);
+        }
+
+        let mut der = Der::new(names);
+        while !der.is_complete() {
+            let (name_tag, name) = der.read().ok_or(TlsError::BadCertificate)?;
+            if GeneralName::from_der(name_tag, name).is_none() {
+                return Err(TlsError::BadCertificate);
+            }
+        }
+        Ok(Self(names))
+    }
+}
+
+/// Name in a subject alternative name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneralName<'a> {
+    /// DNS name, which is ASCII but may not be a valid hostname.
+    DnsName(&'a [u8]),
+    /// IPv4 or IPv6 address, as 4 or 16 bytes in network order.
+    IpAddress(&'a [u8]),
+    /// Any other form of name, which isn't read.
+    Other,
+}
+
+impl<'a> GeneralName<'a> {
+    const DNS_NAME: u8 = tag::context_primitive(2);
+    const IP_ADDRESS: u8 = tag::context_primitive(7);
+
+    fn from_der(name_tag: u8, name: &'a [u8]) -> Option<Self> {
+        match name_tag {
+            Self::DNS_NAME if name.is_ascii() => Some(GeneralName::DnsName(name)),
+            Self::IP_ADDRESS if name.len() == 4 || name.len() == 16 => {
+                Some(GeneralName::IpAddress(name))
+            }
+            Self::DNS_NAME | Self::IP_ADDRESS => None,
+            _ => Some(GeneralName::Other),
+        }
+    }
+}
+
+/// Iterator over the names of a [`SubjectAltName`].
+pub struct GeneralNames<'a>(Der<'a>);
+
+impl<'a> Iterator for GeneralNames<'a> {
+    type Item = GeneralName<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The names were checked when the extension was parsed.
+        let (name_tag, name) = self.0.read()?;
+        GeneralName::from_der(name_tag, name)
+    }
+}
+
+/// Reads a UTCTime or GeneralizedTime in the forms RFC 5280 allows (section 4.1.2.5), as
+/// seconds since the Unix epoch. Times before the epoch are clamped to it.
+fn parse_time(der: &mut Der<'_>) -> Option<u64> {
+    let (year, rest) = match der.read()? {
+        (tag::UTC_TIME, time) if time.len() == 13 => {
+            let year = digits(&time[..2])?;
+            // Two digit years from 50 are in the 1900s.
+            let century = if year >= 50 { 1900 } else { 2000 };
+            (century + year, &time[2..])
+        }
+        (tag::GENERALIZED_TIME, time) if time.len() == 15 => (digits(&time[..4])?, &time[4..]),
+        _ => return None,
+    };
+    if rest[10] != b'Z' {
+        return None;
+    }
+
+    let month = digits(&rest[0..2])?;
+    let day = digits(&rest[2..4])?;
+    let hour = digits(&rest[4..6])?;
+    let minute = digits(&rest[6..8])?;
+    let second = digits(&rest[8..10])?;
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    if year < 1970 {
+        return Some(0);
+    }
+    let days = days_since_epoch(year, month, day);
+    Some(((days * 24 + hour) * 60 + minute) * 60 + second)
+}
+
+fn digits(bytes: &[u8]) -> Option<u64> {
+    bytes.iter().try_fold(0, |n, b| match b {
+        b'0'..=b'9' => Some(n * 10 + u64::from(b - b'0')),
+        _ => None,
+    })
+}
+
+fn is_leap_year(year: u64) -> bool {
+    year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400))
+}
+
+fn days_in_month(year: u64, month: u64) -> u64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to the given date, which must not be before it.
+fn days_since_epoch(year: u64, month: u64, day: u64) -> u64 {
+    let year_days = (1970..year)
+        .map(|y| if is_leap_year(y) { 366 } else { 365 })
+        .sum::<u64>();
+    let month_days = (1..month).map(|m| days_in_month(year, m)).sum::<u64>();
+    year_days + month_days + day - 1
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use rustls::internal::pemfile;
+    use std::{fs::File, io::BufReader, vec::Vec};
+
+    /// DER encoded certificates in a PEM file of the test keys.
+    pub fn test_certs(path: &str) -> Vec<Vec<u8>> {
+        let file = File::open(std::format!("../test-keys/{}", path)).unwrap();
+        pemfile::certs(&mut BufReader::new(file))
+            .unwrap()
+            .into_iter()
+            .map(|cert| cert.0)
+            .collect()
+    }
+
+    fn time(encoded: &[u8]) -> Option<u64> {
+        parse_time(&mut Der::new(encoded))
+    }
+
+    #[test]
+    fn ecdsa_end_entity() {
+        let der = &test_certs("ecdsa/end.cert")[0];
+        let cert = Certificate::parse(der).unwrap();
+
+        assert_eq!(
+            cert.signature_algorithm(),
+            SignatureAlgorithmIdentifier {
+                algorithm: SignatureAlgorithm::Ecdsa,
+                hash: Some(HashAlgorithm::Sha256),
+            }
+        );
+        assert_eq!(
+            cert.public_key().algorithm(),
+            PublicKeyAlgorithm::Ec(NamedGroup::Secp256r1)
+        );
+        assert_eq!(cert.public_key().key().len(), 65);
+        // 2017-12-27 17:19:45 to 2023-06-19 17:19:45.
+        assert_eq!(
+            cert.validity(),
+            Validity {
+                not_before: 1_514_395_185,
+                not_after: 1_687_195_185,
+            }
+        );
+        assert_eq!(
+            cert.basic_constraints(),
+            Some(BasicConstraints {
+                ca: false,
+                path_len: None,
+            })
+        );
+        assert!(cert
+            .key_usage()
+            .unwrap()
+            .contains(KeyUsage::DIGITAL_SIGNATURE));
+        assert!(!cert.key_usage().unwrap().contains(KeyUsage::KEY_CERT_SIGN));
+        assert!(cert.extended_key_usage().is_none());
+        assert_eq!(
+            cert.subject_alt_name().unwrap().names().collect::<Vec<_>>(),
+            [
+                GeneralName::DnsName(b"testserver.com"),
+                GeneralName::DnsName(b"second.testserver.com"),
+                GeneralName::DnsName(b"localhost"),
+            ]
+        );
+        assert!(cert.subject().windows(14).any(|w| w == b"testserver.com"));
+    }
+
+    #[test]
+    fn intermediate() {
+        let der = &test_certs("ecdsa/end.chain")[0];
+        let cert = Certificate::parse(der).unwrap();
+
+        assert_eq!(cert.basic_constraints().map(|bc| bc.ca), Some(true));
+        assert!(cert.key_usage().unwrap().contains(KeyUsage::KEY_CERT_SIGN));
+        let eku = cert.extended_key_usage().unwrap();
+        assert!(eku.permits(KeyPurpose::ServerAuth));
+        assert!(eku.permits(KeyPurpose::ClientAuth));
+    }
+
+    #[test]
+    fn client_end_entity() {
+        let der = &test_certs("ecdsa/client.cert")[0];
+        let eku = Certificate::parse(der)
+            .unwrap()
+            .extended_key_usage()
+            .unwrap();
+
+        assert!(eku.permits(KeyPurpose::ClientAuth));
+        assert!(!eku.permits(KeyPurpose::ServerAuth));
+    }
+
+    #[test]
+    fn rsa() {
+        let der = &test_certs("rsa/ca.cert")[0];
+        let cert = Certificate::parse(der).unwrap();
+
+        assert_eq!(cert.public_key().algorithm(), PublicKeyAlgorithm::Rsa);
+        assert_eq!(
+            cert.signature_algorithm(),
+            SignatureAlgorithmIdentifier {
+                algorithm: SignatureAlgorithm::RsaPkcs1,
+                hash: Some(HashAlgorithm::Sha256),
+            }
+        );
+        assert_eq!(cert.issuer(), cert.subject());
+    }
+
+    #[test]
+    fn unknown_extensions() {
+        let der = &test_certs("ecdsa/end.cert")[0];
+        let mut cert = Certificate::parse(der).unwrap();
+        // Extension 1.2.3 with an empty value, then marked critical.
+        let extension = [0x30, 0x08, 0x30, 0x06, 0x06, 0x02, 0x2a, 0x03, 0x04, 0x00];
+        let critical = [
+            0x30, 0x0b, 0x30, 0x09, 0x06, 0x02, 0x2a, 0x03, 0x01, 0x01, 0xff, 0x04, 0x00,
+        ];
+
+        assert!(cert.parse_extensions(&extension).is_ok());
+        assert!(matches!(
+            cert.parse_extensions(&critical),
+            Err(TlsError::UnsupportedCertificate)
+        ));
+    }
+
+    #[test]
+    fn duplicate_extension() {
+        let der = &test_certs("ecdsa/end.cert")[0];
+        let mut cert = Certificate::parse(der).unwrap();
+        let extension = [
+            0x30, 0x0b, 0x30, 0x09, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x04, 0x02, 0x30, 0x00,
+        ];
+
+        assert!(matches!(
+            cert.parse_extensions(&extension),
+            Err(TlsError::BadCertificate)
+        ));
+    }
+
+    #[test]
+    fn general_names() {
+        // DNS name, IPv4 address and a URI.
+        let san = [
+            0x30, 0x10, 0x82, 0x03, b'a', b'.', b'b', 0x87, 0x04, 127, 0, 0, 1, 0x86, 0x03, b'x',
+            b':', b'y',
+        ];
+
+        assert_eq!(
+            SubjectAltName::parse(&san)
+                .unwrap()
+                .names()
+                .collect::<Vec<_>>(),
+            [
+                GeneralName::DnsName(b"a.b"),
+                GeneralName::IpAddress(&[127, 0, 0, 1]),
+                GeneralName::Other,
+            ]
+        );
+        assert!(SubjectAltName::parse(&[0x30, 0x00]).is_err());
+        assert!(SubjectAltName::parse(&[0x30, 0x03, 0x87, 0x01, 0x00]).is_err());
+        assert!(SubjectAltName::parse(&[0x30, 0x03, 0x82, 0x01, 0xff]).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let der = &test_certs("ecdsa/end.cert")[0];
+
+        assert!(matches!(
+            Certificate::parse(&der[..der.len() - 1]),
+            Err(TlsError::BadCertificate)
+        ));
+        assert!(matches!(
+            Certificate::parse(&[der.as_slice(), &[0]].concat()),
+            Err(TlsError::BadCertificate)
+        ));
+    }
+
+    #[test]
+    fn utc_time() {
+        assert_eq!(time(b"\x17\x0d700101000000Z"), Some(0));
+        assert_eq!(time(b"\x17\x0d491231235959Z"), Some(2_524_607_999));
+        assert_eq!(time(b"\x17\x0d500101000000Z"), Some(0));
+        assert_eq!(time(b"\x17\x0d000229120000Z"), Some(951_825_600));
+    }
+
+    #[test]
+    fn generalized_time() {
+        assert_eq!(time(b"\x18\x0f20500101000000Z"), Some(2_524_608_000));
+    }
+
+    #[test]
+    fn invalid_time() {
+        assert_eq!(time(b"\x17\x0d010229000000Z"), None);
+        assert_eq!(time(b"\x17\x0d701301000000Z"), None);
+        assert_eq!(time(b"\x17\x0d700101240000Z"), None);
+        assert_eq!(time(b"\x17\x0d700101000000+"), None);
+        assert_eq!(time(b"\x17\x0b7001010000Z"), None);
+        assert_eq!(time(b"\x18\x0d700101000000Z"), None);
+    }
+
+    #[test]
+    fn key_usage_bits() {
+        // digitalSignature and keyCertSign, with two unused bits.
+        let usage = KeyUsage::parse(&[0x03, 0x02, 0x02, 0x84]).unwrap();
+
+        assert!(usage.contains(KeyUsage::DIGITAL_SIGNATURE));
+        assert!(usage.contains(KeyUsage::KEY_CERT_SIGN));
+        assert!(!KeyUsage::parse(&[0x03, 0x02, 0x07, 0x80])
+            .unwrap()
+            .contains(KeyUsage::KEY_CERT_SIGN));
+    }
+
+    #[test]
+    fn basic_constraints() {
+        assert_eq!(
+            BasicConstraints::parse(&[0x30, 0x06, 0x01, 0x01, 0xff, 0x02, 0x01, 0x00]).unwrap(),
+            BasicConstraints {
+                ca: true,
+                path_len: Some(0),
+            }
+        );
+        assert_eq!(
+            BasicConstraints::parse(&[0x30, 0x00]).unwrap(),
+            BasicConstraints {
+                ca: false,
+                path_len: None,
+            }
+        );
+        assert!(BasicConstraints::parse(&[0x30, 0x03, 0x05, 0x01, 0x00]).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::codec::Decoder;
+
+/// Tags of the DER values found in certificates.
+pub mod tag {
+    pub const BOOLEAN: u8 = 0x01;
+    pub const INTEGER: u8 = 0x02;
+    pub const BIT_STRING: u8 = 0x03;
+    pub const OCTET_STRING: u8 = 0x04;
+    pub const NULL: u8 = 0x05;
+    pub const OID: u8 = 0x06;
+    pub const UTC_TIME: u8 = 0x17;
+    pub const GENERALIZED_TIME: u8 = 0x18;
+    pub const SEQUENCE: u8 = 0x30;
+    pub const SET: u8 = 0x31;
+
+    /// Constructed, context specific tag `[n]`, as used for EXPLICIT tagging.
+    pub const fn context(n: u8) -> u8 {
+        0xa0 | n
+    }
+
+    /// Primitive, context specific tag `[n]`, as used for IMPLICIT tagging of primitive values.
+    pub const fn context_primitive(n: u8) -> u8 {
+        0x80 | n
+    }
+}
+
+/// Reader of DER encoded values (ITU-T X.690), borrowing from the encoding.
+///
+/// As with [`Decoder`], reading returns `None` when the encoding is malformed or isn't what was
+/// asked for, and the reader shouldn't be used after that.
+pub struct Der<'a>(Decoder<'a>);
+
+impl<'a> Der<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self(Decoder::new(bytes))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.0.is_complete()
+    }
+
+    /// Tag of the next value, without consuming it.
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.0.peek(1).map(|tag| tag[0])
+    }
+
+    /// Reads the next value, returning its tag and contents.
+    pub fn read(&mut self) -> Option<(u8, &'a [u8])> {
+        let (tag, contents, _) = self.read_tlv()?;
+        Some((tag, contents))
+    }
+
+    /// Reads the next value, which must have `tag`, returning its contents.
+    pub fn take(&mut self, tag: u8) -> Option<&'a [u8]> {
+        match self.read()? {
+            (t, contents) if t == tag => Some(contents),
+            _ => None,
+        }
+    }
+
+    /// Reads the next value, which must have `tag`, returning its whole encoding including the
+    /// tag and length.
+    pub fn take_encoded(&mut self, tag: u8) -> Option<&'a [u8]> {
+        match self.read_tlv()? {
+            (t, _, encoded) if t == tag => Some(encoded),
+            _ => None,
+        }
+    }
+
+    /// Reads the next value, which must have `tag`, returning a reader over its contents.
+    pub fn sub(&mut self, tag: u8) -> Option<Der<'a>> {
+        self.take(tag).map(Der::new)
+    }
+
+    /// Reads the next value if it has `tag`, returning `Some(None)` if the next value has
+    /// another tag or there are no more values.
+    pub fn take_optional(&mut self, tag: u8) -> Option<Option<&'a [u8]>> {
+        if self.peek_tag() != Some(tag) {
+            return Some(None);
+        }
+        self.take(tag).map(Some)
+    }
+
+    pub fn boolean(&mut self) -> Option<bool> {
+        match self.take(tag::BOOLEAN)? {
+            [0x00] => Some(false),
+            [0xff] => Some(true),
+            _ => None,
+        }
+    }
+
+    /// Reads a non-negative INTEGER small enough for a `u8`.
+    pub fn small_uint(&mut self) -> Option<u8> {
+        match self.take(tag::INTEGER)? {
+            [n] if *n < 0x80 => Some(*n),
+            [0, n] if *n >= 0x80 => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Reads a BIT STRING with a whole number of bytes.
+    pub fn bit_string(&mut self) -> Option<&'a [u8]> {
+        match self.take(tag::BIT_STRING)? {
+            [0, bits @ ..] => Some(bits),
+            _ => None,
+        }
+    }
+
+    fn read_tlv(&mut self) -> Option<(u8, &'a [u8], &'a [u8])> {
+        let bytes = self.0.as_bytes();
+        let start = bytes.len() - self.0.remaining();
+        let tag = self.0.take(1)?[0];
+        // High tag numbers aren't used in certificates.
+        if tag & 0x1f == 0x1f {
+            return None;
+        }
+
+        let len = self.read_len()?;
+        let contents = self.0.take(len)?;
+        let end = bytes.len() - self.0.remaining();
+        Some((tag, contents, &bytes[start..end]))
+    }
+
+    fn read_len(&mut self) -> Option<usize> {
+        let first = self.0.take(1)?[0];
+        if first < 0x80 {
+            return Some(usize::from(first));
+        }
+
+        // Certificates are never longer than the three bytes of length allow.
+        let bytes = match first {
+            0x81..=0x83 => self.0.take(usize::from(first & 0x7f))?,
+            _ => return None,
+        };
+        let len = bytes.iter().fold(0, |len, b| (len << 8) | usize::from(*b));
+        // DER requires the shortest encoding.
+        if bytes[0] == 0 || len < 0x80 {
+            return None;
+        }
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_short_len() {
+        let bytes = [0x04, 2, 1, 2, 0x05, 0];
+        let mut der = Der::new(&bytes);
+
+        assert_eq!(der.read(), Some((tag::OCTET_STRING, &[1, 2][..])));
+        assert_eq!(der.take(tag::NULL), Some(&[][..]));
+        assert!(der.is_complete());
+    }
+
+    #[test]
+    fn read_long_len() {
+        let mut bytes = vec![0x04, 0x81, 0x80];
+        bytes.extend_from_slice(&[7; 0x80]);
+        let mut der = Der::new(&bytes);
+
+        assert_eq!(der.take(tag::OCTET_STRING), Some(&[7; 0x80][..]));
+        assert!(der.is_complete());
+    }
+
+    #[test]
+    fn non_minimal_len() {
+        assert!(Der::new(&[0x04, 0x81, 0x01, 0]).read().is_none());
+        assert!(Der::new(&[0x04, 0x82, 0x00, 0x80]).read().is_none());
+        assert!(Der::new(&[0x04, 0x80]).read().is_none());
+    }
+
+    #[test]
+    fn truncated() {
+        assert!(Der::new(&[0x04, 3, 1, 2]).read().is_none());
+        assert!(Der::new(&[0x04]).read().is_none());
+    }
+
+    #[test]
+    fn wrong_tag() {
+        assert!(Der::new(&[0x04, 0]).take(tag::NULL).is_none());
+    }
+
+    #[test]
+    fn sub() {
+        let bytes = [0x30, 5, 0x02, 1, 5, 0x05, 0];
+        let mut der = Der::new(&bytes);
+        let mut seq = der.sub(tag::SEQUENCE).unwrap();
+
+        assert_eq!(seq.small_uint(), Some(5));
+        assert_eq!(seq.take(tag::NULL), Some(&[][..]));
+        assert!(seq.is_complete());
+        assert!(der.is_complete());
+    }
+
+    #[test]
+    fn take_encoded() {
+        let bytes = [0x30, 3, 0x01, 1, 0xff];
+        let mut der = Der::new(&bytes);
+
+        assert_eq!(der.take_encoded(tag::SEQUENCE), Some(&bytes[..]));
+    }
+
+    #[test]
+    fn take_optional() {
+        let bytes = [0x01, 1, 0xff];
+        let mut der = Der::new(&bytes);
+
+        assert_eq!(der.take_optional(tag::INTEGER), Some(None));
+        assert_eq!(der.take_optional(tag::BOOLEAN), Some(Some(&[0xff][..])));
+        assert_eq!(der.take_optional(tag::BOOLEAN), Some(None));
+    }
+
+    #[test]
+    fn boolean() {
+        assert_eq!(Der::new(&[0x01, 1, 0xff]).boolean(), Some(true));
+        assert_eq!(Der::new(&[0x01, 1, 0x00]).boolean(), Some(false));
+        assert_eq!(Der::new(&[0x01, 1, 0x01]).boolean(), None);
+    }
+
+    #[test]
+    fn small_uint() {
+        assert_eq!(Der::new(&[0x02, 1, 0x7f]).small_uint(), Some(0x7f));
+        assert_eq!(Der::new(&[0x02, 2, 0, 0x80]).small_uint(), Some(0x80));
+        assert_eq!(Der::new(&[0x02, 1, 0x80]).small_uint(), None);
+        assert_eq!(Der::new(&[0x02, 2, 0, 0x01]).small_uint(), None);
+    }
+
+    #[test]
+    fn bit_string() {
+        assert_eq!(Der::new(&[0x03, 2, 0, 5]).bit_string(), Some(&[5][..]));
+        assert_eq!(Der::new(&[0x03, 2, 1, 4]).bit_string(), None);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{
+    error::{Error as TlsError, Result as TlsResult},
+    x509::certificate::{Certificate, GeneralName, SubjectAltName},
+};
+use core::net::IpAddr;
+
+/// Longest DNS name, without a trailing dot (RFC 1035, section 2.3.4).
+const MAX_DNS_NAME_LEN: usize = 253;
+
+/// Longest label of a DNS name.
+const MAX_LABEL_LEN: usize = 63;
+
+/// Name a server is expected to have, which its certificate must be issued for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerName<'a> {
+    /// DNS name, without a trailing dot.
+    DnsName(&'a str),
+    IpAddress(IpAddr),
+}
+
+impl<'a> ServerName<'a> {
+    /// Reads an IPv4 or IPv6 literal, or otherwise a DNS name in preferred name syntax.
+    pub fn new(name: &'a str) -> TlsResult<Self> {
+        if let Ok(address) = name.parse() {
+            return Ok(ServerName::IpAddress(address));
+        }
+
+        let name = name.strip_suffix('.').unwrap_or(name);
+        if name.len() > MAX_DNS_NAME_LEN || !name.split('.').all(is_valid_label) {
+            return Err(TlsError::InternalError("invalid server name"));
+        }
+        Ok(ServerName::DnsName(name))
+    }
+
+    /// Checks `cert` was issued for this name by its subject alternative names (RFC 6125, section
+    /// 6).
+    ///
+    /// The common name is never used, so certificates without subject alternative names never
+    /// match. Mismatches fail with [`TlsError::CertificateNameMismatch`].
+    pub fn verify(&self, cert: &Certificate<'_>) -> TlsResult<()> {
+        match cert.subject_alt_name() {
+            Some(names) if self.matches(names) => Ok(()),
+            _ => Err(TlsError::CertificateNameMismatch),
+        }
+    }
+
+    fn matches(&self, names: SubjectAltName<'_>) -> bool {
+        let mut names = names.names();
+        match *self {
+            ServerName::DnsName(name) => names.any(|presented| match presented {
+                GeneralName::DnsName(presented) => dns_name_matches(name.as_bytes(), presented),
+                _ => false,
+            }),
+            ServerName::IpAddress(address) => names.any(|presented| match (presented, address) {
+                (GeneralName::IpAddress(presented), IpAddr::V4(address)) => {
+                    presented == address.octets()
+                }
+                (GeneralName::IpAddress(presented), IpAddr::V6(address)) => {
+                    presented == address.octets()
+                }
+                _ => false,
+            }),
+        }
+    }
+}
+
+/// Whether a label is 1 to 63 letters, digits and hyphens, not starting or ending with a hyphen.
+fn is_valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= MAX_LABEL_LEN
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// Whether the presented identifier of a certificate matches the reference name, ignoring case.
+///
+/// Only a wildcard that is the whole of the leftmost label is allowed, which matches exactly one
+/// label. It must be followed by at least two labels, so it can't match every name under a top
+/// level domain (RFC 6125, section 6.4.3).
+fn dns_name_matches(reference: &[u8], presented: &[u8]) -> bool {
+    match presented {
+        [b'*', b'.', rest @ ..] => {
+            if !rest.contains(&b'.') || rest.contains(&b'*') {
+                return false;
+            }
+            match reference.iter().position(|&b| b == b'.') {
+                Some(end) if end > 0 => reference[end + 1..].eq_ignore_ascii_case(rest),
+                _ => false,
+            }
+        }
+        _ => !presented.contains(&b'*') && reference.eq_ignore_ascii_case(presented),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x509::certificate::tests::test_certs;
+    use core::net::{Ipv4Addr, Ipv6Addr};
+
+    fn verify(name: &str) -> TlsResult<()> {
+        let der = &test_certs("ecdsa/end.cert")[0];
+        ServerName::new(name)
+            .unwrap()
+            .verify(&Certificate::parse(der).unwrap())
+    }
+
+    #[test]
+    fn server_name() {
+        assert_eq!(
+            ServerName::new("example.com.").unwrap(),
+            ServerName::DnsName("example.com")
+        );
+        assert_eq!(
+            ServerName::new("127.0.0.1").unwrap(),
+            ServerName::IpAddress(Ipv4Addr::LOCALHOST.into())
+        );
+        assert_eq!(
+            ServerName::new("::1").unwrap(),
+            ServerName::IpAddress(Ipv6Addr::LOCALHOST.into())
+        );
+        assert!(ServerName::new("").is_err());
+        assert!(ServerName::new("a..b").is_err());
+        assert!(ServerName::new("-a.b").is_err());
+        assert!(ServerName::new("*.example.com").is_err());
+        assert!(ServerName::new(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn dns_names() {
+        assert!(verify("testserver.com").is_ok());
+        assert!(verify("Second.TestServer.com.").is_ok());
+        assert!(verify("localhost").is_ok());
+    }
+
+    #[test]
+    fn mismatch() {
+        assert!(matches!(
+            verify("other.testserver.com"),
+            Err(TlsError::CertificateNameMismatch)
+        ));
+        assert!(matches!(
+            verify("testserver.co"),
+            Err(TlsError::CertificateNameMismatch)
+        ));
+        assert!(matches!(
+            verify("127.0.0.1"),
+            Err(TlsError::CertificateNameMismatch)
+        ));
+    }
+
+    #[test]
+    fn no_common_name_fallback() {
+        // The CA's common name is "ponytown ECDSA CA", and it has no subject alternative names.
+        let der = &test_certs("ecdsa/ca.cert")[0];
+        let cert = Certificate::parse(der).unwrap();
+
+        assert!(cert.subject_alt_name().is_none());
+        assert!(matches!(
+            ServerName::DnsName("ponytown ECDSA CA").verify(&cert),
+            Err(TlsError::CertificateNameMismatch)
+        ));
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(dns_name_matches(b"www.example.com", b"*.example.com"));
+        assert!(dns_name_matches(b"WWW.Example.com", b"*.example.COM"));
+        assert!(!dns_name_matches(b"example.com", b"*.example.com"));
+        assert!(!dns_name_matches(b"a.www.example.com", b"*.example.com"));
+        assert!(!dns_name_matches(b"example.com", b"*.com"));
+        assert!(!dns_name_matches(b"www.example.com", b"w*.example.com"));
+        assert!(!dns_name_matches(b"www.example.com", b"*w.example.com"));
+        assert!(!dns_name_matches(b"www.example.com", b"www.*.com"));
+        assert!(!dns_name_matches(b"www.example.com", b"*.*.com"));
+    }
+
+    #[test]
+    fn ip_addresses() {
+        // A DNS name of "10.0.0.1", and the addresses 10.0.0.2 and ::1.
+        let san = [
+            0x30, 0x22, 0x82, 0x08, b'1', b'0', b'.', b'0', b'.', b'0', b'.', b'1', 0x87, 0x04, 10,
+            0, 0, 2, 0x87, 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1,
+        ];
+        let names = SubjectAltName::parse(&san).unwrap();
+        let name = |name| ServerName::new(name).unwrap().matches(names);
+
+        assert!(name("10.0.0.2"));
+        assert!(name("::1"));
+        assert!(!name("10.0.0.1"));
+        assert!(!name("::ffff:10.0.0.2"));
+        assert!(!name("127.0.0.1"));
+    }
+}
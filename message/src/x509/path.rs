@@ -0,0 +1,510 @@
+use crate::{
+    crypto::SignatureVerifier,
+    error::{Error as TlsError, Result as TlsResult},
+    time::Now,
+    x509::certificate::{Certificate, KeyPurpose, KeyUsage, SubjectPublicKeyInfo},
+};
+
+/// Most intermediate certificates a path may have between the end-entity and a trust anchor.
+pub const MAX_INTERMEDIATES: usize = 6;
+
+/// Most intermediate certificates that may be presented to build a path from.
+pub const MAX_PRESENTED_INTERMEDIATES: usize = 32;
+
+/// Most signatures checked while building a path, like webpki, so crafted intermediates can't
+/// make the search take exponential time.
+pub const MAX_SIGNATURE_CHECKS: usize = 100;
+
+/// Issuer that is trusted without being certified by another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustAnchor<'a> {
+    /// Encoding of the anchor's distinguished name.
+    pub subject: &'a [u8],
+    pub public_key: SubjectPublicKeyInfo<'a>,
+}
+
+impl<'a> TrustAnchor<'a> {
+    /// Trusts the subject and key of a (usually self-signed) certificate.
+    ///
+    /// Only the subject and key are used, so the certificate's own validity and extensions don't
+    /// constrain paths to it.
+    pub fn from_certificate(der: &'a [u8]) -> TlsResult<Self> {
+        let cert = Certificate::parse(der)?;
+        Ok(Self {
+            subject: cert.subject(),
+            public_key: *cert.public_key(),
+        })
+    }
+}
+
+/// Certification path built by [`PathValidator::validate`], from the end-entity's issuer up to a
+/// trust anchor.
+#[derive(Debug, Clone, Copy)]
+pub struct CertificatePath<'a, 'c> {
+    intermediates: [&'c [u8]; MAX_INTERMEDIATES],
+    len: usize,
+    anchor: &'a TrustAnchor<'a>,
+}
+
+impl<'a, 'c> CertificatePath<'a, 'c> {
+    /// Intermediates in the path, the end-entity's issuer first. Presented intermediates that
+    /// aren't in the path are left out.
+    pub fn intermediates(&self) -> &[&'c [u8]] {
+        &self.intermediates[..self.len]
+    }
+
+    /// Trust anchor the path ends at.
+    pub fn anchor(&self) -> &'a TrustAnchor<'a> {
+        self.anchor
+    }
+}
+
+/// Validates certification paths from an end-entity certificate to a set of trust anchors (RFC
+/// 5280, section 6).
+///
+/// Intermediates may be presented in any order and may include unrelated certificates. Each
+/// candidate issuer is tried in turn, backtracking when a path through it fails.
+pub struct PathValidator<'a, V: SignatureVerifier> {
+    anchors: &'a [TrustAnchor<'a>],
+    verifier: V,
+}
+
+impl<'a, V: SignatureVerifier> PathValidator<'a, V> {
+    /// Creates a validator checking signatures with `verifier`.
+    pub fn new(anchors: &'a [TrustAnchor<'a>], verifier: V) -> Self {
+        Self { anchors, verifier }
+    }
+
+    /// Checks that `end_entity` is valid for `purpose` at `now`, and chains to a trust anchor
+    /// through `intermediates`, returning the path built.
+    ///
+    /// Expired certificates fail with [`TlsError::CertificateExpired`], and paths that don't
+    /// reach a trust anchor with [`TlsError::UnknownCertificateAuthority`]. More than
+    /// [`MAX_PRESENTED_INTERMEDIATES`] intermediates, or a search needing more than
+    /// [`MAX_SIGNATURE_CHECKS`] signature checks, fail with [`TlsError::BadCertificate`].
+    pub fn validate<'c>(
+        &self,
+        end_entity: &[u8],
+        intermediates: &[&'c [u8]],
+        purpose: KeyPurpose,
+        now: Now,
+    ) -> TlsResult<CertificatePath<'a, 'c>> {
+        if intermediates.len() > MAX_PRESENTED_INTERMEDIATES {
+            return Err(TlsError::BadCertificate);
+        }
+        let cert = Certificate::parse(end_entity)?;
+        if cert.basic_constraints().is_some_and(|bc| bc.ca) {
+            return Err(TlsError::BadCertificate);
+        }
+        if let Some(usage) = cert.key_usage() {
+            if !usage.contains(KeyUsage::DIGITAL_SIGNATURE) {
+                return Err(TlsError::BadCertificate);
+            }
+        }
+        if let Some(usage) = cert.extended_key_usage() {
+            if !usage.permits(purpose) {
+                return Err(TlsError::BadCertificate);
+            }
+        }
+        if !cert.validity().contains(now) {
+            return Err(TlsError::CertificateExpired);
+        }
+
+        let mut search = Search {
+            intermediates,
+            purpose,
+            now,
+            budget: MAX_SIGNATURE_CHECKS,
+            exhausted: false,
+        };
+        self.build(&mut search, &cert, 0, 0)
+    }
+
+    /// Finds an issuer for `cert`, which has `depth` intermediates below it, from the anchors or
+    /// the intermediates not already in the path (bit `i` of `used` for intermediate `i`).
+    ///
+    /// Candidates are only considered if their subject is the issuer of `cert`, before any
+    /// signature is checked.
+    fn build<'c>(
+        &self,
+        search: &mut Search<'_, 'c>,
+        cert: &Certificate<'_>,
+        depth: usize,
+        used: u32,
+    ) -> TlsResult<CertificatePath<'a, 'c>> {
+        let mut result = Err(TlsError::UnknownCertificateAuthority);
+
+        for anchor in self.anchors {
+            if anchor.subject != cert.issuer() {
+                continue;
+            }
+            match self.verify_signature(search, cert, &anchor.public_key) {
+                Ok(()) => {
+                    return Ok(CertificatePath {
+                        intermediates: [&[]; MAX_INTERMEDIATES],
+                        len: depth,
+                        anchor,
+                    })
+                }
+                Err(err) if search.exhausted => return Err(err),
+                Err(err) => result = Err(err),
+            }
+        }
+
+        if depth == MAX_INTERMEDIATES {
+            return result;
+        }
+
+        for (i, der) in search.intermediates.iter().enumerate() {
+            if used & 1 << i != 0 {
+                continue;
+            }
+            let issuer = match Certificate::parse(der) {
+                Ok(issuer) if issuer.subject() == cert.issuer() => issuer,
+                _ => continue,
+            };
+
+            let path = self
+                .check_issuer(search, &issuer, cert, depth)
+                .and_then(|()| self.build(search, &issuer, depth + 1, used | 1 << i));
+            match path {
+                Ok(mut path) => {
+                    path.intermediates[depth] = der;
+                    return Ok(path);
+                }
+                // The search is over once the budget is spent.
+                Err(err) if search.exhausted => return Err(err),
+                // A specific failure explains more than the lack of an issuer.
+                Err(TlsError::UnknownCertificateAuthority) => {}
+                Err(err) => result = Err(err),
+            }
+        }
+        result
+    }
+
+    /// Checks `issuer`, an intermediate with `depth` intermediates below it, may issue `cert`.
+    fn check_issuer(
+        &self,
+        search: &mut Search<'_, '_>,
+        issuer: &Certificate<'_>,
+        cert: &Certificate<'_>,
+        depth: usize,
+    ) -> TlsResult<()> {
+        match issuer.basic_constraints() {
+            Some(bc) if bc.ca && bc.path_len.is_none_or(|len| depth <= usize::from(len)) => {}
+            _ => return Err(TlsError::BadCertificate),
+        }
+        if let Some(usage) = issuer.key_usage() {
+            if !usage.contains(KeyUsage::KEY_CERT_SIGN) {
+                return Err(TlsError::BadCertificate);
+            }
+        }
+        if let Some(usage) = issuer.extended_key_usage() {
+            if !usage.permits(search.purpose) {
+                return Err(TlsError::BadCertificate);
+            }
+        }
+        if !issuer.validity().contains(search.now) {
+            return Err(TlsError::CertificateExpired);
+        }
+
+        self.verify_signature(search, cert, issuer.public_key())
+    }
+
+    fn verify_signature(
+        &self,
+        search: &mut Search<'_, '_>,
+        cert: &Certificate<'_>,
+        public_key: &SubjectPublicKeyInfo<'_>,
+    ) -> TlsResult<()> {
+        if search.budget == 0 {
+            search.exhausted = true;
+            return Err(TlsError::BadCertificate);
+        }
+        search.budget -= 1;
+        self.verifier.verify(
+            public_key,
+            cert.signature_algorithm(),
+            cert.tbs(),
+            cert.signature(),
+        )
+    }
+}
+
+/// What a path is searched for, and how much searching is left.
+struct Search<'i, 'c> {
+    intermediates: &'i [&'c [u8]],
+    purpose: KeyPurpose,
+    now: Now,
+    /// Signatures that may still be checked.
+    budget: usize,
+    /// Whether a signature needed checking once the budget was spent.
+    exhausted: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{RingVerifier, NOW},
+        x509::certificate::{tests::test_certs, SignatureAlgorithmIdentifier},
+    };
+    use core::cell::Cell;
+    use std::vec::Vec;
+
+    fn validate(anchor: &str, chain: &[Vec<u8>], purpose: KeyPurpose, now: Now) -> TlsResult<()> {
+        let anchor = test_certs(anchor);
+        let anchors = [TrustAnchor::from_certificate(&anchor[0]).unwrap()];
+        let intermediates = chain[1..].iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        PathValidator::new(&anchors, RingVerifier)
+            .validate(&chain[0], &intermediates, purpose, now)
+            .map(|_| ())
+    }
+
+    #[test]
+    fn ecdsa() {
+        let chain = test_certs("ecdsa/end.fullchain");
+
+        assert!(validate(
+            "ecdsa/ca.cert",
+            &chain,
+            KeyPurpose::ServerAuth,
+            Now::UnixTime(NOW)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn rsa() {
+        let chain = test_certs("rsa/end.fullchain");
+
+        assert!(validate(
+            "rsa/ca.cert",
+            &chain,
+            KeyPurpose::ServerAuth,
+            Now::UnixTime(NOW)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn intermediates_out_of_order() {
+        let mut chain = test_certs("ecdsa/end.fullchain");
+        chain.swap(1, 2);
+        chain.extend(test_certs("rsa/end.chain"));
+
+        assert!(validate(
+            "ecdsa/ca.cert",
+            &chain,
+            KeyPurpose::ServerAuth,
+            Now::UnixTime(NOW)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn built_path() {
+        let chain = test_certs("ecdsa/end.fullchain");
+        let unrelated = test_certs("rsa/end.chain");
+        let intermediates = [&unrelated[0][..], &chain[2], &chain[1]];
+        let anchor = test_certs("ecdsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&anchor[0]).unwrap()];
+        let validator = PathValidator::new(&anchors, RingVerifier);
+
+        let path = validator
+            .validate(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW),
+            )
+            .unwrap();
+        assert_eq!(path.intermediates(), [&chain[1][..]]);
+        assert_eq!(path.anchor(), &anchors[0]);
+    }
+
+    #[test]
+    fn client() {
+        let chain = test_certs("ecdsa/client.fullchain");
+
+        assert!(validate(
+            "ecdsa/ca.cert",
+            &chain,
+            KeyPurpose::ClientAuth,
+            Now::UnixTime(NOW)
+        )
+        .is_ok());
+        assert!(matches!(
+            validate(
+                "ecdsa/ca.cert",
+                &chain,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::BadCertificate)
+        ));
+    }
+
+    #[test]
+    fn expired() {
+        let chain = test_certs("ecdsa/end.fullchain");
+
+        // 2024-01-01, after the end-entity expires.
+        assert!(matches!(
+            validate(
+                "ecdsa/ca.cert",
+                &chain,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(1_704_067_200)
+            ),
+            Err(TlsError::CertificateExpired)
+        ));
+        // 2017-01-01, before any were issued.
+        assert!(matches!(
+            validate(
+                "ecdsa/ca.cert",
+                &chain,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(1_483_228_800)
+            ),
+            Err(TlsError::CertificateExpired)
+        ));
+    }
+
+    #[test]
+    fn skip_time_checks() {
+        let chain = test_certs("ecdsa/end.fullchain");
+
+        assert!(validate(
+            "ecdsa/ca.cert",
+            &chain,
+            KeyPurpose::ServerAuth,
+            Now::DangerousSkipTimeChecks
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn unknown_authority() {
+        let chain = test_certs("ecdsa/end.fullchain");
+
+        assert!(matches!(
+            validate(
+                "rsa/ca.cert",
+                &chain,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::UnknownCertificateAuthority)
+        ));
+        assert!(matches!(
+            validate(
+                "ecdsa/ca.cert",
+                &chain[..1],
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::UnknownCertificateAuthority)
+        ));
+    }
+
+    #[test]
+    fn bad_signature() {
+        let mut chain = test_certs("ecdsa/end.fullchain");
+        // Changes the last byte of the end-entity's signature, which is an ECDSA `s` value.
+        *chain[0].last_mut().unwrap() ^= 1;
+
+        assert!(matches!(
+            validate(
+                "ecdsa/ca.cert",
+                &chain,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::BadCertificate)
+        ));
+    }
+
+    #[test]
+    fn ca_as_end_entity() {
+        let chain = test_certs("ecdsa/end.fullchain");
+
+        assert!(matches!(
+            validate(
+                "ecdsa/ca.cert",
+                &chain[1..],
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::BadCertificate)
+        ));
+    }
+
+    /// Accepts every signature, counting how many it was asked to check.
+    struct CountingVerifier(Cell<usize>);
+
+    impl SignatureVerifier for CountingVerifier {
+        fn verify(
+            &self,
+            _public_key: &SubjectPublicKeyInfo<'_>,
+            _algorithm: SignatureAlgorithmIdentifier,
+            _message: &[u8],
+            _signature: &[u8],
+        ) -> TlsResult<()> {
+            self.0.set(self.0.get() + 1);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn signature_check_budget() {
+        // Copies of a self-issued CA can each issue every other, so without a budget every
+        // ordering of them would be tried in search of the unrelated anchor.
+        let chain = test_certs("ecdsa/end.fullchain");
+        let mut intermediates = vec![chain[1].as_slice()];
+        intermediates.extend(core::iter::repeat_n(chain[2].as_slice(), 20));
+        let anchor = test_certs("rsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&anchor[0]).unwrap()];
+        let validator = PathValidator::new(&anchors, CountingVerifier(Cell::new(0)));
+
+        assert!(matches!(
+            validator.validate(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::BadCertificate)
+        ));
+        assert_eq!(validator.verifier.0.get(), MAX_SIGNATURE_CHECKS);
+    }
+
+    #[test]
+    fn too_many_intermediates() {
+        let chain = test_certs("ecdsa/end.fullchain");
+        let mut intermediates = vec![chain[1].as_slice(); MAX_PRESENTED_INTERMEDIATES];
+        let anchor = test_certs("ecdsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&anchor[0]).unwrap()];
+        let validator = PathValidator::new(&anchors, RingVerifier);
+        assert!(validator
+            .validate(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            )
+            .is_ok());
+
+        intermediates.push(chain[1].as_slice());
+        assert!(matches!(
+            validator.validate(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::BadCertificate)
+        ));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::error::{Error as TlsError, Result as TlsResult};
+use core::str;
+
+const BEGIN: &[u8] = b"-----BEGIN ";
+const END: &[u8] = b"-----END ";
+const DASHES: &[u8] = b"-----";
+
+/// Section of a PEM file, between its BEGIN and END lines (RFC 7468).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PemSection<'a> {
+    label: &'a str,
+    encoded: &'a [u8],
+}
+
+impl<'a> PemSection<'a> {
+    /// Label of the section, such as "CERTIFICATE" or "PRIVATE KEY".
+    pub fn label(&self) -> &'a str {
+        self.label
+    }
+
+    /// Most bytes the section can decode to, for sizing the buffer given to `decode`.
+    pub fn max_decoded_len(&self) -> usize {
+        self.encoded.len() / 4 * 3
+    }
+
+    /// Decodes the base64 contents of the section into `out`, returning the decoded bytes.
+    pub fn decode<'b>(&self, out: &'b mut [u8]) -> TlsResult<&'b [u8]> {
+        let len = decode_base64(self.encoded, out)?;
+        Ok(&out[..len])
+    }
+}
+
+/// Iterator over the sections of a PEM file, ignoring any text between them.
+///
+/// A section that isn't closed by a matching END line gives an error, after which there are no
+/// more sections.
+pub struct PemSections<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> PemSections<'a> {
+    pub fn new(pem: &'a [u8]) -> Self {
+        Self { rest: pem }
+    }
+
+    fn next_section(&mut self) -> Option<TlsResult<PemSection<'a>>> {
+        let start = find(self.rest, BEGIN)? + BEGIN.len();
+        let rest = &self.rest[start..];
+        // Nothing more can be read from a malformed section.
+        self.rest = &[];
+
+        let section = find(rest, DASHES)
+            .and_then(|len| {
+                let label = str::from_utf8(&rest[..len]).ok()?;
+                let rest = &rest[len + DASHES.len()..];
+
+                let end = find(rest, END)?;
+                let encoded = &rest[..end];
+                let rest = rest[end + END.len()..].strip_prefix(label.as_bytes())?;
+                let rest = rest.strip_prefix(DASHES)?;
+                Some((PemSection { label, encoded }, rest))
+            })
+            .filter(|(section, _)| {
+                section
+                    .label
+                    .bytes()
+                    .all(|b| b.is_ascii_graphic() || b == b' ')
+            });
+
+        match section {
+            Some((section, rest)) => {
+                self.rest = rest;
+                Some(Ok(section))
+            }
+            None => Some(Err(TlsError::DecodeError)),
+        }
+    }
+}
+
+impl<'a> Iterator for PemSections<'a> {
+    type Item = TlsResult<PemSection<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_section()
+    }
+}
+
+/// Position of the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Decodes base64 with padding (RFC 4648, section 4) into `out`, skipping whitespace, and
+/// returns the length decoded.
+fn decode_base64(encoded: &[u8], out: &mut [u8]) -> TlsResult<usize> {
+    let mut bits = 0u32;
+    let mut bit_count = 0;
+    let mut len = 0;
+    let mut chars = 0;
+    let mut padding = 0;
+
+    for &c in encoded.iter().filter(|c| !c.is_ascii_whitespace()) {
+        chars += 1;
+        if c == b'=' {
+            padding += 1;
+            continue;
+        }
+        if padding > 0 {
+            return Err(TlsError::DecodeError);
+        }
+
+        bits = bits << 6 | u32::from(base64_value(c).ok_or(TlsError::DecodeError)?);
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            *out.get_mut(len)
+                .ok_or(TlsError::InternalError("PEM section exceeds buffer"))? =
+                (bits >> bit_count) as u8;
+            bits &= (1 << bit_count) - 1;
+            len += 1;
+        }
+    }
+
+    // Bits left over from the last character must be zero for the encoding to be canonical.
+    if chars % 4 != 0 || padding > 2 || bits != 0 {
+        return Err(TlsError::DecodeError);
+    }
+    Ok(len)
+}
+
+fn base64_value(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::x509::certificate::tests::test_certs;
+    use std::vec::Vec;
+
+    fn base64(encoded: &str) -> TlsResult<Vec<u8>> {
+        let mut out = [0; 64];
+        decode_base64(encoded.as_bytes(), &mut out).map(|len| out[..len].to_vec())
+    }
+
+    #[test]
+    fn base64_vectors() {
+        // RFC 4648, section 10.
+        assert_eq!(base64("").unwrap(), b"");
+        assert_eq!(base64("Zg==").unwrap(), b"f");
+        assert_eq!(base64("Zm8=").unwrap(), b"fo");
+        assert_eq!(base64("Zm9v").unwrap(), b"foo");
+        assert_eq!(base64("Zm9vYg==").unwrap(), b"foob");
+        assert_eq!(base64("Zm9vYmE=").unwrap(), b"fooba");
+        assert_eq!(base64("Zm9v\r\nYmFy").unwrap(), b"foobar");
+        assert_eq!(base64("+/+/").unwrap(), [0xfb, 0xff, 0xbf]);
+    }
+
+    #[test]
+    fn base64_invalid() {
+        assert!(base64("Zg").is_err());
+        assert!(base64("Zg=").is_err());
+        assert!(base64("Z===").is_err());
+        assert!(base64("Zg==Zg==").is_err());
+        assert!(base64("Zh==").is_err());
+        assert!(base64("Zm9v!").is_err());
+        assert!(matches!(
+            decode_base64(b"Zm9v", &mut [0; 2]),
+            Err(TlsError::InternalError(_))
+        ));
+    }
+
+    #[test]
+    fn certificates() {
+        let pem = include_bytes!("../../../test-keys/ecdsa/end.fullchain");
+        let mut out = [0; 2048];
+
+        let decoded = PemSections::new(pem)
+            .map(|section| {
+                let section = section.unwrap();
+                assert_eq!(section.label(), "CERTIFICATE");
+                section.decode(&mut out).unwrap().to_vec()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(decoded, test_certs("ecdsa/end.fullchain"));
+    }
+
+    #[test]
+    fn text_between_sections() {
+        let pem = b"subject=foo\n-----BEGIN A-----\nZm9v\n-----END A-----\nbar\n\
+                    -----BEGIN B C-----\r\nYmFy\r\n-----END B C-----\r\n";
+        let sections = PemSections::new(pem)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            sections,
+            [
+                PemSection {
+                    label: "A",
+                    encoded: b"\nZm9v\n",
+                },
+                PemSection {
+                    label: "B C",
+                    encoded: b"\r\nYmFy\r\n",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated() {
+        let mut sections = PemSections::new(b"-----BEGIN A-----\nZm9v\n-----END B-----\n");
+
+        assert!(matches!(sections.next(), Some(Err(TlsError::DecodeError))));
+        assert!(sections.next().is_none());
+        assert!(PemSections::new(b"-----BEGIN A-----\nZm9v\n")
+            .next()
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use crate::{
+    crypto::{Digest, Hash, SignatureVerifier},
+    error::{Error as TlsError, Result as TlsResult},
+    time::Now,
+    x509::{
+        certificate::{
+            Certificate, KeyPurpose, SignatureAlgorithmIdentifier, SubjectPublicKeyInfo,
+        },
+        path::{PathValidator, MAX_INTERMEDIATES},
+    },
+};
+
+/// SHA-256 hash of a DER encoded SubjectPublicKeyInfo.
+pub type SpkiPin = [u8; 32];
+
+/// Named set of public keys, one of which a peer's certificates must have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinSet<'a> {
+    name: &'static str,
+    pins: &'a [SpkiPin],
+}
+
+impl<'a> PinSet<'a> {
+    /// Creates a pin set, whose `name` is given in the error when no certificate matches it.
+    pub const fn new(name: &'static str, pins: &'a [SpkiPin]) -> Self {
+        Self { name, pins }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn pins(&self) -> &'a [SpkiPin] {
+        self.pins
+    }
+
+    fn contains(&self, pin: &Digest) -> bool {
+        self.pins.iter().any(|p| p[..] == *pin.as_ref())
+    }
+}
+
+/// Verifies a peer's certificates have pinned public keys, for peers that are known in advance.
+///
+/// Each pin set must be matched, and a certificate fails with
+/// [`TlsError::CertificatePinMismatch`] naming the first pin set it doesn't match. Without any
+/// pin set, nothing is verified.
+///
+/// Pins are checked after validating the certification path with an inner [`PathValidator`], or
+/// against the end-entity alone in place of path validation.
+pub struct PinVerifier<'a, H: Hash, V: SignatureVerifier> {
+    pin_sets: &'a [PinSet<'a>],
+    sha256: H,
+    path: Option<PathValidator<'a, V>>,
+}
+
+impl<'a, H: Hash, V: SignatureVerifier> PinVerifier<'a, H, V> {
+    /// Creates a verifier validating the certification path with `path`, then checking each pin
+    /// set is matched by the key of the end-entity, an intermediate in the path built, or its
+    /// trust anchor.
+    ///
+    /// `sha256` is a SHA-256 context that no data has been added to, for hashing public keys.
+    pub fn new(pin_sets: &'a [PinSet<'a>], sha256: H, path: PathValidator<'a, V>) -> Self {
+        Self {
+            pin_sets,
+            sha256,
+            path: Some(path),
+        }
+    }
+
+    /// Verifies the certificates of a peer authenticating for `purpose`, checking validity
+    /// periods against `now`.
+    ///
+    /// Presented intermediates that aren't in the path built never match a pin, as they could
+    /// have been sent with any certificate. Fails with [`TlsError::InternalError`] if there are
+    /// no pin sets.
+    pub fn verify(
+        &self,
+        end_entity: &[u8],
+        intermediates: &[&[u8]],
+        purpose: KeyPurpose,
+        now: Now,
+    ) -> TlsResult<()> {
+        if self.pin_sets.is_empty() {
+            return Err(TlsError::InternalError("no pin sets"));
+        }
+        let path = match self.path {
+            Some(ref validator) => {
+                Some(validator.validate(end_entity, intermediates, purpose, now)?)
+            }
+            None => None,
+        };
+
+        let end_entity = Certificate::parse(end_entity)?;
+        let mut keys = [*end_entity.public_key(); 1 + MAX_INTERMEDIATES + 1];
+        let mut len = 1;
+        if let Some(ref path) = path {
+            for der in path.intermediates() {
+                keys[len] = *Certificate::parse(der)?.public_key();
+                len += 1;
+            }
+            keys[len] = path.anchor().public_key;
+            len += 1;
+        }
+        self.check(&keys[..len])
+    }
+
+    fn check(&self, keys: &[SubjectPublicKeyInfo<'_>]) -> TlsResult<()> {
+        'pin_sets: for set in self.pin_sets {
+            for key in keys {
+                if set.contains(&self.pin(key)) {
+                    continue 'pin_sets;
+                }
+            }
+            return Err(TlsError::CertificatePinMismatch(set.name));
+        }
+        Ok(())
+    }
+
+    fn pin(&self, key: &SubjectPublicKeyInfo<'_>) -> Digest {
+        let mut hash = self.sha256.clone();
+        hash.update(key.as_bytes());
+        hash.finish()
+    }
+}
+
+impl<'a, H: Hash> PinVerifier<'a, H, NoPathValidation> {
+    /// Creates a verifier checking each pin set is matched by the key of the end-entity, in
+    /// place of validating the certification path.
+    ///
+    /// Nothing else about the certificate is checked, not even that it is valid now.
+    /// Intermediates are ignored, as they would otherwise match whatever certificate they were
+    /// sent with.
+    pub fn end_entity_only(pin_sets: &'a [PinSet<'a>], sha256: H) -> Self {
+        Self {
+            pin_sets,
+            sha256,
+            path: None,
+        }
+    }
+}
+
+/// Stands in for the signature verifier of a [`PinVerifier`] without path validation, which never
+/// checks signatures.
+#[derive(Debug)]
+pub enum NoPathValidation {}
+
+impl SignatureVerifier for NoPathValidation {
+    fn verify(
+        &self,
+        _public_key: &SubjectPublicKeyInfo<'_>,
+        _algorithm: SignatureAlgorithmIdentifier,
+        _message: &[u8],
+        _signature: &[u8],
+    ) -> TlsResult<()> {
+        match *self {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{sha256, RingVerifier, Sha256, NOW},
+        x509::{certificate::tests::test_certs, path::TrustAnchor},
+    };
+    use std::vec::Vec;
+
+    /// SHA-256 hash of the key of the first certificate in a PEM file of the test keys.
+    fn spki_pin(path: &str) -> SpkiPin {
+        let der = &test_certs(path)[0];
+        let cert = Certificate::parse(der).unwrap();
+        let mut pin = [0; 32];
+        pin.copy_from_slice(sha256(cert.public_key().as_bytes()).as_ref());
+        pin
+    }
+
+    fn verify(pin_sets: &[PinSet<'_>], anchor: &str, chain: &str, now: u64) -> TlsResult<()> {
+        let anchor = test_certs(anchor);
+        let anchors = [TrustAnchor::from_certificate(&anchor[0]).unwrap()];
+        let path = PathValidator::new(&anchors, RingVerifier);
+        let chain = test_certs(chain);
+        let intermediates = chain[1..].iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        PinVerifier::new(pin_sets, Sha256::default(), path).verify(
+            &chain[0],
+            &intermediates,
+            KeyPurpose::ServerAuth,
+            Now::UnixTime(now),
+        )
+    }
+
+    #[test]
+    fn end_entity_pin() {
+        let pins = [spki_pin("rsa/end.cert"), spki_pin("ecdsa/end.cert")];
+        let pin_sets = [PinSet::new("backend", &pins)];
+
+        assert!(verify(&pin_sets, "ecdsa/ca.cert", "ecdsa/end.fullchain", NOW).is_ok());
+    }
+
+    #[test]
+    fn intermediate_pin() {
+        let pins = [spki_pin("ecdsa/end.chain")];
+        let pin_sets = [PinSet::new("backend", &pins)];
+
+        assert!(verify(&pin_sets, "ecdsa/ca.cert", "ecdsa/end.fullchain", NOW).is_ok());
+    }
+
+    #[test]
+    fn anchor_pin() {
+        let pins = [spki_pin("ecdsa/ca.cert")];
+        let pin_sets = [PinSet::new("backend", &pins)];
+
+        assert!(verify(&pin_sets, "ecdsa/ca.cert", "ecdsa/end.fullchain", NOW).is_ok());
+    }
+
+    #[test]
+    fn mismatch() {
+        let pins = [spki_pin("rsa/end.cert")];
+        let pin_sets = [PinSet::new("backend", &pins)];
+
+        assert!(matches!(
+            verify(&pin_sets, "ecdsa/ca.cert", "ecdsa/end.fullchain", NOW),
+            Err(TlsError::CertificatePinMismatch("backend"))
+        ));
+    }
+
+    #[test]
+    fn pin_outside_path() {
+        // The pinned certificate is presented, but isn't in the path built.
+        let pins = [spki_pin("rsa/end.cert")];
+        let pin_sets = [PinSet::new("backend", &pins)];
+        let anchor = test_certs("ecdsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&anchor[0]).unwrap()];
+        let path = PathValidator::new(&anchors, RingVerifier);
+        let mut chain = test_certs("ecdsa/end.fullchain");
+        chain.extend(test_certs("rsa/end.cert"));
+        let intermediates = chain[1..].iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        assert!(matches!(
+            PinVerifier::new(&pin_sets, Sha256::default(), path).verify(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::CertificatePinMismatch("backend"))
+        ));
+    }
+
+    #[test]
+    fn every_pin_set() {
+        let backend = [spki_pin("ecdsa/end.cert")];
+        let backup = [spki_pin("rsa/end.chain")];
+        let pin_sets = [
+            PinSet::new("backend", &backend),
+            PinSet::new("backup", &backup),
+        ];
+
+        assert!(matches!(
+            verify(&pin_sets, "ecdsa/ca.cert", "ecdsa/end.fullchain", NOW),
+            Err(TlsError::CertificatePinMismatch("backup"))
+        ));
+    }
+
+    #[test]
+    fn path_validation_first() {
+        let pins = [spki_pin("ecdsa/end.cert")];
+        let pin_sets = [PinSet::new("backend", &pins)];
+
+        assert!(matches!(
+            verify(&pin_sets, "rsa/ca.cert", "ecdsa/end.fullchain", NOW),
+            Err(TlsError::UnknownCertificateAuthority)
+        ));
+        // 2024-01-01, after the end-entity expires.
+        assert!(matches!(
+            verify(
+                &pin_sets,
+                "ecdsa/ca.cert",
+                "ecdsa/end.fullchain",
+                1_704_067_200
+            ),
+            Err(TlsError::CertificateExpired)
+        ));
+    }
+
+    #[test]
+    fn end_entity_only() {
+        let chain = test_certs("ecdsa/end.fullchain");
+        let intermediates = chain[1..].iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let pins = [spki_pin("ecdsa/end.cert")];
+        let intermediate_pins = [spki_pin("ecdsa/end.chain")];
+
+        let pin_sets = [PinSet::new("backend", &pins)];
+        assert!(PinVerifier::end_entity_only(&pin_sets, Sha256::default())
+            .verify(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            )
+            .is_ok());
+
+        let pin_sets = [PinSet::new("intermediate", &intermediate_pins)];
+        assert!(matches!(
+            PinVerifier::end_entity_only(&pin_sets, Sha256::default()).verify(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::CertificatePinMismatch("intermediate"))
+        ));
+    }
+
+    #[test]
+    fn no_pin_sets() {
+        let chain = test_certs("ecdsa/end.fullchain");
+        let intermediates = chain[1..].iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let anchor = test_certs("ecdsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&anchor[0]).unwrap()];
+        let path = PathValidator::new(&anchors, RingVerifier);
+        let now = Now::UnixTime(NOW);
+
+        assert!(matches!(
+            PinVerifier::new(&[], Sha256::default(), path).verify(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                now
+            ),
+            Err(TlsError::InternalError(_))
+        ));
+        assert!(matches!(
+            PinVerifier::end_entity_only(&[], Sha256::default()).verify(
+                &chain[0],
+                &intermediates,
+                KeyPurpose::ServerAuth,
+                now
+            ),
+            Err(TlsError::InternalError(_))
+        ));
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{
+    crypto::{Hash, SignatureVerifier},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::certificate::CertificatePayload,
+    time::Now,
+    x509::{
+        certificate::{Certificate, KeyPurpose},
+        identity::ServerName,
+        path::PathValidator,
+        pinning::PinVerifier,
+    },
+};
+
+/// Most certificates a peer may present, including the end-entity.
+pub const MAX_CERTIFICATES: usize = 16;
+
+/// Outcome of verifying a peer's certificates, which only a verifier returns.
+#[derive(Debug)]
+pub struct Verified(());
+
+impl Verified {
+    /// Asserts that the certificates were verified, for implementations of the verifier traits.
+    pub fn assertion() -> Self {
+        Verified(())
+    }
+}
+
+/// Decides whether to trust the certificates a server presents.
+///
+/// [`PathValidator`] and [`PinVerifier`] are the built-in implementations. Others can implement
+/// their own trust logic, such as for a private PKI.
+pub trait ServerCertVerifier {
+    /// Verifies `certificates`, whose first entry is the end-entity, was issued to
+    /// `server_name`. `ocsp_response` and `signed_certificate_timestamps` are those sent with the
+    /// end-entity certificate, if any, and `now` is the time read from the connection's
+    /// [`TimeSource`](crate::time::TimeSource).
+    fn verify_server_cert(
+        &self,
+        certificates: &CertificatePayload<'_>,
+        server_name: ServerName<'_>,
+        ocsp_response: Option<&[u8]>,
+        signed_certificate_timestamps: Option<&[u8]>,
+        now: Now,
+    ) -> TlsResult<Verified>;
+}
+
+/// Decides whether to trust the certificates a client presents.
+///
+/// [`PathValidator`] and [`PinVerifier`] are the built-in implementations. Others can implement
+/// their own trust logic, such as for device attestation certificates.
+pub trait ClientCertVerifier {
+    /// Verifies `certificates`, whose first entry is the end-entity. `ocsp_response` and
+    /// `signed_certificate_timestamps` are those sent with the end-entity certificate, if any,
+    /// and `now` is the time read from the connection's [`TimeSource`](crate::time::TimeSource).
+    fn verify_client_cert(
+        &self,
+        certificates: &CertificatePayload<'_>,
+        ocsp_response: Option<&[u8]>,
+        signed_certificate_timestamps: Option<&[u8]>,
+        now: Now,
+    ) -> TlsResult<Verified>;
+}
+
+impl<'a, V: SignatureVerifier> ServerCertVerifier for PathValidator<'a, V> {
+    /// Validates the certification path for server authentication, then checks the end-entity
+    /// certificate's subject alternative names match `server_name`.
+    fn verify_server_cert(
+        &self,
+        certificates: &CertificatePayload<'_>,
+        server_name: ServerName<'_>,
+        _ocsp_response: Option<&[u8]>,
+        _signed_certificate_timestamps: Option<&[u8]>,
+        now: Now,
+    ) -> TlsResult<Verified> {
+        let mut buf = [&[][..]; MAX_CERTIFICATES];
+        // A server must always send a certificate (RFC 8446, section 4.4.2.4).
+        let (end_entity, intermediates) = split_chain(certificates, &mut buf)?
+            .split_first()
+            .ok_or(TlsError::DecodeError)?;
+
+        self.validate(end_entity, intermediates, KeyPurpose::ServerAuth, now)?;
+        server_name.verify(&Certificate::parse(end_entity)?)?;
+        Ok(Verified::assertion())
+    }
+}
+
+impl<'a, V: SignatureVerifier> ClientCertVerifier for PathValidator<'a, V> {
+    /// Validates the certification path for client authentication.
+    fn verify_client_cert(
+        &self,
+        certificates: &CertificatePayload<'_>,
+        _ocsp_response: Option<&[u8]>,
+        _signed_certificate_timestamps: Option<&[u8]>,
+        now: Now,
+    ) -> TlsResult<Verified> {
+        let mut buf = [&[][..]; MAX_CERTIFICATES];
+        let (end_entity, intermediates) = split_chain(certificates, &mut buf)?
+            .split_first()
+            .ok_or(TlsError::CertificateRequired)?;
+
+        self.validate(end_entity, intermediates, KeyPurpose::ClientAuth, now)?;
+        Ok(Verified::assertion())
+    }
+}
+
+impl<'a, H: Hash, V: SignatureVerifier> ServerCertVerifier for PinVerifier<'a, H, V> {
+    /// Checks the pins for server authentication, then checks the end-entity certificate's
+    /// subject alternative names match `server_name`.
+    fn verify_server_cert(
+        &self,
+        certificates: &CertificatePayload<'_>,
+        server_name: ServerName<'_>,
+        _ocsp_response: Option<&[u8]>,
+        _signed_certificate_timestamps: Option<&[u8]>,
+        now: Now,
+    ) -> TlsResult<Verified> {
+        let mut buf = [&[][..]; MAX_CERTIFICATES];
+        let (end_entity, intermediates) = split_chain(certificates, &mut buf)?
+            .split_first()
+            .ok_or(TlsError::DecodeError)?;
+
+        self.verify(end_entity, intermediates, KeyPurpose::ServerAuth, now)?;
+        server_name.verify(&Certificate::parse(end_entity)?)?;
+        Ok(Verified::assertion())
+    }
+}
+
+impl<'a, H: Hash, V: SignatureVerifier> ClientCertVerifier for PinVerifier<'a, H, V> {
+    /// Checks the pins for client authentication.
+    fn verify_client_cert(
+        &self,
+        certificates: &CertificatePayload<'_>,
+        _ocsp_response: Option<&[u8]>,
+        _signed_certificate_timestamps: Option<&[u8]>,
+        now: Now,
+    ) -> TlsResult<Verified> {
+        let mut buf = [&[][..]; MAX_CERTIFICATES];
+        let (end_entity, intermediates) = split_chain(certificates, &mut buf)?
+            .split_first()
+            .ok_or(TlsError::CertificateRequired)?;
+
+        self.verify(end_entity, intermediates, KeyPurpose::ClientAuth, now)?;
+        Ok(Verified::assertion())
+    }
+}
+
+/// Collects the certificates of the entries into `buf`, returning those filled.
+fn split_chain<'c, 'b>(
+    certificates: &CertificatePayload<'c>,
+    buf: &'b mut [&'c [u8]; MAX_CERTIFICATES],
+) -> TlsResult<&'b [&'c [u8]]> {
+    let mut len = 0;
+    for entry in certificates.entries() {
+        let slot = buf.get_mut(len).ok_or(TlsError::BadCertificate)?;
+        *slot = entry.as_ref().data;
+        len += 1;
+    }
+    Ok(&buf[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        handshake::{certificate::CertificateEntry, extension::Extensions},
+        testing::{sha256, RingVerifier, Sha256, NOW},
+        x509::{certificate::tests::test_certs, path::TrustAnchor, pinning::PinSet},
+    };
+    use std::vec::Vec;
+
+    fn entries(chain: &[Vec<u8>]) -> Vec<CertificateEntry<'_>> {
+        chain
+            .iter()
+            .map(|der| CertificateEntry {
+                data: der,
+                extensions: Extensions::empty(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn server() {
+        let ca = test_certs("ecdsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&ca[0]).unwrap()];
+        let verifier = PathValidator::new(&anchors, RingVerifier);
+        let chain = test_certs("ecdsa/end.fullchain");
+        let entries = entries(&chain);
+        let payload = CertificatePayload::new(&[], &entries);
+
+        assert!(verifier
+            .verify_server_cert(
+                &payload,
+                ServerName::new("localhost").unwrap(),
+                None,
+                None,
+                Now::UnixTime(NOW)
+            )
+            .is_ok());
+        assert!(matches!(
+            verifier.verify_server_cert(
+                &payload,
+                ServerName::new("example.com").unwrap(),
+                None,
+                None,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::CertificateNameMismatch)
+        ));
+        assert!(matches!(
+            verifier.verify_server_cert(
+                &CertificatePayload::new(&[], &[]),
+                ServerName::new("localhost").unwrap(),
+                None,
+                None,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::DecodeError)
+        ));
+    }
+
+    #[test]
+    fn client() {
+        let ca = test_certs("ecdsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&ca[0]).unwrap()];
+        let verifier = PathValidator::new(&anchors, RingVerifier);
+        let chain = test_certs("ecdsa/client.fullchain");
+        let entries = entries(&chain);
+
+        assert!(verifier
+            .verify_client_cert(
+                &CertificatePayload::new(&[], &entries),
+                None,
+                None,
+                Now::UnixTime(NOW)
+            )
+            .is_ok());
+        assert!(matches!(
+            verifier.verify_client_cert(
+                &CertificatePayload::new(&[], &[]),
+                None,
+                None,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::CertificateRequired)
+        ));
+    }
+
+    #[test]
+    fn too_many_certificates() {
+        let ca = test_certs("ecdsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&ca[0]).unwrap()];
+        let verifier = PathValidator::new(&anchors, RingVerifier);
+        let chain = test_certs("ecdsa/client.fullchain");
+        let chain = [&chain[..]; 6].concat();
+        let entries = entries(&chain);
+
+        assert!(matches!(
+            verifier.verify_client_cert(
+                &CertificatePayload::new(&[], &entries),
+                None,
+                None,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::BadCertificate)
+        ));
+    }
+
+    #[test]
+    fn pinned() {
+        let chain = test_certs("ecdsa/end.fullchain");
+        let entries = entries(&chain);
+        let payload = CertificatePayload::new(&[], &entries);
+        let name = ServerName::new("localhost").unwrap();
+        let pin = |der: &[u8]| {
+            let mut pin = [0; 32];
+            let cert = Certificate::parse(der).unwrap();
+            pin.copy_from_slice(sha256(cert.public_key().as_bytes()).as_ref());
+            pin
+        };
+        let pins = [pin(&chain[0])];
+        let pin_sets = [PinSet::new("backend", &pins)];
+
+        let verifier = PinVerifier::end_entity_only(&pin_sets, Sha256::default());
+        assert!(verifier
+            .verify_server_cert(&payload, name, None, None, Now::UnixTime(NOW))
+            .is_ok());
+        assert!(verifier
+            .verify_client_cert(&payload, None, None, Now::UnixTime(NOW))
+            .is_ok());
+
+        let ca = test_certs("ecdsa/ca.cert");
+        let anchors = [TrustAnchor::from_certificate(&ca[0]).unwrap()];
+        let path = PathValidator::new(&anchors, RingVerifier);
+        let verifier = PinVerifier::new(&pin_sets, Sha256::default(), path);
+        assert!(verifier
+            .verify_server_cert(&payload, name, None, None, Now::UnixTime(NOW))
+            .is_ok());
+        assert!(matches!(
+            verifier.verify_server_cert(
+                &payload,
+                ServerName::new("example.com").unwrap(),
+                None,
+                None,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::CertificateNameMismatch)
+        ));
+        assert!(verifier
+            .verify_client_cert(&payload, None, None, Now::UnixTime(NOW))
+            .is_ok());
+
+        let pins = [pin(&chain[1])];
+        let pin_sets = [PinSet::new("intermediate", &pins)];
+        assert!(matches!(
+            PinVerifier::end_entity_only(&pin_sets, Sha256::default()).verify_server_cert(
+                &payload,
+                name,
+                None,
+                None,
+                Now::UnixTime(NOW)
+            ),
+            Err(TlsError::CertificatePinMismatch("intermediate"))
+        ));
+    }
+}
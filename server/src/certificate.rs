@@ -0,0 +1,443 @@
+use mtls_message::{
+    crypto::{Digest, SigningKey, MAX_SIGNATURE_LEN},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::{
+        certificate_verify::{CertificateVerifyPayload, Sender},
+        client::ClientHelloPayload,
+        enums::{SignatureAlgorithm, SignatureScheme},
+        extension::{client::ClientExtension, SignatureSchemes},
+    },
+};
+
+/// Certificate chain the server authenticates with, and the key of its end-entity certificate.
+#[derive(Clone, Copy)]
+pub struct ServerCertificate<'a> {
+    /// DER encoded certificates, from the end-entity towards the trust anchor.
+    pub chain: &'a [&'a [u8]],
+    /// Signs the CertificateVerify. The key may live outside the server, such as in a secure
+    /// element, and only needs to support the schemes it advertises.
+    pub key: &'a dyn SigningKey,
+}
+
+impl<'a> ServerCertificate<'a> {
+    /// Builds the server's CertificateVerify, given the transcript hash up to and including its
+    /// Certificate message. The signature is written to `out`.
+    pub fn certificate_verify<'b>(
+        &self,
+        scheme: SignatureScheme,
+        transcript_hash: &Digest,
+        out: &'b mut [u8; MAX_SIGNATURE_LEN],
+    ) -> TlsResult<CertificateVerifyPayload<'b>> {
+        CertificateVerifyPayload::sign(Sender::Server, transcript_hash, self.key, scheme, out)
+    }
+
+    /// Scheme to sign the CertificateVerify with: the key's first the client offered, if any.
+    pub fn select_scheme(&self, hello: &ClientHello<'_>) -> Option<SignatureScheme> {
+        self.key
+            .schemes()
+            .iter()
+            .copied()
+            .find(|scheme| scheme.is_tls13_handshake() && hello.offers(*scheme))
+    }
+}
+
+/// What a server's certificate is chosen by from a ClientHello.
+#[derive(Debug, Clone)]
+pub struct ClientHello<'a> {
+    /// Host name from the server_name extension, if the client sent one.
+    pub server_name: Option<&'a str>,
+    pub signature_schemes: SignatureSchemes<'a>,
+}
+
+impl<'a> ClientHello<'a> {
+    /// Fails with the alert to send when the client didn't send signature_algorithms, which
+    /// TLS 1.3 requires for certificate authentication (RFC 8446, section 4.2.3).
+    pub fn new(hello: &ClientHelloPayload<'a>) -> TlsResult<Self> {
+        let mut server_name = None;
+        let mut signature_schemes = None;
+        for ext in hello.extensions().iter() {
+            match ext.as_ref() {
+                ClientExtension::ServerName(name) => server_name = Some(*name),
+                ClientExtension::SignatureAlgorithms(schemes) => {
+                    signature_schemes = Some(schemes.clone())
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            server_name,
+            signature_schemes: signature_schemes.ok_or(TlsError::MissingExtension)?,
+        })
+    }
+
+    /// Whether the client offered `scheme` in signature_algorithms.
+    pub fn offers(&self, scheme: SignatureScheme) -> bool {
+        self.signature_schemes.iter().any(|s| s == scheme)
+    }
+}
+
+/// Chooses the certificate a server authenticates with.
+///
+/// [`ServerCertificate`] is the simplest implementation, always offering the same certificate,
+/// and [`SniResolver`] picks one by the name the client asked for.
+pub trait ResolvesServerCert {
+    /// Returns the certificate for `hello`, or `None` when there is none the client can accept,
+    /// in which case the handshake fails.
+    fn resolve(&self, hello: &ClientHello<'_>) -> Option<ServerCertificate<'_>>;
+}
+
+impl<'a> ResolvesServerCert for ServerCertificate<'a> {
+    fn resolve(&self, hello: &ClientHello<'_>) -> Option<ServerCertificate<'_>> {
+        self.select_scheme(hello).map(|_| *self)
+    }
+}
+
+/// Certificates served for a host name, for [`SniResolver`].
+#[derive(Clone, Copy)]
+pub struct SniEntry<'a> {
+    /// DNS name, compared without regard to case or a trailing dot.
+    pub name: &'a str,
+    /// Certificates for the name, usually one with an ECDSA key and one with an RSA key.
+    pub certificates: &'a [ServerCertificate<'a>],
+}
+
+/// Resolves certificates by the host name in the client's server_name extension.
+///
+/// Of the certificates for a name, one with a key that signs with an ECDSA scheme the client
+/// offered is preferred, otherwise the first that signs with any scheme the client offered,
+/// such as RSA-PSS.
+#[derive(Clone, Copy)]
+pub struct SniResolver<'a> {
+    pub entries: &'a [SniEntry<'a>],
+    /// Certificates for clients that sent no server_name, or a name with no entry.
+    pub default: &'a [ServerCertificate<'a>],
+}
+
+impl<'a> ResolvesServerCert for SniResolver<'a> {
+    fn resolve(&self, hello: &ClientHello<'_>) -> Option<ServerCertificate<'_>> {
+        let certificates = hello
+            .server_name
+            .and_then(|name| {
+                self.entries
+                    .iter()
+                    .find(|entry| names_match(entry.name, name))
+            })
+            .map_or(self.default, |entry| entry.certificates);
+
+        let is_ecdsa = |scheme: SignatureScheme| {
+            scheme
+                .info()
+                .is_some_and(|info| info.algorithm == SignatureAlgorithm::Ecdsa)
+        };
+        certificates
+            .iter()
+            .find(|cert| cert.select_scheme(hello).is_some_and(is_ecdsa))
+            .or_else(|| {
+                certificates
+                    .iter()
+                    .find(|cert| cert.select_scheme(hello).is_some())
+            })
+            .copied()
+    }
+}
+
+fn names_match(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::convert::TryFrom;
+    use mtls_message::{
+        crypto::SoftwareSigningKey,
+        handshake::certificate_verify::MAX_SIGNED_CONTENT_LEN,
+        testing::{pem, RingSigner},
+        x509::{certificate::Certificate, key::PrivateKey},
+        Codec, Decoder,
+    };
+    use ring::signature;
+    use rustls::{
+        internal::msgs::{
+            base::Payload as r_Payload,
+            codec::Codec as r_Codec,
+            enums::{Compression as r_Compression, ExtensionType as r_ExtensionType},
+            handshake::{
+                ClientExtension as r_ClientExtension, ClientHelloPayload as r_ClientHelloPayload,
+                Random as r_Random, SessionID as r_SessionId,
+                UnknownExtension as r_UnknownExtension,
+            },
+        },
+        CipherSuite as r_CipherSuite, ProtocolVersion as r_ProtocolVersion,
+        SignatureScheme as r_SignatureScheme,
+    };
+    use std::vec::Vec;
+
+    /// DER of a test end-entity certificate and its PKCS#8 key.
+    struct Files {
+        cert: Vec<u8>,
+        key: Vec<u8>,
+    }
+
+    impl Files {
+        fn load(cert: &[u8], key: &[u8]) -> Self {
+            let mut buf = [0; 4096];
+            let cert = pem(cert, &mut buf).to_vec();
+            let key = pem(key, &mut buf).to_vec();
+            Self { cert, key }
+        }
+
+        fn ecdsa() -> Self {
+            Self::load(
+                include_bytes!("../../test-keys/ecdsa/end.cert"),
+                include_bytes!("../../test-keys/ecdsa/end.key"),
+            )
+        }
+
+        fn rsa() -> Self {
+            Self::load(
+                include_bytes!("../../test-keys/rsa/end.cert"),
+                include_bytes!("../../test-keys/rsa/end.key"),
+            )
+        }
+
+        fn signing_key(&self) -> SoftwareSigningKey<'_, RingSigner> {
+            SoftwareSigningKey::new(PrivateKey::from_pkcs8(&self.key).unwrap(), RingSigner)
+        }
+    }
+
+    fn hello_bytes(server_name: Option<&str>, schemes: &[r_SignatureScheme]) -> Vec<u8> {
+        let mut extensions = vec![r_ClientExtension::SignatureAlgorithms(schemes.to_vec())];
+        if let Some(name) = server_name {
+            let mut sni = vec![];
+            sni.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes());
+            sni.push(0);
+            sni.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            sni.extend_from_slice(name.as_bytes());
+            extensions.push(r_ClientExtension::Unknown(r_UnknownExtension {
+                typ: r_ExtensionType::ServerName,
+                payload: r_Payload::new(sni),
+            }));
+        }
+
+        let payload = r_ClientHelloPayload {
+            client_version: r_ProtocolVersion::TLSv1_2,
+            random: r_Random::from_slice(&[0; 32]),
+            session_id: r_SessionId::empty(),
+            cipher_suites: vec![r_CipherSuite::TLS13_AES_128_GCM_SHA256],
+            compression_methods: vec![r_Compression::Null],
+            extensions,
+        };
+        let mut enc = vec![];
+        payload.encode(&mut enc);
+        enc
+    }
+
+    fn with_hello<T>(bytes: &[u8], f: impl FnOnce(&ClientHello<'_>) -> T) -> T {
+        let payload = ClientHelloPayload::decode(&mut Decoder::new(bytes)).unwrap();
+        f(&ClientHello::new(&payload).unwrap())
+    }
+
+    const ECDSA_AND_RSA: &[r_SignatureScheme] = &[
+        r_SignatureScheme::RSA_PSS_SHA256,
+        r_SignatureScheme::ECDSA_NISTP256_SHA256,
+    ];
+    const RSA_ONLY: &[r_SignatureScheme] = &[
+        r_SignatureScheme::RSA_PKCS1_SHA256,
+        r_SignatureScheme::RSA_PSS_SHA384,
+    ];
+
+    #[test]
+    fn certificate_verify() {
+        let ecdsa = Files::ecdsa();
+        let key = ecdsa.signing_key();
+        let chain = [&ecdsa.cert[..]];
+        let certificate = ServerCertificate {
+            chain: &chain,
+            key: &key,
+        };
+
+        let hash = Digest::try_from(&[0x5a; 32][..]).unwrap();
+        let mut out = [0; MAX_SIGNATURE_LEN];
+        let verify = certificate
+            .certificate_verify(SignatureScheme::EcdsaNistp256Sha256, &hash, &mut out)
+            .unwrap();
+        assert_eq!(verify.scheme, SignatureScheme::EcdsaNistp256Sha256);
+        check_signature(&certificate, &hash, &verify);
+    }
+
+    #[test]
+    fn certificate_verify_with_unsupported_scheme() {
+        let ecdsa = Files::ecdsa();
+        let key = ecdsa.signing_key();
+        let certificate = ServerCertificate {
+            chain: &[],
+            key: &key,
+        };
+
+        let hash = Digest::try_from(&[0x5a; 32][..]).unwrap();
+        let mut out = [0; MAX_SIGNATURE_LEN];
+        let result =
+            certificate.certificate_verify(SignatureScheme::EcdsaNistp384Sha384, &hash, &mut out);
+        assert!(matches!(result, Err(TlsError::InternalError(_))));
+    }
+
+    #[test]
+    fn client_hello() {
+        let bytes = hello_bytes(Some("example.com"), RSA_ONLY);
+        with_hello(&bytes, |hello| {
+            assert_eq!(hello.server_name, Some("example.com"));
+            assert!(hello.offers(SignatureScheme::RsaPssSha384));
+            assert!(!hello.offers(SignatureScheme::EcdsaNistp256Sha256));
+        });
+
+        let bytes = hello_bytes(None, RSA_ONLY);
+        with_hello(&bytes, |hello| assert_eq!(hello.server_name, None));
+    }
+
+    #[test]
+    fn client_hello_without_signature_algorithms() {
+        let mut bytes = hello_bytes(None, &[]);
+        let mut payload = r_ClientHelloPayload::read_bytes(&bytes).unwrap();
+        payload.extensions.clear();
+        bytes.clear();
+        payload.encode(&mut bytes);
+
+        let payload = ClientHelloPayload::decode(&mut Decoder::new(&bytes)).unwrap();
+        assert!(matches!(
+            ClientHello::new(&payload),
+            Err(TlsError::MissingExtension)
+        ));
+    }
+
+    #[test]
+    fn single_certificate() {
+        let ecdsa = Files::ecdsa();
+        let key = ecdsa.signing_key();
+        let certificate = ServerCertificate {
+            chain: &[],
+            key: &key,
+        };
+
+        let bytes = hello_bytes(None, ECDSA_AND_RSA);
+        assert!(with_hello(&bytes, |hello| certificate
+            .resolve(hello)
+            .is_some()));
+        let bytes = hello_bytes(None, RSA_ONLY);
+        assert!(with_hello(&bytes, |hello| certificate
+            .resolve(hello)
+            .is_none()));
+    }
+
+    /// Serves ECDSA and RSA certificates for the test certificates' name from one resolver, the
+    /// RSA certificate listed first so the ECDSA preference is the resolver's own.
+    #[test]
+    fn sni_resolver_serves_both_key_types() {
+        let (ecdsa, rsa) = (Files::ecdsa(), Files::rsa());
+        let (ecdsa_key, rsa_key) = (ecdsa.signing_key(), rsa.signing_key());
+        let (ecdsa_chain, rsa_chain) = ([&ecdsa.cert[..]], [&rsa.cert[..]]);
+        let certificates = [
+            ServerCertificate {
+                chain: &rsa_chain,
+                key: &rsa_key,
+            },
+            ServerCertificate {
+                chain: &ecdsa_chain,
+                key: &ecdsa_key,
+            },
+        ];
+        let entries = [SniEntry {
+            name: "localhost",
+            certificates: &certificates,
+        }];
+        let resolver = SniResolver {
+            entries: &entries,
+            default: &[],
+        };
+
+        let cases = [
+            (
+                ECDSA_AND_RSA,
+                &ecdsa.cert,
+                SignatureScheme::EcdsaNistp256Sha256,
+            ),
+            (RSA_ONLY, &rsa.cert, SignatureScheme::RsaPssSha384),
+        ];
+        for (schemes, cert, scheme) in cases.iter() {
+            let bytes = hello_bytes(Some("LocalHost."), schemes);
+            with_hello(&bytes, |hello| {
+                let certificate = resolver.resolve(hello).unwrap();
+                assert_eq!(certificate.chain, [&cert[..]]);
+                assert_eq!(certificate.select_scheme(hello), Some(*scheme));
+
+                let hash = Digest::try_from(&[0x5a; 32][..]).unwrap();
+                let mut out = [0; MAX_SIGNATURE_LEN];
+                let verify = certificate
+                    .certificate_verify(*scheme, &hash, &mut out)
+                    .unwrap();
+                check_signature(&certificate, &hash, &verify);
+            });
+        }
+    }
+
+    #[test]
+    fn sni_resolver_default() {
+        let (ecdsa, rsa) = (Files::ecdsa(), Files::rsa());
+        let (ecdsa_key, rsa_key) = (ecdsa.signing_key(), rsa.signing_key());
+        let (ecdsa_chain, rsa_chain) = ([&ecdsa.cert[..]], [&rsa.cert[..]]);
+        let named = [ServerCertificate {
+            chain: &ecdsa_chain,
+            key: &ecdsa_key,
+        }];
+        let default = [ServerCertificate {
+            chain: &rsa_chain,
+            key: &rsa_key,
+        }];
+        let entries = [SniEntry {
+            name: "localhost",
+            certificates: &named,
+        }];
+        let resolver = SniResolver {
+            entries: &entries,
+            default: &default,
+        };
+
+        for name in &[None, Some("example.com")] {
+            let bytes = hello_bytes(*name, ECDSA_AND_RSA);
+            let chain = with_hello(&bytes, |hello| resolver.resolve(hello).unwrap().chain);
+            assert_eq!(chain, rsa_chain);
+        }
+
+        // The named certificate is never replaced by the default, even when unusable.
+        let bytes = hello_bytes(Some("localhost"), RSA_ONLY);
+        assert!(with_hello(&bytes, |hello| resolver
+            .resolve(hello)
+            .is_none()));
+    }
+
+    fn check_signature(
+        certificate: &ServerCertificate<'_>,
+        hash: &Digest,
+        verify: &CertificateVerifyPayload<'_>,
+    ) {
+        let verification: &dyn signature::VerificationAlgorithm = match verify.scheme {
+            SignatureScheme::EcdsaNistp256Sha256 => &signature::ECDSA_P256_SHA256_ASN1,
+            SignatureScheme::RsaPssSha256 => &signature::RSA_PSS_2048_8192_SHA256,
+            SignatureScheme::RsaPssSha384 => &signature::RSA_PSS_2048_8192_SHA384,
+            SignatureScheme::RsaPssSha512 => &signature::RSA_PSS_2048_8192_SHA512,
+            scheme => panic!("unexpected scheme {:?}", scheme),
+        };
+        let mut buf = [0; MAX_SIGNED_CONTENT_LEN];
+        let content = CertificateVerifyPayload::signed_content(Sender::Server, hash, &mut buf);
+        let cert = Certificate::parse(certificate.chain[0]).unwrap();
+        signature::verify(
+            verification,
+            untrusted::Input::from(cert.public_key().key()),
+            untrusted::Input::from(content),
+            untrusted::Input::from(verify.signature),
+        )
+        .unwrap();
+    }
+}
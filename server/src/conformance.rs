@@ -0,0 +1,423 @@
+use managed::ManagedSlice;
+use mtls_message::handshake::{
+    client::ClientHelloPayload,
+    enums::{CompressionMethod, ExtensionType, NamedGroup, ProtocolVersion},
+    extension::client::ClientExtension,
+};
+
+/// Way in which a TLS 1.3 ClientHello breaks RFC 8446.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// legacy_version isn't TLS 1.2.
+    LegacyVersion(ProtocolVersion),
+    /// legacy_compression_methods isn't just the null method.
+    CompressionMethods,
+    /// An extension required by section 9.2 is missing.
+    MissingExtension(ExtensionType),
+    /// An extension appears more than once.
+    DuplicateExtension(ExtensionType),
+    /// pre_shared_key isn't the last extension.
+    PreSharedKeyNotLast,
+    /// A key share is for a group that isn't in supported_groups.
+    KeyShareNotSupported(NamedGroup),
+    /// A key share is out of the order of supported_groups, or repeats a group.
+    KeyShareOrder(NamedGroup),
+}
+
+/// Violations found in a ClientHello, kept in a caller-provided buffer.
+pub struct Violations<'v> {
+    buf: ManagedSlice<'v, Option<Violation>>,
+    len: usize,
+    truncated: bool,
+}
+
+impl<'v> Violations<'v> {
+    fn new<T: Into<ManagedSlice<'v, Option<Violation>>>>(buf: T) -> Self {
+        Self {
+            buf: buf.into(),
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Violation> {
+        self.buf[..self.len].iter().filter_map(Option::as_ref)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether violations were left out because the buffer was full.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn push(&mut self, violation: Violation) {
+        match self.buf {
+            ManagedSlice::Borrowed(ref mut buf) => match buf.get_mut(self.len) {
+                Some(slot) => *slot = Some(violation),
+                None => {
+                    self.truncated = true;
+                    return;
+                }
+            },
+            ManagedSlice::Owned(ref mut buf) => {
+                buf.truncate(self.len);
+                buf.push(Some(violation));
+            }
+        }
+        self.len += 1;
+    }
+}
+
+/// Checks a TLS 1.3 ClientHello against RFC 8446, returning every violation found in `buf`.
+///
+/// Besides the mandatory extensions of section 9.2, this checks the legacy fields, that no
+/// extension is repeated, that pre_shared_key comes last, and that key shares follow the order
+/// of supported_groups (section 4.2.8).
+pub fn check_client_hello<'v, T>(hello: &ClientHelloPayload<'_>, buf: T) -> Violations<'v>
+where
+    T: Into<ManagedSlice<'v, Option<Violation>>>,
+{
+    let mut violations = Violations::new(buf);
+
+    if hello.client_version() != ProtocolVersion::TLSv1_2 {
+        violations.push(Violation::LegacyVersion(hello.client_version()));
+    }
+    let methods = hello.compression_methods();
+    if methods.len() != 1 || !methods.iter().all(|m| m == CompressionMethod::Null) {
+        violations.push(Violation::CompressionMethods);
+    }
+
+    check_extensions(hello, &mut violations);
+    check_mandatory_extensions(hello, &mut violations);
+    check_key_shares(hello, &mut violations);
+    violations
+}
+
+fn check_extensions(hello: &ClientHelloPayload<'_>, violations: &mut Violations<'_>) {
+    let types = || hello.extensions().iter().map(|ext| ext.as_ref().ty());
+    let count = types().count();
+
+    for (i, ty) in types().enumerate() {
+        // Only the first repeat is reported.
+        if types().take(i).filter(|t| *t == ty).count() == 1 {
+            violations.push(Violation::DuplicateExtension(ty));
+        }
+        if ty == ExtensionType::PreSharedKey && i != count - 1 {
+            violations.push(Violation::PreSharedKeyNotLast);
+        }
+    }
+}
+
+/// Checks the extensions required by section 9.2.
+fn check_mandatory_extensions(hello: &ClientHelloPayload<'_>, violations: &mut Violations<'_>) {
+    let has = |ty: ExtensionType| hello.extensions().iter().any(|ext| ext.as_ref().ty() == ty);
+    let mut require = |ty: ExtensionType| {
+        if !has(ty) {
+            violations.push(Violation::MissingExtension(ty));
+        }
+    };
+
+    require(ExtensionType::SupportedVersions);
+    if has(ExtensionType::PreSharedKey) {
+        require(ExtensionType::PskKeyExchangeModes);
+    } else {
+        require(ExtensionType::SignatureAlgorithms);
+    }
+    if !has(ExtensionType::PreSharedKey) || has(ExtensionType::KeyShare) {
+        require(ExtensionType::SupportedGroups);
+    }
+    if has(ExtensionType::SupportedGroups) {
+        require(ExtensionType::KeyShare);
+    }
+}
+
+/// Checks key shares are for groups in supported_groups, in the same order and without repeats.
+fn check_key_shares(hello: &ClientHelloPayload<'_>, violations: &mut Violations<'_>) {
+    let groups = hello
+        .extensions()
+        .iter()
+        .find_map(|ext| match ext.as_ref() {
+            ClientExtension::SupportedGroups(groups) => Some(groups.clone()),
+            _ => None,
+        });
+    let shares = hello
+        .extensions()
+        .iter()
+        .find_map(|ext| match ext.as_ref() {
+            ClientExtension::KeyShare(shares) => Some(shares.clone()),
+            _ => None,
+        });
+    let (groups, shares) = match (groups, shares) {
+        (Some(groups), Some(shares)) => (groups, shares),
+        _ => return,
+    };
+
+    let mut last_position = None;
+    for share in shares.iter() {
+        let group = share.as_ref().group;
+        match groups.iter().position(|g| g == group) {
+            None => violations.push(Violation::KeyShareNotSupported(group)),
+            Some(position) => {
+                if last_position.is_some_and(|last| position <= last) {
+                    violations.push(Violation::KeyShareOrder(group));
+                }
+                last_position = Some(position);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mtls_message::{Codec, Decoder};
+    use rustls::{
+        internal::msgs::{
+            base::Payload as r_Payload,
+            codec::Codec as r_Codec,
+            enums::{
+                Compression as r_Compression, ExtensionType as r_ExtensionType,
+                NamedGroup as r_NamedGroup,
+            },
+            handshake::{
+                ClientExtension as r_ClientExtension, ClientHelloPayload as r_ClientHelloPayload,
+                KeyShareEntry as r_KeyShareEntry, Random as r_Random, SessionID as r_SessionId,
+                UnknownExtension as r_UnknownExtension,
+            },
+        },
+        CipherSuite as r_CipherSuite, ProtocolVersion as r_ProtocolVersion,
+        SignatureScheme as r_SignatureScheme,
+    };
+    use std::vec::Vec;
+
+    fn client_hello() -> r_ClientHelloPayload {
+        r_ClientHelloPayload {
+            client_version: r_ProtocolVersion::TLSv1_2,
+            random: r_Random::from_slice(&[0; 32]),
+            session_id: r_SessionId::empty(),
+            cipher_suites: vec![r_CipherSuite::TLS13_AES_128_GCM_SHA256],
+            compression_methods: vec![r_Compression::Null],
+            extensions: vec![
+                r_ClientExtension::SupportedVersions(vec![r_ProtocolVersion::TLSv1_3]),
+                r_ClientExtension::NamedGroups(vec![r_NamedGroup::X25519, r_NamedGroup::secp256r1]),
+                key_share(&[r_NamedGroup::X25519, r_NamedGroup::secp256r1]),
+                r_ClientExtension::SignatureAlgorithms(vec![
+                    r_SignatureScheme::ECDSA_NISTP256_SHA256,
+                ]),
+            ],
+        }
+    }
+
+    fn key_share(groups: &[r_NamedGroup]) -> r_ClientExtension {
+        r_ClientExtension::KeyShare(
+            groups
+                .iter()
+                .map(|group| r_KeyShareEntry::new(*group, &[1, 2, 3]))
+                .collect(),
+        )
+    }
+
+    fn unknown(typ: r_ExtensionType) -> r_ClientExtension {
+        r_ClientExtension::Unknown(r_UnknownExtension {
+            typ,
+            payload: r_Payload::new(vec![0]),
+        })
+    }
+
+    fn check(hello: r_ClientHelloPayload) -> Vec<Violation> {
+        let bytes = hello.get_encoding();
+        let hello = ClientHelloPayload::decode(&mut Decoder::new(&bytes)).unwrap();
+        let violations = check_client_hello(&hello, vec![]);
+
+        assert_eq!(violations.len(), violations.iter().count());
+        violations.iter().copied().collect()
+    }
+
+    #[test]
+    fn conformant() {
+        assert!(check(client_hello()).is_empty());
+    }
+
+    #[test]
+    fn legacy_version() {
+        let mut hello = client_hello();
+        hello.client_version = r_ProtocolVersion::TLSv1_3;
+
+        assert_eq!(
+            check(hello),
+            vec![Violation::LegacyVersion(ProtocolVersion::TLSv1_3)]
+        );
+    }
+
+    #[test]
+    fn compression_methods() {
+        let mut hello = client_hello();
+        hello.compression_methods = vec![r_Compression::Null, r_Compression::Deflate];
+
+        assert_eq!(check(hello), vec![Violation::CompressionMethods]);
+    }
+
+    #[test]
+    fn missing_extensions() {
+        let mut hello = client_hello();
+        hello.extensions.clear();
+
+        assert_eq!(
+            check(hello),
+            vec![
+                Violation::MissingExtension(ExtensionType::SupportedVersions),
+                Violation::MissingExtension(ExtensionType::SignatureAlgorithms),
+                Violation::MissingExtension(ExtensionType::SupportedGroups),
+            ]
+        );
+    }
+
+    #[test]
+    fn supported_groups_without_key_share() {
+        let mut hello = client_hello();
+        hello.extensions.remove(2);
+
+        assert_eq!(
+            check(hello),
+            vec![Violation::MissingExtension(ExtensionType::KeyShare)]
+        );
+    }
+
+    #[test]
+    fn psk_only() {
+        let mut hello = client_hello();
+        hello.extensions = vec![
+            r_ClientExtension::SupportedVersions(vec![r_ProtocolVersion::TLSv1_3]),
+            unknown(r_ExtensionType::PSKKeyExchangeModes),
+            unknown(r_ExtensionType::PreSharedKey),
+        ];
+
+        assert!(check(hello).is_empty());
+    }
+
+    #[test]
+    fn psk_without_modes() {
+        let mut hello = client_hello();
+        hello
+            .extensions
+            .push(unknown(r_ExtensionType::PreSharedKey));
+
+        assert_eq!(
+            check(hello),
+            vec![Violation::MissingExtension(
+                ExtensionType::PskKeyExchangeModes
+            )]
+        );
+    }
+
+    #[test]
+    fn pre_shared_key_not_last() {
+        let mut hello = client_hello();
+        hello
+            .extensions
+            .insert(0, unknown(r_ExtensionType::PSKKeyExchangeModes));
+        hello
+            .extensions
+            .insert(0, unknown(r_ExtensionType::PreSharedKey));
+
+        assert_eq!(check(hello), vec![Violation::PreSharedKeyNotLast]);
+    }
+
+    #[test]
+    fn duplicate_extensions() {
+        let mut hello = client_hello();
+        hello.extensions.push(unknown(r_ExtensionType::Padding));
+        hello.extensions.push(unknown(r_ExtensionType::Padding));
+        hello.extensions.push(unknown(r_ExtensionType::Padding));
+        hello
+            .extensions
+            .push(r_ClientExtension::SupportedVersions(vec![
+                r_ProtocolVersion::TLSv1_3,
+            ]));
+
+        assert_eq!(
+            check(hello),
+            vec![
+                Violation::DuplicateExtension(ExtensionType::Padding),
+                Violation::DuplicateExtension(ExtensionType::SupportedVersions),
+            ]
+        );
+    }
+
+    #[test]
+    fn key_share_not_supported() {
+        let mut hello = client_hello();
+        hello.extensions[2] = key_share(&[r_NamedGroup::secp384r1]);
+
+        assert_eq!(
+            check(hello),
+            vec![Violation::KeyShareNotSupported(NamedGroup::Secp384r1)]
+        );
+    }
+
+    #[test]
+    fn key_share_order() {
+        let mut hello = client_hello();
+        hello.extensions[2] = key_share(&[r_NamedGroup::secp256r1, r_NamedGroup::X25519]);
+
+        assert_eq!(
+            check(hello),
+            vec![Violation::KeyShareOrder(NamedGroup::X25519)]
+        );
+    }
+
+    #[test]
+    fn repeated_key_share() {
+        let mut hello = client_hello();
+        hello.extensions[2] = key_share(&[r_NamedGroup::X25519, r_NamedGroup::X25519]);
+
+        assert_eq!(
+            check(hello),
+            vec![Violation::KeyShareOrder(NamedGroup::X25519)]
+        );
+    }
+
+    #[test]
+    fn all_violations() {
+        let mut hello = client_hello();
+        hello.client_version = r_ProtocolVersion::TLSv1_0;
+        hello.compression_methods = vec![];
+        hello.extensions.remove(3);
+        hello.extensions.remove(1);
+
+        assert_eq!(
+            check(hello),
+            vec![
+                Violation::LegacyVersion(ProtocolVersion::Unknown(0x0301)),
+                Violation::CompressionMethods,
+                Violation::MissingExtension(ExtensionType::SignatureAlgorithms),
+                Violation::MissingExtension(ExtensionType::SupportedGroups),
+            ]
+        );
+    }
+
+    #[test]
+    fn borrowed_buffer_truncated() {
+        let mut hello = client_hello();
+        hello.client_version = r_ProtocolVersion::TLSv1_0;
+        hello.compression_methods = vec![];
+        let bytes = hello.get_encoding();
+        let hello = ClientHelloPayload::decode(&mut Decoder::new(&bytes)).unwrap();
+
+        let mut buf = [None; 1];
+        let violations = check_client_hello(&hello, buf.as_mut());
+        assert_eq!(violations.len(), 1);
+        assert!(violations.is_truncated());
+        assert_eq!(
+            violations.iter().next(),
+            Some(&Violation::LegacyVersion(ProtocolVersion::Unknown(0x0301)))
+        );
+    }
+}
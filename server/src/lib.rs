@@ -0,0 +1,11 @@
+#![no_std]
+
+#[cfg(test)]
+#[macro_use]
+extern crate std;
+
+pub mod certificate;
+pub mod conformance;
+pub mod negotiation;
+pub mod record;
+pub mod stateless;
@@ -0,0 +1,571 @@
+use mtls_message::{
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::{
+        client::ClientHelloPayload,
+        enums::{CipherSuite, NamedGroup, ProtocolVersion, SignatureAlgorithm, SignatureScheme},
+        extension::{client::ClientExtension, KeyShareEntries, KeyShareEntry, NamedGroups},
+    },
+};
+
+/// Whose order of preference decides between cipher suites both sides support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preference {
+    Server,
+    Client,
+}
+
+/// Type of the key in the server's certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateKey {
+    Rsa,
+    EcdsaNistp256,
+    EcdsaNistp384,
+    EcdsaNistp521,
+    Ed25519,
+    Ed448,
+}
+
+impl CertificateKey {
+    /// Whether the key can sign TLS 1.3 handshake messages with `scheme`.
+    pub fn supports(self, scheme: SignatureScheme) -> bool {
+        if !scheme.is_tls13_handshake() {
+            return false;
+        }
+
+        match self {
+            CertificateKey::Rsa => scheme
+                .info()
+                .is_some_and(|info| info.algorithm == SignatureAlgorithm::RsaPss),
+            CertificateKey::EcdsaNistp256 => scheme == SignatureScheme::EcdsaNistp256Sha256,
+            CertificateKey::EcdsaNistp384 => scheme == SignatureScheme::EcdsaNistp384Sha384,
+            CertificateKey::EcdsaNistp521 => scheme == SignatureScheme::EcdsaNistp521Sha512,
+            CertificateKey::Ed25519 => scheme == SignatureScheme::Ed25519,
+            CertificateKey::Ed448 => scheme == SignatureScheme::Ed448,
+        }
+    }
+}
+
+/// What the server is willing to negotiate. Lists are in the server's order of preference.
+#[derive(Debug, Clone, Copy)]
+pub struct Policy<'p> {
+    pub cipher_suites: &'p [CipherSuite],
+    pub cipher_suite_preference: Preference,
+    pub groups: &'p [NamedGroup],
+    pub signature_schemes: &'p [SignatureScheme],
+    pub certificate_key: CertificateKey,
+}
+
+/// How keys will be exchanged with the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyExchange<'a> {
+    /// The client sent a share for the selected group.
+    Share(KeyShareEntry<'a>),
+    /// The client supports the selected group but didn't send a share for it, so a
+    /// HelloRetryRequest is needed.
+    HelloRetry(NamedGroup),
+}
+
+/// Parameters of a TLS 1.3 handshake agreed from a ClientHello.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Negotiated<'a> {
+    pub version: ProtocolVersion,
+    pub cipher_suite: CipherSuite,
+    pub key_exchange: KeyExchange<'a>,
+    pub signature_scheme: SignatureScheme,
+}
+
+/// Agrees on the parameters of a TLS 1.3 handshake with the client.
+///
+/// On failure, the error is the alert to send to the client.
+///
+/// The group is the first in the policy the client sent a key share for. When there is none, it
+/// is the first in the policy the client supports, and a HelloRetryRequest is needed.
+pub fn negotiate<'a>(
+    hello: &ClientHelloPayload<'a>,
+    policy: &Policy<'_>,
+) -> TlsResult<Negotiated<'a>> {
+    let version = select_version(hello)?;
+    let cipher_suite = select_cipher_suite(hello, policy)?;
+    let key_exchange = select_key_exchange(hello, policy)?;
+    let signature_scheme = select_signature_scheme(hello, policy)?;
+
+    Ok(Negotiated {
+        version,
+        cipher_suite,
+        key_exchange,
+        signature_scheme,
+    })
+}
+
+fn select_version(hello: &ClientHelloPayload<'_>) -> TlsResult<ProtocolVersion> {
+    // Clients that don't send supported_versions only support TLS 1.2 and earlier.
+    let tls13 = find_extension(hello, |ext| match ext {
+        ClientExtension::SupportedVersions(versions) => {
+            Some(versions.iter().any(|v| v == ProtocolVersion::TLSv1_3))
+        }
+        _ => None,
+    });
+
+    match tls13 {
+        Some(true) => Ok(ProtocolVersion::TLSv1_3),
+        _ => Err(TlsError::ProtocolVersion),
+    }
+}
+
+fn select_cipher_suite(
+    hello: &ClientHelloPayload<'_>,
+    policy: &Policy<'_>,
+) -> TlsResult<CipherSuite> {
+    let offered = |suite: &CipherSuite| hello.cipher_suites().iter().any(|s| s == *suite);
+    let supported =
+        |suite: &CipherSuite| suite.info().is_some() && policy.cipher_suites.contains(suite);
+
+    let suite = match policy.cipher_suite_preference {
+        Preference::Server => policy
+            .cipher_suites
+            .iter()
+            .find(|suite| suite.info().is_some() && offered(suite))
+            .copied(),
+        Preference::Client => hello
+            .cipher_suites()
+            .iter()
+            .map(|suite| *suite.as_ref())
+            .find(supported),
+    };
+    suite.ok_or(TlsError::HandshakeFailure)
+}
+
+fn select_key_exchange<'a>(
+    hello: &ClientHelloPayload<'a>,
+    policy: &Policy<'_>,
+) -> TlsResult<KeyExchange<'a>> {
+    let groups = find_extension(hello, |ext| match ext {
+        ClientExtension::SupportedGroups(groups) => Some(groups.clone()),
+        _ => None,
+    })
+    .ok_or(TlsError::MissingExtension)?;
+    let shares = find_extension(hello, |ext| match ext {
+        ClientExtension::KeyShare(shares) => Some(shares.clone()),
+        _ => None,
+    })
+    .ok_or(TlsError::MissingExtension)?;
+    check_key_shares(&groups, &shares)?;
+
+    let shared = policy.groups.iter().find_map(|group| {
+        shares
+            .iter()
+            .map(|share| *share.as_ref())
+            .find(|share| share.group == *group)
+    });
+    if let Some(share) = shared {
+        return Ok(KeyExchange::Share(share));
+    }
+
+    policy
+        .groups
+        .iter()
+        .find(|group| groups.iter().any(|g| g == **group))
+        .map(|group| KeyExchange::HelloRetry(*group))
+        .ok_or(TlsError::HandshakeFailure)
+}
+
+/// Checks every key share is for a distinct group the client supports (RFC 8446, section
+/// 4.2.8).
+fn check_key_shares(groups: &NamedGroups<'_>, shares: &KeyShareEntries<'_>) -> TlsResult<()> {
+    for (i, share) in shares.iter().enumerate() {
+        let group = share.as_ref().group;
+        let supported = groups.iter().any(|g| g == group);
+        let duplicate = shares.iter().take(i).any(|s| s.as_ref().group == group);
+        if !supported || duplicate {
+            return Err(TlsError::IllegalParameter);
+        }
+    }
+    Ok(())
+}
+
+fn select_signature_scheme(
+    hello: &ClientHelloPayload<'_>,
+    policy: &Policy<'_>,
+) -> TlsResult<SignatureScheme> {
+    let schemes = find_extension(hello, |ext| match ext {
+        ClientExtension::SignatureAlgorithms(schemes) => Some(schemes.clone()),
+        _ => None,
+    })
+    .ok_or(TlsError::MissingExtension)?;
+
+    policy
+        .signature_schemes
+        .iter()
+        .copied()
+        .filter(|scheme| policy.certificate_key.supports(*scheme))
+        .find(|scheme| schemes.iter().any(|s| s == *scheme))
+        .ok_or(TlsError::HandshakeFailure)
+}
+
+fn find_extension<'a, T>(
+    hello: &ClientHelloPayload<'a>,
+    f: impl Fn(&ClientExtension<'a>) -> Option<T>,
+) -> Option<T> {
+    hello.extensions().iter().find_map(|ext| f(ext.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mtls_message::{Codec, Decoder};
+    use rustls::{
+        internal::msgs::{
+            codec::Codec as r_Codec,
+            enums::{Compression as r_Compression, NamedGroup as r_NamedGroup},
+            handshake::{
+                ClientExtension as r_ClientExtension, ClientHelloPayload as r_ClientHelloPayload,
+                KeyShareEntry as r_KeyShareEntry, Random as r_Random, SessionID as r_SessionId,
+            },
+        },
+        CipherSuite as r_CipherSuite, ProtocolVersion as r_ProtocolVersion,
+        SignatureScheme as r_SignatureScheme,
+    };
+    use std::vec::Vec;
+
+    const POLICY: Policy<'static> = Policy {
+        cipher_suites: &[
+            CipherSuite::TlsAes256GcmSha384,
+            CipherSuite::TlsAes128GcmSha256,
+        ],
+        cipher_suite_preference: Preference::Server,
+        groups: &[NamedGroup::X25519, NamedGroup::Secp256r1],
+        signature_schemes: &[
+            SignatureScheme::EcdsaNistp256Sha256,
+            SignatureScheme::RsaPssSha256,
+            SignatureScheme::RsaPkcs1Sha256,
+        ],
+        certificate_key: CertificateKey::EcdsaNistp256,
+    };
+
+    fn versions() -> r_ClientExtension {
+        r_ClientExtension::SupportedVersions(vec![
+            r_ProtocolVersion::TLSv1_3,
+            r_ProtocolVersion::TLSv1_2,
+        ])
+    }
+
+    fn groups() -> r_ClientExtension {
+        r_ClientExtension::NamedGroups(vec![r_NamedGroup::secp256r1, r_NamedGroup::X25519])
+    }
+
+    fn key_share(groups: &[r_NamedGroup]) -> r_ClientExtension {
+        r_ClientExtension::KeyShare(
+            groups
+                .iter()
+                .map(|group| r_KeyShareEntry::new(*group, &[1, 2, 3]))
+                .collect(),
+        )
+    }
+
+    fn signature_algorithms() -> r_ClientExtension {
+        r_ClientExtension::SignatureAlgorithms(vec![
+            r_SignatureScheme::RSA_PKCS1_SHA256,
+            r_SignatureScheme::RSA_PSS_SHA256,
+            r_SignatureScheme::ECDSA_NISTP256_SHA256,
+        ])
+    }
+
+    fn full_extensions() -> Vec<r_ClientExtension> {
+        vec![
+            versions(),
+            groups(),
+            key_share(&[r_NamedGroup::secp256r1]),
+            signature_algorithms(),
+        ]
+    }
+
+    fn rustls_bytes(
+        cipher_suites: Vec<r_CipherSuite>,
+        extensions: Vec<r_ClientExtension>,
+    ) -> Vec<u8> {
+        let payload = r_ClientHelloPayload {
+            client_version: r_ProtocolVersion::TLSv1_2,
+            random: r_Random::from_slice(&[0; 32]),
+            session_id: r_SessionId::empty(),
+            cipher_suites,
+            compression_methods: vec![r_Compression::Null],
+            extensions,
+        };
+        let mut enc = vec![];
+        payload.encode(&mut enc);
+        enc
+    }
+
+    fn all_suites() -> Vec<r_CipherSuite> {
+        vec![
+            r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+            r_CipherSuite::TLS13_AES_256_GCM_SHA384,
+        ]
+    }
+
+    fn negotiate_bytes<'a>(bytes: &'a [u8], policy: &Policy<'_>) -> TlsResult<Negotiated<'a>> {
+        let hello = ClientHelloPayload::decode(&mut Decoder::new(bytes)).unwrap();
+        negotiate(&hello, policy)
+    }
+
+    #[test]
+    fn full_handshake() {
+        let bytes = rustls_bytes(all_suites(), full_extensions());
+
+        assert_eq!(
+            negotiate_bytes(&bytes, &POLICY).unwrap(),
+            Negotiated {
+                version: ProtocolVersion::TLSv1_3,
+                cipher_suite: CipherSuite::TlsAes256GcmSha384,
+                key_exchange: KeyExchange::Share(KeyShareEntry {
+                    group: NamedGroup::Secp256r1,
+                    payload: &[1, 2, 3],
+                }),
+                signature_scheme: SignatureScheme::EcdsaNistp256Sha256,
+            }
+        );
+    }
+
+    #[test]
+    fn client_cipher_suite_preference() {
+        let bytes = rustls_bytes(all_suites(), full_extensions());
+        let policy = Policy {
+            cipher_suite_preference: Preference::Client,
+            ..POLICY
+        };
+
+        assert_eq!(
+            negotiate_bytes(&bytes, &policy).unwrap().cipher_suite,
+            CipherSuite::TlsAes128GcmSha256
+        );
+    }
+
+    #[test]
+    fn grease_ignored() {
+        let mut cipher_suites = all_suites();
+        cipher_suites.insert(0, r_CipherSuite::Unknown(0x3a3a));
+        let bytes = rustls_bytes(
+            cipher_suites,
+            vec![
+                r_ClientExtension::SupportedVersions(vec![
+                    r_ProtocolVersion::Unknown(0x7a7a),
+                    r_ProtocolVersion::TLSv1_3,
+                ]),
+                r_ClientExtension::NamedGroups(vec![
+                    r_NamedGroup::Unknown(0xeaea),
+                    r_NamedGroup::X25519,
+                ]),
+                key_share(&[r_NamedGroup::Unknown(0xeaea), r_NamedGroup::X25519]),
+                r_ClientExtension::SignatureAlgorithms(vec![
+                    r_SignatureScheme::Unknown(0x1a1a),
+                    r_SignatureScheme::ECDSA_NISTP256_SHA256,
+                ]),
+            ],
+        );
+        let policy = Policy {
+            cipher_suite_preference: Preference::Client,
+            ..POLICY
+        };
+
+        assert_eq!(
+            negotiate_bytes(&bytes, &policy).unwrap(),
+            Negotiated {
+                version: ProtocolVersion::TLSv1_3,
+                cipher_suite: CipherSuite::TlsAes128GcmSha256,
+                key_exchange: KeyExchange::Share(KeyShareEntry {
+                    group: NamedGroup::X25519,
+                    payload: &[1, 2, 3],
+                }),
+                signature_scheme: SignatureScheme::EcdsaNistp256Sha256,
+            }
+        );
+    }
+
+    #[test]
+    fn no_common_cipher_suite() {
+        let bytes = rustls_bytes(
+            vec![r_CipherSuite::TLS13_CHACHA20_POLY1305_SHA256],
+            full_extensions(),
+        );
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &POLICY),
+            Err(TlsError::HandshakeFailure)
+        ));
+    }
+
+    #[test]
+    fn unknown_cipher_suite_in_policy() {
+        let bytes = rustls_bytes(vec![r_CipherSuite::Unknown(0x00ff)], full_extensions());
+        let policy = Policy {
+            cipher_suites: &[CipherSuite::Unknown(0x00ff)],
+            ..POLICY
+        };
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &policy),
+            Err(TlsError::HandshakeFailure)
+        ));
+    }
+
+    #[test]
+    fn missing_supported_versions() {
+        let mut extensions = full_extensions();
+        extensions.remove(0);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &POLICY),
+            Err(TlsError::ProtocolVersion)
+        ));
+    }
+
+    #[test]
+    fn tls12_only() {
+        let mut extensions = full_extensions();
+        extensions[0] = r_ClientExtension::SupportedVersions(vec![r_ProtocolVersion::TLSv1_2]);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &POLICY),
+            Err(TlsError::ProtocolVersion)
+        ));
+    }
+
+    #[test]
+    fn preferred_key_share() {
+        let mut extensions = full_extensions();
+        extensions[2] = key_share(&[r_NamedGroup::secp256r1, r_NamedGroup::X25519]);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert_eq!(
+            negotiate_bytes(&bytes, &POLICY).unwrap().key_exchange,
+            KeyExchange::Share(KeyShareEntry {
+                group: NamedGroup::X25519,
+                payload: &[1, 2, 3],
+            })
+        );
+    }
+
+    #[test]
+    fn hello_retry_for_supported_group() {
+        let mut extensions = full_extensions();
+        extensions[2] = key_share(&[]);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert_eq!(
+            negotiate_bytes(&bytes, &POLICY).unwrap().key_exchange,
+            KeyExchange::HelloRetry(NamedGroup::X25519)
+        );
+    }
+
+    #[test]
+    fn no_common_group() {
+        let bytes = rustls_bytes(all_suites(), full_extensions());
+        let policy = Policy {
+            groups: &[NamedGroup::X448],
+            ..POLICY
+        };
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &policy),
+            Err(TlsError::HandshakeFailure)
+        ));
+    }
+
+    #[test]
+    fn missing_key_share() {
+        let mut extensions = full_extensions();
+        extensions.remove(2);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &POLICY),
+            Err(TlsError::MissingExtension)
+        ));
+    }
+
+    #[test]
+    fn missing_supported_groups() {
+        let mut extensions = full_extensions();
+        extensions.remove(1);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &POLICY),
+            Err(TlsError::MissingExtension)
+        ));
+    }
+
+    #[test]
+    fn key_share_for_unsupported_group() {
+        let mut extensions = full_extensions();
+        extensions[2] = key_share(&[r_NamedGroup::secp384r1]);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &POLICY),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn duplicate_key_share() {
+        let mut extensions = full_extensions();
+        extensions[2] = key_share(&[r_NamedGroup::X25519, r_NamedGroup::X25519]);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &POLICY),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn signature_scheme_for_rsa_key() {
+        let bytes = rustls_bytes(all_suites(), full_extensions());
+        let policy = Policy {
+            certificate_key: CertificateKey::Rsa,
+            ..POLICY
+        };
+
+        assert_eq!(
+            negotiate_bytes(&bytes, &policy).unwrap().signature_scheme,
+            SignatureScheme::RsaPssSha256
+        );
+    }
+
+    #[test]
+    fn no_signature_scheme_for_key() {
+        let bytes = rustls_bytes(all_suites(), full_extensions());
+        let policy = Policy {
+            certificate_key: CertificateKey::Ed25519,
+            ..POLICY
+        };
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &policy),
+            Err(TlsError::HandshakeFailure)
+        ));
+    }
+
+    #[test]
+    fn missing_signature_algorithms() {
+        let mut extensions = full_extensions();
+        extensions.remove(3);
+        let bytes = rustls_bytes(all_suites(), extensions);
+
+        assert!(matches!(
+            negotiate_bytes(&bytes, &POLICY),
+            Err(TlsError::MissingExtension)
+        ));
+    }
+
+    #[test]
+    fn certificate_key_supports() {
+        assert!(CertificateKey::Rsa.supports(SignatureScheme::RsaPssSha512));
+        assert!(!CertificateKey::Rsa.supports(SignatureScheme::RsaPkcs1Sha256));
+        assert!(!CertificateKey::EcdsaNistp256.supports(SignatureScheme::EcdsaNistp384Sha384));
+        assert!(CertificateKey::Ed448.supports(SignatureScheme::Ed448));
+    }
+}
@@ -0,0 +1,264 @@
+use mtls_message::{
+    crypto::Aead,
+    error::{Error as TlsError, Result as TlsResult},
+    record::{
+        change_cipher_spec::ChangeCipherSpecFilter, protection::RecordProtection, ContentType,
+        RecordHeader, MAX_FRAGMENT_LEN, RECORD_HEADER_SIZE,
+    },
+    Codec, Decoder,
+};
+
+/// What [`RecordReader::read`] found at the start of the buffer.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Read<'b> {
+    /// The buffer doesn't hold a whole record yet.
+    Incomplete,
+    /// A record of `len` bytes, with its content decrypted in place if records are protected.
+    Record {
+        len: usize,
+        ty: ContentType,
+        content: &'b [u8],
+    },
+    /// The ChangeCipherSpec record of a client in middlebox compatibility mode, `len` bytes
+    /// long, which was discarded (RFC 8446, appendix D.4).
+    Discarded { len: usize },
+}
+
+/// Reads the records received from a client.
+///
+/// A client in middlebox compatibility mode sends a single unprotected ChangeCipherSpec record
+/// between its ClientHello and its Finished, which is discarded. Any other ChangeCipherSpec
+/// record is rejected.
+pub struct RecordReader<A: Aead> {
+    protection: Option<RecordProtection<A>>,
+    change_cipher_spec: ChangeCipherSpecFilter,
+    client_hello_received: bool,
+}
+
+impl<A: Aead> RecordReader<A> {
+    pub fn new() -> Self {
+        Self {
+            protection: None,
+            change_cipher_spec: ChangeCipherSpecFilter::new(),
+            client_hello_received: false,
+        }
+    }
+
+    /// Protects records read from now on.
+    pub fn set_protection(&mut self, protection: RecordProtection<A>) {
+        self.protection = Some(protection);
+    }
+
+    /// Marks the end of the handshake, once the client's Finished was received. The client may
+    /// no longer send a ChangeCipherSpec record (RFC 8446, section 5).
+    pub fn complete_handshake(&mut self) {
+        self.change_cipher_spec.handshake_complete();
+    }
+
+    /// Reads the record at the start of `buf`, decrypting it in place once records are
+    /// protected.
+    ///
+    /// On failure, the error is the alert to send to the client.
+    pub fn read<'b>(&mut self, buf: &'b mut [u8]) -> TlsResult<Read<'b>> {
+        let header = match RecordHeader::decode(&mut Decoder::new(buf)) {
+            Some(header) => header,
+            None => return Ok(Read::Incomplete),
+        };
+        let max_len = match self.protection {
+            Some(ref prot) => prot.max_ciphertext_len(),
+            None => MAX_FRAGMENT_LEN,
+        };
+        if usize::from(header.len) > max_len {
+            return Err(TlsError::RecordOverflow);
+        }
+
+        let len = RECORD_HEADER_SIZE + usize::from(header.len);
+        if buf.len() < len {
+            return Ok(Read::Incomplete);
+        }
+        let record = &mut buf[..len];
+
+        // The ChangeCipherSpec stays unprotected even once the handshake is, and can only follow
+        // the ClientHello.
+        if header.ty == ContentType::ChangeCipherSpec {
+            if !self.client_hello_received {
+                return Err(TlsError::UnexpectedMessage);
+            }
+            self.change_cipher_spec
+                .discard(&record[RECORD_HEADER_SIZE..])?;
+            return Ok(Read::Discarded { len });
+        }
+
+        let (ty, content) = match self.protection {
+            Some(ref mut prot) => prot.open(record)?,
+            None => (header.ty, &record[RECORD_HEADER_SIZE..]),
+        };
+        if ty == ContentType::ChangeCipherSpec {
+            return Err(TlsError::UnexpectedMessage);
+        }
+        if ty == ContentType::Handshake {
+            self.client_hello_received = true;
+        }
+        Ok(Read::Record { len, ty, content })
+    }
+}
+
+impl<A: Aead> Default for RecordReader<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mtls_message::testing::RingAead;
+    use std::vec::Vec;
+
+    fn record(ty: ContentType, content: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![u8::from(ty), 3, 3];
+        bytes.extend_from_slice(&(content.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(content);
+        bytes
+    }
+
+    fn protection() -> RecordProtection<RingAead> {
+        RecordProtection::new(RingAead::aes_128_gcm(&[7; 16]), [9; 12])
+    }
+
+    /// Reader that received a ClientHello.
+    fn reader() -> RecordReader<RingAead> {
+        let mut reader = RecordReader::new();
+        let mut hello = record(ContentType::Handshake, &[1, 0, 0, 0]);
+        reader.read(&mut hello).unwrap();
+        reader
+    }
+
+    fn read(reader: &mut RecordReader<RingAead>, bytes: &[u8]) -> TlsResult<Vec<u8>> {
+        let mut bytes = bytes.to_vec();
+        match reader.read(&mut bytes)? {
+            Read::Record { content, .. } => Ok(content.to_vec()),
+            read => panic!("unexpected {:?}", read),
+        }
+    }
+
+    #[test]
+    fn plaintext() {
+        let mut reader = RecordReader::<RingAead>::new();
+        let mut bytes = record(ContentType::Handshake, &[1, 0, 0, 0]);
+        bytes.extend_from_slice(&[0x16, 3]);
+
+        assert_eq!(
+            reader.read(&mut bytes).unwrap(),
+            Read::Record {
+                len: 9,
+                ty: ContentType::Handshake,
+                content: &[1, 0, 0, 0],
+            }
+        );
+    }
+
+    #[test]
+    fn incomplete() {
+        let bytes = record(ContentType::Handshake, &[1, 0, 0, 0]);
+        for len in [0, 4, 8] {
+            let mut bytes = bytes[..len].to_vec();
+            assert_eq!(reader().read(&mut bytes).unwrap(), Read::Incomplete);
+        }
+    }
+
+    #[test]
+    fn overflow() {
+        let mut bytes = vec![0x16, 3, 3, 0x40, 0x01];
+
+        assert!(matches!(
+            reader().read(&mut bytes),
+            Err(TlsError::RecordOverflow)
+        ));
+    }
+
+    #[test]
+    fn discards_change_cipher_spec() {
+        let mut reader = reader();
+        let mut bytes = record(ContentType::ChangeCipherSpec, &[1]);
+
+        assert_eq!(reader.read(&mut bytes).unwrap(), Read::Discarded { len: 6 });
+    }
+
+    #[test]
+    fn change_cipher_spec_when_protected() {
+        let mut reader = reader();
+        reader.set_protection(protection());
+        let mut bytes = record(ContentType::ChangeCipherSpec, &[1]);
+        assert_eq!(reader.read(&mut bytes).unwrap(), Read::Discarded { len: 6 });
+
+        let mut sealed = vec![0; 64];
+        sealed[RECORD_HEADER_SIZE..RECORD_HEADER_SIZE + 3].copy_from_slice(&[1, 2, 3]);
+        let len = protection()
+            .seal(ContentType::Handshake, &mut sealed, 3)
+            .unwrap();
+        assert_eq!(read(&mut reader, &sealed[..len]).unwrap(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn second_change_cipher_spec() {
+        let mut reader = reader();
+        let bytes = record(ContentType::ChangeCipherSpec, &[1]);
+        reader.read(&mut bytes.clone()).unwrap();
+
+        assert!(matches!(
+            reader.read(&mut bytes.clone()),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn invalid_change_cipher_spec() {
+        for content in [&[2][..], &[1, 1], &[]] {
+            let mut bytes = record(ContentType::ChangeCipherSpec, content);
+
+            assert!(matches!(
+                reader().read(&mut bytes),
+                Err(TlsError::UnexpectedMessage)
+            ));
+        }
+    }
+
+    #[test]
+    fn change_cipher_spec_before_client_hello() {
+        let mut bytes = record(ContentType::ChangeCipherSpec, &[1]);
+
+        assert!(matches!(
+            RecordReader::<RingAead>::new().read(&mut bytes),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn change_cipher_spec_after_handshake() {
+        let mut reader = reader();
+        reader.complete_handshake();
+        let mut bytes = record(ContentType::ChangeCipherSpec, &[1]);
+
+        assert!(matches!(
+            reader.read(&mut bytes),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+
+    #[test]
+    fn protected_change_cipher_spec() {
+        let mut reader = reader();
+        reader.set_protection(protection());
+        let mut sealed = vec![0; 64];
+        sealed[RECORD_HEADER_SIZE] = 1;
+        let len = protection()
+            .seal(ContentType::ChangeCipherSpec, &mut sealed, 1)
+            .unwrap();
+
+        assert!(matches!(
+            reader.read(&mut sealed[..len]),
+            Err(TlsError::UnexpectedMessage)
+        ));
+    }
+}
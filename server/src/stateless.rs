@@ -0,0 +1,609 @@
+use crate::negotiation::{KeyExchange, Negotiated};
+use core::convert::TryFrom;
+use mtls_message::{
+    crypto::{Aead, Digest, Hash, MAX_DIGEST_LEN, NONCE_LEN},
+    error::{Error as TlsError, Result as TlsResult},
+    handshake::{
+        client::ClientHelloPayload,
+        enums::{CipherSuite, HandshakeType, HashAlgorithm, NamedGroup, ProtocolVersion},
+        extension::{client::ClientExtension, server::ServerExtension, Extensions},
+        message::{HandshakeMessage, HandshakePayload},
+        server::ServerHelloPayload,
+        session::SessionId,
+        transcript::Transcript,
+    },
+    Codec, Decoder, Encoder,
+};
+
+/// Longest tag of a cookie key's AEAD.
+const MAX_TAG_LEN: usize = 16;
+
+/// Cipher suite, group, and length and bytes of the ClientHello1 hash.
+const MAX_STATE_LEN: usize = 2 + 2 + 1 + MAX_DIGEST_LEN;
+
+/// Key id, nonce, sealed state and tag.
+pub const MAX_COOKIE_LEN: usize = 1 + NONCE_LEN + MAX_STATE_LEN + MAX_TAG_LEN;
+
+/// Handshake header, legacy fields and the supported_versions, key_share and cookie extensions.
+const MAX_HELLO_RETRY_LEN: usize = 4 + 2 + 32 + 33 + 2 + 1 + 2 + 6 + 6 + 6 + MAX_COOKIE_LEN;
+
+/// State of a handshake paused by a stateless HelloRetryRequest, carried by the client in the
+/// cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryState {
+    pub cipher_suite: CipherSuite,
+    pub group: NamedGroup,
+    /// Hash of ClientHello1, with the hash algorithm of `cipher_suite`.
+    pub client_hello_hash: Digest,
+}
+
+/// Sealed `RetryState`, to put in a HelloRetryRequest.
+#[derive(Debug, Clone, Copy)]
+pub struct Cookie {
+    data: [u8; MAX_COOKIE_LEN],
+    len: usize,
+}
+
+impl AsRef<[u8]> for Cookie {
+    fn as_ref(&self) -> &[u8] {
+        &self.data[..self.len]
+    }
+}
+
+/// Keys cookies are sealed with.
+///
+/// Cookies sealed with the current or the previous key are accepted, so rotating the key
+/// regularly expires old cookies.
+pub struct CookieKeys<A: Aead> {
+    current: A,
+    current_id: u8,
+    previous: Option<A>,
+}
+
+impl<A: Aead> CookieKeys<A> {
+    pub fn new(key: A) -> Self {
+        Self {
+            current: key,
+            current_id: 0,
+            previous: None,
+        }
+    }
+
+    /// Makes `key` the key new cookies are sealed with. Cookies sealed with the key it replaces
+    /// are still accepted, and those sealed with any older key no longer are.
+    pub fn rotate(&mut self, key: A) {
+        self.previous = Some(core::mem::replace(&mut self.current, key));
+        self.current_id = self.current_id.wrapping_add(1);
+    }
+
+    /// Seals `state` with the current key, bound to the client's legacy session id.
+    ///
+    /// `nonce` must never be used twice with the same key, so should be freshly random.
+    pub fn seal(
+        &self,
+        state: &RetryState,
+        session_id: &SessionId,
+        nonce: [u8; NONCE_LEN],
+    ) -> TlsResult<Cookie> {
+        let tag_len = self.current.tag_len();
+        if tag_len > MAX_TAG_LEN {
+            return Err(TlsError::InternalError("cookie key tag too long"));
+        }
+
+        let mut data = [0; MAX_COOKIE_LEN];
+        data[0] = self.current_id;
+        data[1..=NONCE_LEN].copy_from_slice(&nonce);
+
+        let start = 1 + NONCE_LEN;
+        let state_len = {
+            let mut enc = Encoder::new(&mut data[start..start + MAX_STATE_LEN]);
+            state.cipher_suite.encode(&mut enc)?;
+            state.group.encode(&mut enc)?;
+            (state.client_hello_hash.len() as u8).encode(&mut enc)?;
+            enc.append(state.client_hello_hash)?;
+            enc.bytes().len()
+        };
+        let len = start + state_len + tag_len;
+
+        let (aad, aad_len) = cookie_aad(self.current_id, session_id);
+        self.current
+            .seal_in_place(&nonce, &aad[..aad_len], &mut data[start..len])?;
+        Ok(Cookie { data, len })
+    }
+
+    /// Opens a cookie sealed by `seal` for a client with `session_id`.
+    ///
+    /// On failure, the error is the alert to send to the client.
+    pub fn open(&self, cookie: &[u8], session_id: &SessionId) -> TlsResult<RetryState> {
+        let start = 1 + NONCE_LEN;
+        if cookie.len() <= start || cookie.len() > MAX_COOKIE_LEN {
+            return Err(TlsError::IllegalParameter);
+        }
+
+        let id = cookie[0];
+        let key = if id == self.current_id {
+            &self.current
+        } else if id == self.current_id.wrapping_sub(1) {
+            self.previous.as_ref().ok_or(TlsError::IllegalParameter)?
+        } else {
+            return Err(TlsError::IllegalParameter);
+        };
+
+        let mut nonce = [0; NONCE_LEN];
+        nonce.copy_from_slice(&cookie[1..start]);
+        let mut data = [0; MAX_COOKIE_LEN];
+        let sealed = &mut data[..cookie.len() - start];
+        sealed.copy_from_slice(&cookie[start..]);
+
+        let (aad, aad_len) = cookie_aad(id, session_id);
+        let len = key
+            .open_in_place(&nonce, &aad[..aad_len], sealed)
+            .map_err(|_| TlsError::IllegalParameter)?;
+
+        decode_state(&sealed[..len]).ok_or(TlsError::IllegalParameter)
+    }
+}
+
+/// Additional data authenticated with a cookie: its key id and the client's legacy session id.
+fn cookie_aad(id: u8, session_id: &SessionId) -> ([u8; 33], usize) {
+    let session_id = session_id.as_bytes();
+    let mut aad = [0; 33];
+    aad[0] = id;
+    aad[1..=session_id.len()].copy_from_slice(session_id);
+    (aad, 1 + session_id.len())
+}
+
+fn decode_state(bytes: &[u8]) -> Option<RetryState> {
+    let mut dec = Decoder::new(bytes);
+    let cipher_suite = CipherSuite::decode(&mut dec)?;
+    let group = NamedGroup::decode(&mut dec)?;
+    let hash_len = u8::decode(&mut dec)?;
+    let hash = dec.take(usize::from(hash_len))?;
+    if !dec.is_complete() {
+        return None;
+    }
+
+    Some(RetryState {
+        cipher_suite,
+        group,
+        client_hello_hash: Digest::try_from(hash).ok()?,
+    })
+}
+
+/// Sends a HelloRetryRequest without keeping any state: the hash of ClientHello1 and the
+/// negotiated parameters are sealed into its cookie, and the HelloRetryRequest is encoded to
+/// `enc`.
+///
+/// `client_hello` is the encoded ClientHello1 message, including its header, and `new_hash`
+/// makes the hash context for the hash algorithm of the negotiated cipher suite.
+pub fn hello_retry<H: Hash, A: Aead>(
+    client_hello: &[u8],
+    hello: &ClientHelloPayload<'_>,
+    negotiated: &Negotiated<'_>,
+    new_hash: fn(HashAlgorithm) -> Option<H>,
+    keys: &CookieKeys<A>,
+    nonce: [u8; NONCE_LEN],
+    enc: &mut Encoder<'_>,
+) -> TlsResult<()> {
+    let group = match negotiated.key_exchange {
+        KeyExchange::HelloRetry(group) => group,
+        KeyExchange::Share(_) => {
+            return Err(TlsError::InternalError("no HelloRetryRequest needed"));
+        }
+    };
+
+    let mut hash = new_hash(hash_algorithm(negotiated.cipher_suite)?)
+        .ok_or(TlsError::InternalError("no hash for cipher suite"))?;
+    hash.update(client_hello);
+    let state = RetryState {
+        cipher_suite: negotiated.cipher_suite,
+        group,
+        client_hello_hash: hash.finish(),
+    };
+    let cookie = keys.seal(&state, hello.session_id(), nonce)?;
+
+    encode_hello_retry_request(hello.session_id(), &state, cookie.as_ref(), enc)
+}
+
+/// Restores a handshake from ClientHello2 of a client that was sent a stateless
+/// HelloRetryRequest.
+///
+/// Checks the cookie and that ClientHello2 follows the HelloRetryRequest, then starts
+/// `transcript` with the hash context `new_hash` makes for the cipher suite in the cookie, and
+/// adds ClientHello1 and the HelloRetryRequest to it, ready for ClientHello2 to be added. On
+/// failure, the error is the alert to send to the client.
+pub fn resume<H: Hash, A: Aead>(
+    hello: &ClientHelloPayload<'_>,
+    keys: &CookieKeys<A>,
+    new_hash: fn(HashAlgorithm) -> Option<H>,
+    transcript: &mut Transcript<'_, H>,
+) -> TlsResult<RetryState> {
+    let cookie = hello
+        .extensions()
+        .iter()
+        .find_map(|ext| match *ext.as_ref() {
+            ClientExtension::Cookie(cookie) => Some(cookie),
+            _ => None,
+        })
+        .ok_or(TlsError::MissingExtension)?;
+    let state = keys.open(cookie, hello.session_id())?;
+
+    if !hello
+        .cipher_suites()
+        .iter()
+        .any(|suite| suite == state.cipher_suite)
+    {
+        return Err(TlsError::IllegalParameter);
+    }
+    // The client must replace its key shares with one for the requested group (RFC 8446,
+    // section 4.2.8).
+    let shares_requested_group = hello.extensions().iter().any(|ext| match ext.as_ref() {
+        ClientExtension::KeyShare(shares) => {
+            let mut shares = shares.iter();
+            shares
+                .next()
+                .is_some_and(|share| share.as_ref().group == state.group)
+                && shares.next().is_none()
+        }
+        _ => false,
+    });
+    if !shares_requested_group {
+        return Err(TlsError::IllegalParameter);
+    }
+
+    // The hash must be one the cipher suite's hash algorithm makes, even if the cookie was
+    // sealed with a different configuration.
+    let algorithm = hash_algorithm(state.cipher_suite)?;
+    if state.client_hello_hash.len() != algorithm.output_len() {
+        return Err(TlsError::IllegalParameter);
+    }
+    let hash = new_hash(algorithm).ok_or(TlsError::InternalError("no hash for cipher suite"))?;
+
+    transcript.resume_hello_retry(hash, &state.client_hello_hash)?;
+    let mut buf = [0; MAX_HELLO_RETRY_LEN];
+    let mut enc = Encoder::new(buf.as_mut());
+    encode_hello_retry_request(hello.session_id(), &state, cookie, &mut enc)?;
+    transcript.add(enc.bytes())?;
+
+    Ok(state)
+}
+
+fn hash_algorithm(cipher_suite: CipherSuite) -> TlsResult<HashAlgorithm> {
+    cipher_suite
+        .hash()
+        .ok_or(TlsError::InternalError("unknown cipher suite"))
+}
+
+fn encode_hello_retry_request(
+    session_id: &SessionId,
+    state: &RetryState,
+    cookie: &[u8],
+    enc: &mut Encoder<'_>,
+) -> TlsResult<()> {
+    let extensions = [
+        ServerExtension::SupportedVersions(ProtocolVersion::TLSv1_3),
+        ServerExtension::HelloRetryKeyShare(state.group),
+        ServerExtension::Cookie(cookie),
+    ];
+    let mut buf = [0; MAX_HELLO_RETRY_LEN];
+    let mut msg_enc = Encoder::new(buf.as_mut());
+    HandshakeMessage {
+        ty: HandshakeType::ServerHello,
+        payload: HandshakePayload::ServerHello(ServerHelloPayload::hello_retry_request(
+            *session_id,
+            state.cipher_suite,
+            Extensions::from(&extensions[..]),
+        )),
+    }
+    .encode(&mut msg_enc)?;
+
+    enc.append(msg_enc.bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::negotiation::{negotiate, CertificateKey, Policy, Preference};
+    use mtls_message::{
+        handshake::enums::SignatureScheme,
+        testing::{new_hash, RingAead, Sha256},
+    };
+    use rustls::{
+        internal::msgs::{
+            base::PayloadU16 as r_PayloadU16,
+            codec::Codec as r_Codec,
+            enums::{
+                Compression as r_Compression, HandshakeType as r_HandshakeType,
+                NamedGroup as r_NamedGroup,
+            },
+            handshake::{
+                ClientExtension as r_ClientExtension, ClientHelloPayload as r_ClientHelloPayload,
+                HandshakeMessagePayload as r_HandshakeMessagePayload,
+                HandshakePayload as r_HandshakePayload,
+                HelloRetryExtension as r_HelloRetryExtension,
+                HelloRetryRequest as r_HelloRetryRequest, KeyShareEntry as r_KeyShareEntry,
+                Random as r_Random, SessionID as r_SessionId,
+            },
+        },
+        CipherSuite as r_CipherSuite, ProtocolVersion as r_ProtocolVersion,
+        SignatureScheme as r_SignatureScheme,
+    };
+    use std::vec::Vec;
+
+    const POLICY: Policy<'static> = Policy {
+        cipher_suites: &[CipherSuite::TlsAes128GcmSha256],
+        cipher_suite_preference: Preference::Server,
+        groups: &[NamedGroup::X25519],
+        signature_schemes: &[SignatureScheme::EcdsaNistp256Sha256],
+        certificate_key: CertificateKey::EcdsaNistp256,
+    };
+
+    const NONCE: [u8; NONCE_LEN] = [5; NONCE_LEN];
+
+    fn state() -> RetryState {
+        RetryState {
+            cipher_suite: CipherSuite::TlsAes128GcmSha256,
+            group: NamedGroup::X25519,
+            client_hello_hash: Digest::try_from(&[9; 32][..]).unwrap(),
+        }
+    }
+
+    fn client_hello(share: r_NamedGroup, cookie: Option<Vec<u8>>) -> Vec<u8> {
+        let mut extensions = vec![
+            r_ClientExtension::SupportedVersions(vec![r_ProtocolVersion::TLSv1_3]),
+            r_ClientExtension::NamedGroups(vec![r_NamedGroup::secp256r1, r_NamedGroup::X25519]),
+            r_ClientExtension::KeyShare(vec![r_KeyShareEntry::new(share, &[1, 2, 3])]),
+            r_ClientExtension::SignatureAlgorithms(vec![r_SignatureScheme::ECDSA_NISTP256_SHA256]),
+        ];
+        if let Some(cookie) = cookie {
+            extensions.push(r_ClientExtension::Cookie(r_PayloadU16::new(cookie)));
+        }
+
+        let mut bytes = vec![];
+        r_HandshakeMessagePayload {
+            typ: r_HandshakeType::ClientHello,
+            payload: r_HandshakePayload::ClientHello(r_ClientHelloPayload {
+                client_version: r_ProtocolVersion::TLSv1_2,
+                random: r_Random::from_slice(&[0; 32]),
+                session_id: r_SessionId::new(&[4; 32]),
+                cipher_suites: vec![r_CipherSuite::TLS13_AES_128_GCM_SHA256],
+                compression_methods: vec![r_Compression::Null],
+                extensions,
+            }),
+        }
+        .encode(&mut bytes);
+        bytes
+    }
+
+    fn payload(msg: &[u8]) -> ClientHelloPayload<'_> {
+        ClientHelloPayload::decode(&mut Decoder::new(&msg[4..])).unwrap()
+    }
+
+    fn first_flight(keys: &CookieKeys<RingAead>) -> (Vec<u8>, Vec<u8>) {
+        let ch1 = client_hello(r_NamedGroup::secp256r1, None);
+        let hello = payload(&ch1);
+        let negotiated = negotiate(&hello, &POLICY).unwrap();
+        let mut enc = Encoder::new(vec![]);
+        hello_retry(&ch1, &hello, &negotiated, new_hash, keys, NONCE, &mut enc).unwrap();
+
+        (ch1, enc.bytes().into())
+    }
+
+    fn hello_retry_cookie(hrr: &[u8]) -> Vec<u8> {
+        match HandshakeMessage::decode(&mut Decoder::new(hrr))
+            .unwrap()
+            .payload
+        {
+            HandshakePayload::ServerHello(payload) => payload
+                .extensions()
+                .iter()
+                .find_map(|ext| match *ext.as_ref() {
+                    ServerExtension::Cookie(cookie) => Some(cookie.to_vec()),
+                    _ => None,
+                })
+                .unwrap(),
+            _ => panic!("not a ServerHello"),
+        }
+    }
+
+    #[test]
+    fn seal_open() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let session_id = SessionId::from([4; 32]);
+        let cookie = keys.seal(&state(), &session_id, NONCE).unwrap();
+
+        assert_eq!(cookie.as_ref().len(), 1 + NONCE_LEN + 5 + 32 + 16);
+        assert_eq!(keys.open(cookie.as_ref(), &session_id).unwrap(), state());
+    }
+
+    #[test]
+    fn open_different_session_id() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let cookie = keys
+            .seal(&state(), &SessionId::from([4; 32]), NONCE)
+            .unwrap();
+
+        assert!(matches!(
+            keys.open(cookie.as_ref(), &SessionId::empty()),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn open_tampered() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let session_id = SessionId::empty();
+        let cookie = keys.seal(&state(), &session_id, NONCE).unwrap();
+        let mut tampered = cookie.as_ref().to_vec();
+        tampered[20] ^= 1;
+
+        assert!(matches!(
+            keys.open(&tampered, &session_id),
+            Err(TlsError::IllegalParameter)
+        ));
+        assert!(matches!(
+            keys.open(&tampered[..10], &session_id),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn rotation() {
+        let mut keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let session_id = SessionId::empty();
+        let cookie = keys.seal(&state(), &session_id, NONCE).unwrap();
+
+        keys.rotate(RingAead::aes_128_gcm(&[2; 16]));
+        assert_eq!(keys.open(cookie.as_ref(), &session_id).unwrap(), state());
+        let rotated = keys.seal(&state(), &session_id, NONCE).unwrap();
+        assert_ne!(rotated.as_ref(), cookie.as_ref());
+
+        keys.rotate(RingAead::aes_128_gcm(&[3; 16]));
+        assert!(matches!(
+            keys.open(cookie.as_ref(), &session_id),
+            Err(TlsError::IllegalParameter)
+        ));
+        assert_eq!(keys.open(rotated.as_ref(), &session_id).unwrap(), state());
+    }
+
+    #[test]
+    fn hello_retry_request_encoding() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let (_, hrr) = first_flight(&keys);
+        let cookie = hello_retry_cookie(&hrr);
+
+        let mut expected = vec![];
+        r_HandshakeMessagePayload {
+            typ: r_HandshakeType::HelloRetryRequest,
+            payload: r_HandshakePayload::HelloRetryRequest(r_HelloRetryRequest {
+                legacy_version: r_ProtocolVersion::TLSv1_2,
+                session_id: r_SessionId::new(&[4; 32]),
+                cipher_suite: r_CipherSuite::TLS13_AES_128_GCM_SHA256,
+                extensions: vec![
+                    r_HelloRetryExtension::SupportedVersions(r_ProtocolVersion::TLSv1_3),
+                    r_HelloRetryExtension::KeyShare(r_NamedGroup::X25519),
+                    r_HelloRetryExtension::Cookie(r_PayloadU16::new(cookie)),
+                ],
+            }),
+        }
+        .encode(&mut expected);
+
+        assert_eq!(hrr, expected);
+    }
+
+    #[test]
+    fn hello_retry_not_needed() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let ch1 = client_hello(r_NamedGroup::X25519, None);
+        let hello = payload(&ch1);
+        let negotiated = negotiate(&hello, &POLICY).unwrap();
+
+        assert!(hello_retry(
+            &ch1,
+            &hello,
+            &negotiated,
+            new_hash,
+            &keys,
+            NONCE,
+            &mut Encoder::new(vec![]),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn resume_transcript() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let (ch1, hrr) = first_flight(&keys);
+        let ch2 = client_hello(r_NamedGroup::X25519, Some(hello_retry_cookie(&hrr)));
+
+        let mut transcript = Transcript::new(vec![]);
+        let state = resume(&payload(&ch2), &keys, new_hash, &mut transcript).unwrap();
+        transcript.add(&ch2).unwrap();
+
+        let mut expected = Transcript::new(vec![]);
+        expected.add(&ch1).unwrap();
+        expected.start_hash(Sha256::default()).unwrap();
+        expected.hello_retry().unwrap();
+        expected.add(&hrr).unwrap();
+        expected.add(&ch2).unwrap();
+
+        assert_eq!(state.group, NamedGroup::X25519);
+        assert_eq!(state.cipher_suite, CipherSuite::TlsAes128GcmSha256);
+        assert_eq!(
+            transcript.current_hash().unwrap(),
+            expected.current_hash().unwrap()
+        );
+    }
+
+    #[test]
+    fn resume_without_cookie() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let ch2 = client_hello(r_NamedGroup::X25519, None);
+
+        assert!(matches!(
+            resume(
+                &payload(&ch2),
+                &keys,
+                new_hash,
+                &mut Transcript::new(vec![])
+            ),
+            Err(TlsError::MissingExtension)
+        ));
+    }
+
+    #[test]
+    fn resume_wrong_key_share() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let (_, hrr) = first_flight(&keys);
+        let ch2 = client_hello(r_NamedGroup::secp256r1, Some(hello_retry_cookie(&hrr)));
+
+        assert!(matches!(
+            resume(
+                &payload(&ch2),
+                &keys,
+                new_hash,
+                &mut Transcript::new(vec![])
+            ),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn resume_forged_cookie() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let other_keys = CookieKeys::new(RingAead::aes_128_gcm(&[2; 16]));
+        let (_, hrr) = first_flight(&other_keys);
+        let ch2 = client_hello(r_NamedGroup::X25519, Some(hello_retry_cookie(&hrr)));
+
+        assert!(matches!(
+            resume(
+                &payload(&ch2),
+                &keys,
+                new_hash,
+                &mut Transcript::new(vec![])
+            ),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+
+    #[test]
+    fn resume_hash_length_mismatch() {
+        let keys = CookieKeys::new(RingAead::aes_128_gcm(&[1; 16]));
+        let state = RetryState {
+            client_hello_hash: Digest::try_from(&[9; 48][..]).unwrap(),
+            ..state()
+        };
+        let cookie = keys.seal(&state, &SessionId::from([4; 32]), NONCE).unwrap();
+        let ch2 = client_hello(r_NamedGroup::X25519, Some(cookie.as_ref().to_vec()));
+
+        assert!(matches!(
+            resume(
+                &payload(&ch2),
+                &keys,
+                new_hash,
+                &mut Transcript::new(vec![])
+            ),
+            Err(TlsError::IllegalParameter)
+        ));
+    }
+}